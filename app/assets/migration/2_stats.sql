@@ -1,9 +1,9 @@
-CREATE TABLE Migration (
+CREATE TABLE IF NOT EXISTS Migration (
     file_name NVARCHAR(50) NOT NULL PRIMARY KEY,
     recorded_on INTEGER NOT NULL
 );
 
-CREATE TABLE Player (
+CREATE TABLE IF NOT EXISTS Player (
     character_id INTEGER NOT NULL PRIMARY KEY,
     name NVARCHAR(50) NOT NULL,
     class_id INTEGER NOT NULL,
@@ -12,9 +12,9 @@ CREATE TABLE Player (
     updated_on INTEGER NOT NULL
 );
 
-CREATE INDEX IX_Players_name ON Player(name);
+CREATE INDEX IF NOT EXISTS IX_Players_name ON Player(name);
 
-CREATE TABLE Raid (
+CREATE TABLE IF NOT EXISTS Raid (
     id SMALLINT PRIMARY KEY,
     created_on INTEGER NOT NULL,
     name NVARCHAR(50) NOT NULL,
@@ -23,7 +23,7 @@ CREATE TABLE Raid (
     npc_ids BLOB NOT NULL
 );
 
-CREATE TABLE Player_stats (
+CREATE TABLE IF NOT EXISTS Player_stats (
     character_id INTEGER NOT NULL PRIMARY KEY,
     raid_id INTEGER NOT NULL,
     encounter_id INTEGER NOT NULL,
@@ -36,7 +36,7 @@ CREATE TABLE Player_stats (
     identity_uptime REAL NOT NULL
 );
 
-CREATE TABLE Damage_log (
+CREATE TABLE IF NOT EXISTS Damage_log (
     encounter_id INTEGER NOT NULL,
     character_id INTEGER NOT NULL,
     value BLOB,
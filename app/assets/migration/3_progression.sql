@@ -0,0 +1,55 @@
+-- the stats tables from 2_stats.sql were never written to, so they can be rebuilt safely
+DROP TABLE IF EXISTS Player_stats;
+DROP TABLE IF EXISTS Damage_log;
+DROP TABLE IF EXISTS Raid;
+
+CREATE TABLE Raid (
+    id INTEGER PRIMARY KEY,
+    created_on INTEGER NOT NULL,
+    name NVARCHAR(50) NOT NULL UNIQUE,
+    zone_name NVARCHAR(50) NULL,
+    gate TINYINT NULL,
+    npc_ids BLOB NULL
+);
+
+CREATE TABLE Player_history (
+    character_id INTEGER NOT NULL,
+    encounter_id INTEGER NOT NULL,
+    name NVARCHAR(50) NOT NULL,
+    class_id INTEGER NOT NULL,
+    gear_score REAL NOT NULL,
+    created_on INTEGER NOT NULL,
+    PRIMARY KEY (character_id, encounter_id),
+    FOREIGN KEY (encounter_id) REFERENCES encounter (id) ON DELETE CASCADE
+);
+
+CREATE TABLE Player_stats (
+    character_id INTEGER NOT NULL,
+    encounter_id INTEGER NOT NULL,
+    raid_id INTEGER NOT NULL,
+    difficulty NVARCHAR(20) NOT NULL,
+    class_id INTEGER NOT NULL,
+    spec NVARCHAR(50) NULL,
+    gear_score REAL NOT NULL,
+    cleared BOOLEAN NOT NULL,
+    created_on INTEGER NOT NULL,
+    duration INTEGER NOT NULL,
+    total_damage INTEGER NOT NULL,
+    dps INTEGER NOT NULL,
+    brand_uptime REAL NOT NULL,
+    attack_power_uptime REAL NOT NULL,
+    identity_uptime REAL NOT NULL,
+    PRIMARY KEY (character_id, encounter_id),
+    FOREIGN KEY (encounter_id) REFERENCES encounter (id) ON DELETE CASCADE,
+    FOREIGN KEY (raid_id) REFERENCES Raid (id)
+);
+
+CREATE INDEX IX_Player_stats_progression ON Player_stats(character_id, raid_id, difficulty, created_on);
+
+CREATE TABLE Damage_log (
+    encounter_id INTEGER NOT NULL,
+    character_id INTEGER NOT NULL,
+    value BLOB,
+    PRIMARY KEY (encounter_id, character_id),
+    FOREIGN KEY (encounter_id) REFERENCES encounter (id) ON DELETE CASCADE
+);
@@ -0,0 +1,3 @@
+ALTER TABLE encounter_preview ADD COLUMN personal_best BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE encounter_preview ADD COLUMN personal_percentile REAL NULL;
+ALTER TABLE encounter_preview ADD COLUMN local_percentile REAL NULL;
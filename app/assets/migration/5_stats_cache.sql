@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS Player_stats_cache (
+    name NVARCHAR(50) NOT NULL,
+    region NVARCHAR(20) NOT NULL,
+    fetched_on INTEGER NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY (name, region)
+);
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS Player_info_queue (
+    encounter_id INTEGER PRIMARY KEY,
+    request TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_on INTEGER NOT NULL,
+    last_error TEXT NULL,
+    created_on INTEGER NOT NULL,
+    FOREIGN KEY (encounter_id) REFERENCES encounter (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS IX_Player_info_queue_next_attempt ON Player_info_queue(next_attempt_on);
@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS Blob_dictionary (
+    id INTEGER PRIMARY KEY,
+    dictionary BLOB NOT NULL,
+    samples INTEGER NOT NULL,
+    created_on INTEGER NOT NULL
+);
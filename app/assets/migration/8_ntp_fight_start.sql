@@ -0,0 +1,2 @@
+ALTER TABLE encounter_preview ADD COLUMN ntp_fight_start INTEGER NULL;
+CREATE INDEX IF NOT EXISTS IX_encounter_preview_ntp_fight_start ON encounter_preview(ntp_fight_start);
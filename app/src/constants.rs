@@ -1,13 +1,56 @@
 use tauri_plugin_window_state::StateFlags;
 
 pub const DB_VERSION: i32 = 5;
+pub const EXPORT_VERSION: u32 = 1;
+pub const SETTINGS_VERSION: u32 = 1;
 pub const TIMEOUT_DELAY_MS: i64 = 1000;
 pub const WORKSHOP_BUFF_ID: u32 = 9701;
 pub const WINDOW_MS: i64 = 5_000;
 pub const WINDOW_S: i64 = 5;
 pub const PORT: u16 = 6040;
+pub const API_PORT: u16 = 6041;
+pub const LIVE_EVENT_CAPACITY: usize = 64;
+pub const PLAYER_INFO_RETRY_POLL_SECS: u64 = 30;
+pub const PLAYER_INFO_RETRY_BASE_SECS: u64 = 30;
+pub const PLAYER_INFO_RETRY_MAX_SECS: u64 = 6 * 3600;
+pub const PLAYER_INFO_MAX_ATTEMPTS: u32 = 10;
+pub const NOTIFICATION_RETRY_BASE_MS: u64 = 2_000;
+pub const NOTIFICATION_MAX_ATTEMPTS: u32 = 5;
+pub const RETENTION_STARTUP_DELAY_SECS: u64 = 120;
+pub const BACKUP_POLL_SECS: u64 = 3600;
+pub const BACKUP_PAGES_PER_STEP: i32 = 256;
+pub const BACKUP_STEP_PAUSE_MS: u64 = 10;
+pub const BLOB_COMPRESSION_LEVEL: i32 = 9;
+pub const DICTIONARY_SIZE: usize = 112 * 1024;
+pub const DICTIONARY_MIN_SAMPLES: usize = 500;
+pub const DICTIONARY_MAX_SAMPLES: usize = 2000;
+pub const RECOMPRESS_STARTUP_DELAY_SECS: u64 = 300;
+pub const RECOMPRESS_BATCH_SIZE: usize = 200;
+pub const RECOMPRESS_PAUSE_MS: u64 = 100;
+pub const RANKING_BACKFILL_STARTUP_DELAY_SECS: u64 = 60;
+pub const RANKING_BACKFILL_BATCH_SIZE: usize = 100;
+pub const RANKING_BACKFILL_PAUSE_MS: u64 = 100;
+pub const WORKER_ACK_TIMEOUT_MS: u64 = 5_000;
+pub const WORKER_POLL_MS: u64 = 100;
+pub const CHECKPOINT_INTERVAL_SECS: u64 = 15;
+pub const NTP_TIMEOUT_SECS: u64 = 5;
+pub const NTP_SERVER: &'static str = "pool.ntp.org:123";
+pub const CRASH_PACKET_CAPACITY: usize = 500;
+pub const DIAGNOSTICS_LATENCY_SAMPLES: usize = 4096;
+pub const DIAGNOSTICS_SAMPLE_BYTES: usize = 64;
+pub const DIAGNOSTICS_MAX_UNKNOWN_IDS: usize = 2048;
 pub const LOCAL_PLAYERS_NAME: &'static str = "local_players.json";
 pub const DB_NAME: &'static str = "encounters.db";
+pub const BACKUP_DIR_NAME: &'static str = "backups";
+pub const RECORDINGS_DIR_NAME: &'static str = "recordings";
+pub const CHECKPOINT_DIR_NAME: &'static str = "checkpoint";
+pub const CRASHES_DIR_NAME: &'static str = "crashes";
+pub const CRASH_REPORT_NAME: &'static str = "report.json";
+pub const CRASH_PACKETS_NAME: &'static str = "packets.bin";
+pub const PROFILES_NAME: &'static str = "profiles.json";
+pub const PROFILES_DIR_NAME: &'static str = "profiles";
+pub const DEFAULT_PROFILE: &'static str = "default";
+pub const INITIAL_MIGRATION: &'static str = "1_init.sql";
 pub const SETTINGS_NAME: &'static str = "settings.json";
 pub const REGION_NAME: &'static str = "current_region";
 pub const GAME_STEAM_URI: [&'static str; 3] = ["/C", "start", "steam://rungameid/1599340"];
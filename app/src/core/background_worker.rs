@@ -1,30 +1,39 @@
 use crate::core::utils::*;
+use crate::core::checkpoint::Checkpoint;
+use crate::core::decryption::{self, DamageDecryptor};
+use crate::core::diagnostics;
+use crate::core::control::{WorkerCommand, WorkerControl, WorkerState};
+use crate::constants::{CHECKPOINT_INTERVAL_SECS, NTP_SERVER, RECORDINGS_DIR_NAME, WORKER_POLL_MS};
 use crate::misc::app_context::AppContext;
-use crate::database::Database;
+use crate::misc::crash;
+use crate::database::{Database, DatabaseManager, SaveToDb};
 use crate::misc::data::AssetsPreloader;
-use crate::misc::flags::FlagsManager;
+use crate::misc::recorder::Recorder;
 use crate::core::encounter_state::EncounterState;
 use crate::core::handler::handle;
 use crate::core::stats_api::{StatsApi, API_URL};
 use crate::misc::local::LocalManager;
 use crate::misc::region::RegionManager;
-use crate::misc::settings::Settings;
-use crate::sniffer::PacketSniffer;
-use anyhow::Result;
+use crate::misc::settings::{Settings, SettingsManager};
+use crate::sniffer::{self, PacketSniffer, SnifferKind};
+use anyhow::{anyhow, Result};
 use chrono::{Duration, Utc};
-use log::{info, warn};
+use log::{error, info, warn};
 use meter_core::packets::opcodes::Pkt;
 use tokio::runtime::Runtime;
+use tokio::sync::watch;
 
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
 use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
 use tauri::{AppHandle, Emitter, EventTarget, Manager};
 
 pub struct BackgroundWorkerArgs {
-    pub packet_sniffer: Box<dyn PacketSniffer>,
+    pub control: WorkerControl,
+    pub sniffer: SnifferKind,
     pub version: String,
     pub app: AppHandle,
-    pub database: Arc<Database>,
+    pub database: Arc<DatabaseManager>,
     pub context: Arc<AppContext>,
     pub port: u16,
     pub settings: Settings
@@ -38,12 +47,20 @@ impl BackgroundWorker {
     }
 
     pub fn run(&mut self, args: BackgroundWorkerArgs) {
-        let handle = thread::spawn(|| {
-            let rt = Runtime::new()?;
-            rt.block_on(async {
-                Self::run_inner(args).await;
-            });
-            Ok(())
+        let status = args.control.status_sender();
+
+        let handle = thread::spawn(move || {
+            let result = Runtime::new()
+                .map_err(anyhow::Error::from)
+                .and_then(|rt| rt.block_on(Self::run_inner(args)));
+
+            // commands would otherwise only fail with "background worker stopped"
+            if let Err(err) = &result {
+                error!("background worker failed: {:?}", err);
+                status.send_modify(|status| status.state = WorkerState::Failed(format!("{:#}", err)));
+            }
+
+            result
         });
 
         if handle.is_finished() {
@@ -58,14 +75,16 @@ impl BackgroundWorker {
         let BackgroundWorkerArgs {
             app,
             context,
-            database,
-            mut packet_sniffer,
+            database: database_manager,
+            mut control,
+            sniffer: sniffer_kind,
             port,
             settings,
             version,
         } = args;
 
         info!("waiting for assets");
+        control.update_status(|status| status.state = WorkerState::WaitingForAssets);
         let asset_preloader = app.state::<Mutex<AssetsPreloader>>();
         let mut asset_preloader = asset_preloader.lock().unwrap();
         asset_preloader.wait_for_load().await;
@@ -73,88 +92,444 @@ impl BackgroundWorker {
 
         let mut state: EncounterState = EncounterState::new(version);
         let mut region_manager = RegionManager::new(context.region_path.clone());
-        let flags_manager = FlagsManager::new();
-        let mut local_manager = LocalManager::new(context.local_players_path.clone())?;
-        let mut stats_api = Arc::new(StatsApi::new());
+        let mut database = database_manager.get();
+        let mut generation = database_manager.generation();
+        let profile_paths = context.profile_paths(&database_manager.profile());
+        let mut local_manager = LocalManager::new(profile_paths.local_players_path)?;
+        let mut checkpoint = Checkpoint::new(profile_paths.checkpoint_path, context.migration_path.clone());
+        let mut checkpointed_on = state.clock.now();
+        let mut stats_api = Arc::new(StatsApi::new(settings.stats_api.clone(), database_manager.clone()));
+        let mut paused = false;
+        let mut recorder: Option<Recorder> = None;
 
-        let rx = packet_sniffer.start(port, context.region_path.to_string_lossy().to_string())?;
+        recover_checkpoint(&app, &mut checkpoint, &database, &context.version).await;
+        let (stats_api_sender, stats_api_receiver) = watch::channel(stats_api.clone());
+        tokio::spawn(retry_player_info(app.clone(), stats_api_receiver, database_manager.clone()));
 
-        let damage_handler = meter_core::decryption::DamageEncryptionHandler::new();
-        damage_handler.start()?;
-        
-        if settings.general.boss_only_damage {
-            flags_manager.set_boss_only_damage();
-            info!("boss only damage enabled")
-        }
+        let mut sniffer = start_sniffer(&control, sniffer_kind, port, &context.region_path.to_string_lossy());
 
-        if settings.general.low_performance_mode {
-            state.update_interval = Duration::milliseconds(1500);
-            info!("low performance mode enabled")
-        }
+        let mut damage_decryptor = create_decryptor(sniffer_kind, None)?;
+        
+        state.clock.sync(NTP_SERVER.to_string());
+        state.boss_only_damage = settings.general.boss_only_damage;
+        apply_settings(&mut state, &settings);
+        let mut settings_receiver = app.state::<Mutex<SettingsManager>>().lock().unwrap().subscribe();
 
         state.region = region_manager.get();
+        control.update_status(|status| {
+            status.boss_only_damage = state.boss_only_damage;
+            status.update_interval_ms = state.update_interval.num_milliseconds() as u64;
+        });
 
-        flags_manager.setup_listeners(&app);
-        let update_interval = Duration::seconds(2).to_std().unwrap();
+        let poll_interval = std::time::Duration::from_millis(WORKER_POLL_MS);
 
         loop {
-            let (op, data) = match rx.recv() {
-                Ok(result) => result,
-                Err(err) => {
-                    warn!("{err:?} {}", err.to_string());
-                    
+            // times out so commands are handled while the game is idle
+            let (op, data) = match sniffer.as_ref().map(|(_, rx)| rx.recv_timeout(poll_interval)) {
+                Some(Ok(result)) => result,
+                Some(Err(RecvTimeoutError::Timeout)) => (Pkt::Void, vec![]),
+                Some(Err(RecvTimeoutError::Disconnected)) => {
+                    warn!("packet sniffer stopped");
+                    control.update_status(|status| status.state = WorkerState::SnifferError("sniffer stopped".to_string()));
+                    sniffer = None;
+                    (Pkt::Void, vec![])
+                }
+                None => {
+                    std::thread::sleep(poll_interval);
                     (Pkt::Void, vec![])
-                },
+                }
             };
 
-            if flags_manager.invoked_reset() {
-                state.soft_reset(true);
+            // the profile was switched, encounters from now on go to its database
+            if database_manager.generation() != generation {
+                // the fight so far belongs to the profile it started in
+                if state.has_unsaved_fight() && let Some(model) = state.get_encounter(true) {
+                    info!("saving the ongoing encounter before switching profiles");
+                    save_to_db(app.clone(), stats_api.clone(), database.clone(), model);
+                    state.saved = true;
+                    state.is_resetting = true;
+                }
+
+                generation = database_manager.generation();
+                database = database_manager.get();
+
+                let profile_paths = context.profile_paths(&database_manager.profile());
+
+                // a broken file of the new profile shouldn't stop the meter
+                match LocalManager::new(profile_paths.local_players_path) {
+                    Ok(manager) => local_manager = manager,
+                    Err(err) => error!("could not read the local players of the new profile, keeping the previous ones: {:?}", err),
+                }
+
+                checkpoint = Checkpoint::new(profile_paths.checkpoint_path, context.migration_path.clone());
+                recover_checkpoint(&app, &mut checkpoint, &database, &context.version).await;
             }
 
-            if flags_manager.is_paused() {
-                continue;
+            if settings_receiver.has_changed().unwrap_or(false) {
+                let settings = settings_receiver.borrow_and_update().clone();
+                apply_settings(&mut state, &settings);
+                crash::set_settings(&settings);
+
+                // also how a profile switch picks up the stats api settings of the new profile
+                if settings.stats_api != *stats_api.settings() {
+                    stats_api = Arc::new(StatsApi::new(settings.stats_api.clone(), database_manager.clone()));
+                    stats_api_sender.send_replace(stats_api.clone());
+                }
+                control.update_status(|status| status.update_interval_ms = state.update_interval.num_milliseconds() as u64);
             }
 
-            if flags_manager.invoked_save() {
-                // state.party_info = state.get_party();
+            let mut shutdown = false;
+            let mut shutdown_ack = None;
 
-                if let Some(model) = state.get_encounter(true) {  
-                    save_to_db(app.clone(), stats_api.clone(), database.clone(), model);
+            while let Some((command, ack)) = control.try_recv() {
+                info!("worker command {:?}", command);
+
+                let result = dispatch(
+                    command,
+                    &mut state,
+                    &control,
+                    &mut paused,
+                    &mut sniffer,
+                    &mut damage_decryptor,
+                    &mut recorder,
+                    &context,
+                    |model| save_to_db(app.clone(), stats_api.clone(), database.clone(), model));
+
+                match result {
+                    Some(result) => {
+                        ack.send(result.map_err(|err| format!("{:#}", err))).ok();
+                    }
+                    None => {
+                        shutdown = true;
+                        // acked once the encounter is flushed
+                        shutdown_ack = Some(ack);
+                    }
+                }
+            }
+
+            if shutdown {
+                // nothing new comes in once the sniffer is gone
+                sniffer = None;
+                recorder = None;
+
+                if state.has_unsaved_fight() && let Some(model) = state.get_encounter(true) {
+                    info!("saving the ongoing encounter before shutting down");
+                    persist(app.clone(), stats_api.clone(), database.clone(), model, false).await;
                     state.saved = true;
-                    state.is_resetting = true;
                 }
+
+                if let Err(err) = checkpoint.clear().await {
+                    warn!("could not clear checkpoint: {:?}", err);
+                }
+
+                if let Some(ack) = shutdown_ack {
+                    ack.send(Ok(())).ok();
+                }
+
+                break;
             }
 
-            if flags_manager.toggled_boss_only_damage() {
-                state.boss_only_damage = true;
-            } else {
-                state.boss_only_damage = false;
+            if paused {
+                continue;
             }
 
-            let now = Utc::now();
-            if let Err(err) = handle(
+            if op != Pkt::Void {
+                crash::record_packet(op, &data);
+
+                if let Some(recorder) = recorder.as_mut() && let Err(err) = recorder.write(op, &data) {
+                    warn!("could not record packet: {}", err);
+                }
+            }
+
+            // one stamp per packet, everything the packet touches agrees on when it happened
+            let now = state.clock.now();
+            let handled_on = std::time::Instant::now();
+            let result = handle(
                 now,
                 op,
                 app.clone(),
                 &mut state,
                 &data,
-                &damage_handler,
+                damage_decryptor.as_ref(),
                 &mut local_manager,
                 &mut region_manager,
                 &stats_api,
-                database.clone()) {
-                warn!("An error occurred whilst parsing {}", err);
+                database.clone());
+
+            if op != Pkt::Void {
+                diagnostics::record_packet(op, data.len(), handled_on.elapsed());
+            }
+
+            if let Err(err) = result {
+                warn!("An error occurred whilst parsing {:?}: {}", op, err);
+                diagnostics::record_error(op, &err, &data);
+            }
+
+            // the query at startup can fail on a flaky connection, zone changes are rare enough to retry on
+            if op == Pkt::NewTransit && state.clock.ntp_offset().is_none() {
+                state.clock.sync(NTP_SERVER.to_string());
             }
 
             if let Some(data) = state.get_ongoing_encounter(now) {
                 app.emit_to(EventTarget::Any, "encounter-update", Some(&data))?;
+                crash::set_encounter(state.summary(paused));
             }
 
             if state.is_resetting {
                 state.soft_reset(true);
             }
+
+            if now - checkpointed_on >= Duration::seconds(CHECKPOINT_INTERVAL_SECS as i64) {
+                checkpointed_on = now;
+                update_checkpoint(&state, &mut checkpoint).await;
+            }
         }
 
+        info!("background worker stopped");
+        control.update_status(|status| status.state = WorkerState::Stopped);
+
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+async fn recover_checkpoint(app: &AppHandle, checkpoint: &mut Checkpoint, database: &Database, version: &str) {
+    match checkpoint.recover(database, version).await {
+        Ok(Some(encounter_id)) => {
+            info!("recovered encounter {} from checkpoint", encounter_id);
+            app.emit_to(EventTarget::Any, "encounter-recovered", encounter_id).ok();
+        }
+        Ok(None) => {}
+        Err(err) => error!("could not recover checkpoint: {:?}", err),
+    }
+}
+
+async fn update_checkpoint(state: &EncounterState, checkpoint: &mut Checkpoint) {
+    let result = match state.has_unsaved_fight().then(|| state.get_encounter(true)).flatten() {
+        Some(model) => checkpoint.write(model),
+        None => checkpoint.clear().await,
+    };
+
+    if let Err(err) = result {
+        warn!("could not update checkpoint: {:?}", err);
+    }
+}
+
+/// a failed start is reported through the status, the worker keeps running without packets
+fn start_sniffer(
+    control: &WorkerControl,
+    kind: SnifferKind,
+    port: u16,
+    region_path: &str) -> Option<(Box<dyn PacketSniffer>, Receiver<(Pkt, Vec<u8>)>)> {
+    let result = sniffer::create(kind).and_then(|mut packet_sniffer| {
+        let rx = packet_sniffer.start(port, region_path.to_string())?;
+        Ok((packet_sniffer, rx))
+    });
+
+    control.update_status(|status| {
+        status.sniffer = kind;
+        status.port = port;
+        status.state = match &result {
+            Ok(_) => WorkerState::Running,
+            Err(err) => WorkerState::SnifferError(format!("{:#}", err)),
+        };
+    });
+
+    match result {
+        Ok(started) => {
+            info!("listening on port: {}", port);
+            Some(started)
+        }
+        Err(err) => {
+            error!("could not start {:?} sniffer: {:?}", kind, err);
+            None
+        }
+    }
+}
+
+/// applies a command from the ui, `None` for `Shutdown` which the loop acks once the encounter is flushed
+fn dispatch(
+    command: WorkerCommand,
+    state: &mut EncounterState,
+    control: &WorkerControl,
+    paused: &mut bool,
+    sniffer: &mut Option<(Box<dyn PacketSniffer>, Receiver<(Pkt, Vec<u8>)>)>,
+    damage_decryptor: &mut Box<dyn DamageDecryptor>,
+    recorder: &mut Option<Recorder>,
+    context: &AppContext,
+    save: impl FnOnce(SaveToDb)) -> Option<Result<()>> {
+    let region_path = context.region_path.to_string_lossy();
+
+    let result = match command {
+        WorkerCommand::Reset => {
+            state.soft_reset(true);
+            Ok(())
+        }
+        WorkerCommand::TogglePause => {
+            *paused = !*paused;
+            control.update_status(|status| status.paused = *paused);
+            Ok(())
+        }
+        WorkerCommand::Save => match state.get_encounter(true) {
+            Some(model) => {
+                save(model);
+                state.saved = true;
+                state.is_resetting = true;
+                Ok(())
+            }
+            None => Err(anyhow!("there is no encounter to save")),
+        },
+        WorkerCommand::SetBossOnlyDamage(enabled) => {
+            state.boss_only_damage = enabled;
+            control.update_status(|status| status.boss_only_damage = enabled);
+            Ok(())
+        }
+        WorkerCommand::SetUpdateInterval(0) => Err(anyhow!("update interval must not be 0")),
+        WorkerCommand::SetUpdateInterval(interval_ms) => {
+            state.update_interval = Duration::milliseconds(interval_ms as i64);
+            control.update_status(|status| status.update_interval_ms = interval_ms);
+            Ok(())
+        }
+        WorkerCommand::SwitchSniffer(kind) => {
+            let port = control.status().port;
+            // the old sniffer stops once its receiver is dropped
+            *sniffer = start_sniffer(control, kind, port, &region_path);
+            create_decryptor(kind, state.zone_instance_id).and_then(|decryptor| {
+                *damage_decryptor = decryptor;
+                status_result(control)
+            })
+        }
+        WorkerCommand::SetPort(0) => Err(anyhow!("port must not be 0")),
+        WorkerCommand::SetPort(port) => {
+            let kind = control.status().sniffer;
+            *sniffer = start_sniffer(control, kind, port, &region_path);
+            status_result(control)
+        }
+        WorkerCommand::StartRecording => start_recording(context).map(|(name, started)| {
+            *recorder = Some(started);
+            control.update_status(|status| status.recording = Some(name));
+        }),
+        WorkerCommand::StopRecording => {
+            *recorder = None;
+            control.update_status(|status| status.recording = None);
+            Ok(())
+        }
+        WorkerCommand::Shutdown => return None,
+    };
+
+    Some(result)
+}
+
+/// a new decryptor only learns the key with the next zone change, until then all damage would be rejected
+fn create_decryptor(kind: SnifferKind, zone_instance_id: Option<u32>) -> Result<Box<dyn DamageDecryptor>> {
+    let decryptor = decryption::create(kind)?;
+
+    if let Some(zone_instance_id) = zone_instance_id {
+        decryptor.update_zone_instance_id(zone_instance_id);
+    }
+
+    Ok(decryptor)
+}
+
+fn status_result(control: &WorkerControl) -> Result<()> {
+    match control.status().state {
+        WorkerState::SnifferError(message) => Err(anyhow!(message)),
+        _ => Ok(()),
+    }
+}
+
+fn start_recording(context: &AppContext) -> Result<(String, Recorder)> {
+    let dir = context.resource_path.join(RECORDINGS_DIR_NAME);
+    std::fs::create_dir_all(&dir)?;
+
+    let name = format!("{}.bin", Utc::now().format("%Y%m%d-%H%M%S"));
+    let recorder = Recorder::new(dir.join(&name))?;
+
+    Ok((name, recorder))
+}
+
+/// the part of the settings that can change while the meter is running
+fn apply_settings(state: &mut EncounterState, settings: &Settings) {
+    let update_interval = if settings.general.low_performance_mode {
+        Duration::milliseconds(1500)
+    } else {
+        Duration::milliseconds(200)
+    };
+
+    if state.update_interval != update_interval {
+        info!("update interval set to {}ms", update_interval.num_milliseconds());
+        state.update_interval = update_interval;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use meter_core::packets::structures::SkillDamageEvent;
+
+    use crate::core::control;
+    use crate::core::decryption::{NoopDecryptor, XorDecryptor};
+
+    use super::*;
+
+    #[test]
+    fn should_dispatch_commands() {
+        let dir = std::env::temp_dir().join(format!("drama-meter-worker-{}", uuid::Uuid::new_v4()));
+        let context = AppContext::new("test".to_string(), dir.clone(), "test".to_string());
+        let (handle, control) = control::channel();
+        let mut state = EncounterState::new("test".to_string());
+        let mut paused = false;
+        let mut sniffer = None;
+        let mut damage_decryptor: Box<dyn DamageDecryptor> = Box::new(NoopDecryptor);
+        let mut recorder = None;
+
+        macro_rules! dispatch {
+            ($command:expr) => {
+                dispatch(
+                    $command,
+                    &mut state,
+                    &control,
+                    &mut paused,
+                    &mut sniffer,
+                    &mut damage_decryptor,
+                    &mut recorder,
+                    &context,
+                    |_| {})
+            };
+        }
+
+        assert!(matches!(dispatch!(WorkerCommand::TogglePause), Some(Ok(()))));
+        assert!(paused && handle.status().paused);
+
+        assert!(matches!(dispatch!(WorkerCommand::SetUpdateInterval(0)), Some(Err(_))));
+        assert!(matches!(dispatch!(WorkerCommand::SetUpdateInterval(500)), Some(Ok(()))));
+        assert_eq!(state.update_interval, Duration::milliseconds(500));
+        assert_eq!(handle.status().update_interval_ms, 500);
+
+        assert!(matches!(dispatch!(WorkerCommand::SetBossOnlyDamage(true)), Some(Ok(()))));
+        assert!(state.boss_only_damage && handle.status().boss_only_damage);
+
+        assert!(matches!(dispatch!(WorkerCommand::SetPort(0)), Some(Err(_))));
+
+        assert!(matches!(dispatch!(WorkerCommand::StartRecording), Some(Ok(()))));
+        assert!(recorder.is_some() && handle.status().recording.is_some());
+        assert!(matches!(dispatch!(WorkerCommand::StopRecording), Some(Ok(()))));
+        assert!(recorder.is_none() && handle.status().recording.is_none());
+
+        // left to the loop, which acks it after flushing
+        assert!(dispatch!(WorkerCommand::Shutdown).is_none());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn should_keep_the_zone_key_for_a_new_decryptor() {
+        let mut event = SkillDamageEvent { damage: 12_345, cur_hp: 1_000, max_hp: 2_000, ..Default::default() };
+        XorDecryptor::encrypt(&mut event, 42);
+
+        let decryptor = create_decryptor(SnifferKind::Fake, None).unwrap();
+        assert!(decryptor.decrypt_damage_event(&mut event.clone()).is_err());
+
+        let decryptor = create_decryptor(SnifferKind::Fake, Some(42)).unwrap();
+        decryptor.decrypt_damage_event(&mut event).unwrap();
+        assert_eq!(event.damage, 12_345);
+    }
+}
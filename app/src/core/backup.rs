@@ -0,0 +1,240 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use log::*;
+use rusqlite::{Connection, OpenFlags};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::constants::BACKUP_POLL_SECS;
+use crate::database::{Database, DatabaseManager};
+use crate::misc::settings::{BackupSettings, SettingsManager};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BackupReason {
+    Scheduled,
+    Migration,
+    Manual,
+    /// taken right before a restore so it can be undone
+    Restore,
+}
+
+impl BackupReason {
+    fn as_str(&self) -> &'static str {
+        match self {
+            BackupReason::Scheduled => "scheduled",
+            BackupReason::Migration => "migration",
+            BackupReason::Manual => "manual",
+            BackupReason::Restore => "restore",
+        }
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "scheduled" => Some(BackupReason::Scheduled),
+            "migration" => Some(BackupReason::Migration),
+            "manual" => Some(BackupReason::Manual),
+            "restore" => Some(BackupReason::Restore),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupInfo {
+    pub name: String,
+    pub reason: BackupReason,
+    pub created_on: i64,
+    pub size: u64,
+}
+
+/// backups are named `encounters-<unix ms>-<reason>.db`, anything else in the directory is ignored
+fn parse_name(name: &str) -> Option<(i64, BackupReason)> {
+    let (created_on, reason) = name.strip_prefix("encounters-")?.strip_suffix(".db")?.split_once('-')?;
+
+    Some((created_on.parse().ok()?, BackupReason::parse(reason)?))
+}
+
+/// newest first, an absent directory means no backups yet
+pub fn list(dir: &Path) -> Result<Vec<BackupInfo>> {
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut backups: Vec<BackupInfo> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let (created_on, reason) = parse_name(&name)?;
+            let size = entry.metadata().ok()?.len();
+
+            Some(BackupInfo { name, reason, created_on, size })
+        })
+        .collect();
+
+    backups.sort_unstable_by_key(|backup| std::cmp::Reverse(backup.created_on));
+
+    Ok(backups)
+}
+
+pub fn create(database: &Database, dir: &Path, reason: BackupReason) -> Result<BackupInfo> {
+    fs::create_dir_all(dir)?;
+
+    let created_on = chrono::Utc::now().timestamp_millis();
+    let name = format!("encounters-{}-{}.db", created_on, reason.as_str());
+    let path = dir.join(&name);
+
+    database.backup_to(&path)?;
+    let size = fs::metadata(&path)?.len();
+
+    Ok(BackupInfo { name, reason, created_on, size })
+}
+
+/// keeps the newest `keep` backups, returns how many were removed
+pub fn rotate(dir: &Path, keep: usize) -> Result<usize> {
+    let backups = list(dir)?;
+    let mut removed = 0;
+
+    for backup in backups.iter().skip(keep.max(1)) {
+        match fs::remove_file(dir.join(&backup.name)) {
+            Ok(_) => removed += 1,
+            Err(err) => warn!("could not remove backup {}: {}", backup.name, err),
+        }
+    }
+
+    Ok(removed)
+}
+
+pub fn check_integrity(path: &Path) -> Result<()> {
+    let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let mut statement = connection.prepare("PRAGMA integrity_check")?;
+    let messages = statement
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    if messages.len() != 1 || messages[0] != "ok" {
+        bail!("integrity check failed: {}", messages.join("; "));
+    }
+
+    Ok(())
+}
+
+/// verifies the backup first and keeps a copy of the current state, so a restore can be undone
+pub fn restore(database: &Database, dir: &Path, name: &str, migration_path: PathBuf) -> Result<BackupInfo> {
+    // only names from the listing, this must not become a way to read arbitrary files
+    if !list(dir)?.iter().any(|backup| backup.name == name) {
+        bail!("unknown backup {}", name);
+    }
+
+    let path = dir.join(name);
+    check_integrity(&path).with_context(|| format!("backup {} is corrupt", name))?;
+
+    let undo = create(database, dir, BackupReason::Restore)?;
+    database.restore_from(&path, migration_path)?;
+    info!("restored {}, previous state saved as {}", name, undo.name);
+
+    Ok(undo)
+}
+
+fn is_due(dir: &Path, settings: &BackupSettings, now: i64) -> Result<bool> {
+    let interval_ms = settings.interval_hours.max(1) as i64 * 3_600_000;
+    let last = list(dir)?
+        .into_iter()
+        .find(|backup| backup.reason == BackupReason::Scheduled);
+
+    Ok(last.is_none_or(|backup| now - backup.created_on >= interval_ms))
+}
+
+/// checks hourly so restarts don't reset the interval, runs until the app exits
+pub async fn run_backups(app_handle: AppHandle, manager: Arc<DatabaseManager>) {
+    loop {
+        let settings = match app_handle.state::<Mutex<SettingsManager>>().lock().unwrap().get() {
+            Ok(settings) => settings.backup.clone(),
+            Err(_) => BackupSettings::default(),
+        };
+
+        if settings.enabled {
+            let database = manager.get();
+            let dir = database.backup_path();
+            let now = chrono::Utc::now().timestamp_millis();
+
+            let result = is_due(dir, &settings, now).and_then(|due| {
+                if !due {
+                    return Ok(());
+                }
+
+                let info = create(&database, dir, BackupReason::Scheduled)?;
+                let removed = rotate(dir, settings.keep)?;
+                info!("created backup {}, removed {} old backups", info.name, removed);
+
+                Ok(())
+            });
+
+            if let Err(err) = result {
+                error!("failed to back up database: {:?}", err);
+            }
+        }
+
+        tokio::time::sleep(Duration::from_secs(BACKUP_POLL_SECS)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::constants::INITIAL_MIGRATION;
+    use crate::database::{create_test_database, migration_path};
+
+    use super::*;
+
+    #[test]
+    fn should_create_rotate_and_restore_backups() {
+        let database = create_test_database();
+        let dir = std::env::temp_dir().join(format!("drama-meter-backups-{}", uuid::Uuid::new_v4()));
+
+        let first = create(&database, &dir, BackupReason::Manual).unwrap();
+        std::thread::sleep(Duration::from_millis(2));
+        create(&database, &dir, BackupReason::Scheduled).unwrap();
+        check_integrity(&dir.join(&first.name)).unwrap();
+
+        assert_eq!(list(&dir).unwrap().len(), 2);
+        assert_eq!(rotate(&dir, 1).unwrap(), 1);
+
+        let backups = list(&dir).unwrap();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(backups[0].reason, BackupReason::Scheduled);
+
+        restore(&database, &dir, &backups[0].name, migration_path()).unwrap();
+        assert!(restore(&database, &dir, "../encounters.db", migration_path()).is_err());
+
+        let corrupt = dir.join("encounters-1-manual.db");
+        fs::write(&corrupt, b"not a database").unwrap();
+        assert!(restore(&database, &dir, "encounters-1-manual.db", migration_path()).is_err());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn should_migrate_restored_backups() {
+        let dir = std::env::temp_dir().join(format!("drama-meter-backups-{}", uuid::Uuid::new_v4()));
+        let first_migration = dir.join("migration");
+        fs::create_dir_all(&first_migration).unwrap();
+        fs::copy(migration_path().join(INITIAL_MIGRATION), first_migration.join(INITIAL_MIGRATION)).unwrap();
+
+        // a backup of the first schema, then the app updated
+        let database = Database::new(dir.join("encounters.db"));
+        database.setup(first_migration).unwrap();
+        let old = create(&database, &dir, BackupReason::Manual).unwrap();
+        database.setup(migration_path()).unwrap();
+
+        restore(&database, &dir, &old.name, migration_path()).unwrap();
+        database.insert_data(Default::default()).unwrap();
+
+        drop(database);
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,188 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::Result;
+use chrono::Utc;
+use log::{info, warn};
+use tokio::task::JoinHandle;
+
+use crate::constants::{DB_VERSION, EXPORT_VERSION};
+use crate::core::utils::calculate_stats;
+use crate::database::{Database, EncounterExport, SaveToDb};
+
+/// a one encounter database next to the profile holding the fight in progress,
+/// whatever is left in it on launch didn't make it to the real database
+pub struct Checkpoint {
+    path: PathBuf,
+    migration_path: PathBuf,
+    database: Option<Arc<Database>>,
+    dirty: bool,
+    /// the worker doesn't wait for writes, a fight can have thousands of entities
+    writing: Option<JoinHandle<()>>,
+}
+
+impl Checkpoint {
+    pub fn new(path: PathBuf, migration_path: PathBuf) -> Self {
+        Self {
+            path,
+            migration_path,
+            database: None,
+            // a previous run may have left one behind
+            dirty: true,
+            writing: None,
+        }
+    }
+
+    fn database(&mut self) -> Result<Arc<Database>> {
+        if self.database.is_none() {
+            if let Some(dir) = self.path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+
+            let database = Database::new(self.path.clone());
+            database.setup(self.migration_path.clone())?;
+            self.database = Some(Arc::new(database));
+        }
+
+        Ok(self.database.clone().unwrap())
+    }
+
+    /// replaces the previous checkpoint in the background, player info is left out so it never waits on the stats api,
+    /// skipped while the last write is still running since the next one has a newer fight anyway
+    pub fn write(&mut self, mut model: SaveToDb) -> Result<()> {
+        if self.writing.as_ref().is_some_and(|writing| !writing.is_finished()) {
+            return Ok(());
+        }
+
+        let database = self.database()?;
+        self.dirty = true;
+
+        self.writing = Some(tokio::task::spawn_blocking(move || {
+            model.misc.recovered = Some(true);
+            model.player_info = None;
+
+            calculate_stats(
+                &mut model.entities,
+                model.started_on.timestamp_millis(),
+                model.updated_on.timestamp_millis(),
+                model.duration_seconds,
+                &model.cast_log,
+                &model.skill_cast_log,
+                None,
+                &model.encounter_damage_stats,
+                &model.damage_log);
+
+            if let Err(err) = database.replace_encounters(model) {
+                warn!("could not write checkpoint: {:?}", err);
+            }
+        }));
+
+        Ok(())
+    }
+
+    /// a write still running would otherwise land after whatever comes next
+    async fn wait(&mut self) {
+        if let Some(writing) = self.writing.take() {
+            writing.await.ok();
+        }
+    }
+
+    pub async fn clear(&mut self) -> Result<()> {
+        self.wait().await;
+
+        if !self.dirty {
+            return Ok(());
+        }
+
+        self.database()?.delete_all_encounters(false).await?;
+        self.dirty = false;
+
+        Ok(())
+    }
+
+    /// moves a leftover checkpoint into `target`, importing dedupes so a fight that was saved
+    /// right before the crash isn't added twice
+    pub async fn recover(&mut self, target: &Database, app_version: &str) -> Result<Option<i64>> {
+        self.wait().await;
+
+        if !self.dirty {
+            return Ok(None);
+        }
+
+        let database = self.database()?;
+        let Some(id) = database.get_last_encounter().await? else {
+            self.dirty = false;
+            return Ok(None);
+        };
+
+        let export = EncounterExport {
+            version: EXPORT_VERSION,
+            app_version: app_version.to_string(),
+            db_version: DB_VERSION,
+            exported_on: Utc::now().timestamp_millis(),
+            encounters: database.export_encounters(vec![id]).await?,
+        };
+
+        let summary = target.import_encounters(export).await?;
+        self.clear().await?;
+
+        if summary.duplicates > 0 {
+            info!("checkpoint was already saved");
+        }
+
+        Ok(summary.imported.first().copied())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::{create_test_database, migration_path, test_export, TestEncounter};
+
+    use super::*;
+
+    fn create_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("drama-meter-checkpoint-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn should_recover_once() {
+        let dir = create_dir();
+        let path = dir.join("encounters.db");
+        let mut checkpoint = Checkpoint::new(path.clone(), migration_path());
+        let target = create_test_database();
+
+        assert_eq!(checkpoint.recover(&target, "test").await.unwrap(), None);
+
+        let encounters = vec![TestEncounter { recovered: true, ..Default::default() }.build()];
+        checkpoint.database().unwrap().import_encounters(test_export(encounters)).await.unwrap();
+
+        // a new launch doesn't know what the last run left behind
+        let mut checkpoint = Checkpoint::new(path, migration_path());
+        assert!(checkpoint.recover(&target, "test").await.unwrap().is_some());
+        assert_eq!(checkpoint.recover(&target, "test").await.unwrap(), None);
+        assert_eq!(target.get_encounter_count().await.unwrap(), 1);
+
+        drop(checkpoint);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn should_keep_only_the_latest_write() {
+        let dir = create_dir();
+        let mut checkpoint = Checkpoint::new(dir.join("encounters.db"), migration_path());
+        let target = create_test_database();
+
+        for boss in ["Thaemine", "Echidna"] {
+            checkpoint.write(SaveToDb { current_boss_name: boss.to_string(), ..Default::default() }).unwrap();
+            checkpoint.wait().await;
+        }
+
+        let id = checkpoint.recover(&target, "test").await.unwrap().unwrap();
+        let encounter = target.load_encounter(id.to_string()).await.unwrap();
+        assert_eq!(encounter.current_boss_name, "Echidna");
+        assert_eq!(target.get_encounter_count().await.unwrap(), 1);
+
+        drop(checkpoint);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
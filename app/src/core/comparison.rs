@@ -0,0 +1,494 @@
+use anyhow::{bail, Result};
+use hashbrown::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+
+use crate::core::utils::{is_hat_buff, is_support_class_id};
+use crate::models::*;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Alignment {
+    #[default]
+    Time,
+    BossHp,
+}
+
+/// `start` and `end` are seconds for time alignment, boss hp percent (100 to 0) otherwise
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CompareOptions {
+    pub alignment: Alignment,
+    pub start: Option<f64>,
+    pub end: Option<f64>,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowStats {
+    pub damage: i64,
+    pub dps: i64,
+    pub casts: i64,
+    pub hits: i64,
+    pub crit_rate: f64,
+    pub support_buff_uptime: f64,
+    pub identity_uptime: f64,
+    pub brand_uptime: f64,
+}
+
+impl WindowStats {
+    fn delta(&self, baseline: &WindowStats) -> WindowStats {
+        WindowStats {
+            damage: self.damage - baseline.damage,
+            dps: self.dps - baseline.dps,
+            casts: self.casts - baseline.casts,
+            hits: self.hits - baseline.hits,
+            crit_rate: self.crit_rate - baseline.crit_rate,
+            support_buff_uptime: self.support_buff_uptime - baseline.support_buff_uptime,
+            identity_uptime: self.identity_uptime - baseline.identity_uptime,
+            brand_uptime: self.brand_uptime - baseline.brand_uptime,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComparedStats {
+    pub stats: WindowStats,
+    /// difference to the first encounter, missing if the baseline has no entry
+    pub delta: Option<WindowStats>,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkillComparison {
+    pub id: u32,
+    pub name: String,
+    pub icon: String,
+    pub entries: Vec<Option<ComparedStats>>,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerComparison {
+    pub name: String,
+    pub class_id: u32,
+    pub entries: Vec<Option<ComparedStats>>,
+    pub skills: Vec<SkillComparison>,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComparedEncounter {
+    pub fight_start: i64,
+    pub window_start: i64,
+    pub window_end: i64,
+}
+
+/// entries of players and skills are in the same order as `encounters`
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncounterComparison {
+    pub boss_name: String,
+    pub alignment: Alignment,
+    pub encounters: Vec<ComparedEncounter>,
+    pub players: Vec<PlayerComparison>,
+}
+
+#[derive(Default)]
+struct SupportBuffs {
+    attack_power: HashSet<u32>,
+    identity: HashSet<u32>,
+    brand: HashSet<u32>,
+}
+
+pub fn compare_encounters(encounters: &[Encounter], options: &CompareOptions) -> Result<EncounterComparison> {
+    if encounters.len() < 2 {
+        bail!("at least two encounters are required");
+    }
+
+    let boss_name = encounters[0].current_boss_name.clone();
+
+    if encounters.iter().any(|encounter| encounter.current_boss_name != boss_name) {
+        bail!("encounters are not of the same boss");
+    }
+
+    let mut compared = vec![];
+    let mut player_stats: Vec<HashMap<&str, (WindowStats, HashMap<u32, WindowStats>)>> = vec![];
+
+    for encounter in encounters {
+        let (start, end) = get_window(encounter, options)?;
+        let support_buffs = get_support_buffs(&encounter.encounter_damage_stats);
+
+        let players = encounter
+            .entities
+            .values()
+            .filter(|entity| entity.entity_type == EntityType::Player)
+            .map(|entity| {
+                let skills: HashMap<u32, WindowStats> = entity
+                    .skills
+                    .values()
+                    .map(|skill| (skill.id, get_window_stats(&[skill], &support_buffs, start, end)))
+                    .collect();
+                let total = get_window_stats(&entity.skills.values().collect::<Vec<_>>(), &support_buffs, start, end);
+
+                (entity.name.as_str(), (total, skills))
+            })
+            .collect();
+
+        compared.push(ComparedEncounter {
+            fight_start: encounter.fight_start,
+            window_start: start,
+            window_end: end,
+        });
+        player_stats.push(players);
+    }
+
+    let mut names: Vec<&str> = player_stats.iter().flat_map(|players| players.keys().copied()).collect();
+    names.sort_unstable();
+    names.dedup();
+
+    let mut players: Vec<PlayerComparison> = names
+        .into_iter()
+        .map(|name| {
+            let entity = encounters
+                .iter()
+                .find_map(|encounter| encounter.entities.get(name))
+                .expect("player exists in at least one encounter");
+
+            let entries = to_entries(player_stats.iter().map(|players| players.get(name).map(|(total, _)| *total)));
+
+            let mut skill_ids: Vec<u32> = player_stats
+                .iter()
+                .filter_map(|players| players.get(name))
+                .flat_map(|(_, skills)| skills.keys().copied())
+                .collect();
+            skill_ids.sort_unstable();
+            skill_ids.dedup();
+
+            let mut skills: Vec<SkillComparison> = skill_ids
+                .into_iter()
+                .map(|id| {
+                    let skill = encounters
+                        .iter()
+                        .filter_map(|encounter| encounter.entities.get(name))
+                        .find_map(|entity| entity.skills.get(&id));
+
+                    SkillComparison {
+                        id,
+                        name: skill.map(|skill| skill.name.clone()).unwrap_or_default(),
+                        icon: skill.map(|skill| skill.icon.clone()).unwrap_or_default(),
+                        entries: to_entries(player_stats.iter().map(|players| {
+                            players.get(name).and_then(|(_, skills)| skills.get(&id).copied())
+                        })),
+                    }
+                })
+                .collect();
+            skills.sort_by_key(|skill| std::cmp::Reverse(max_damage(&skill.entries)));
+
+            PlayerComparison {
+                name: name.to_string(),
+                class_id: entity.class_id,
+                entries,
+                skills,
+            }
+        })
+        .collect();
+
+    players.sort_by_key(|player| std::cmp::Reverse(max_damage(&player.entries)));
+
+    Ok(EncounterComparison {
+        boss_name,
+        alignment: options.alignment,
+        encounters: compared,
+        players,
+    })
+}
+
+/// relative window in milliseconds
+fn get_window(encounter: &Encounter, options: &CompareOptions) -> Result<(i64, i64)> {
+    let duration = (encounter.last_combat_packet - encounter.fight_start).max(0);
+
+    let window = match options.alignment {
+        Alignment::Time => {
+            let start = options.start.map(|start| (start * 1000.0) as i64).unwrap_or(0).clamp(0, duration);
+            let end = options.end.map(|end| (end * 1000.0) as i64).unwrap_or(duration).clamp(start, duration);
+            (start, end)
+        },
+        Alignment::BossHp => {
+            let log = match encounter.encounter_damage_stats.boss_hp_log.get(&encounter.current_boss_name) {
+                Some(log) if !log.is_empty() => log,
+                _ => bail!("encounter {} has no boss hp log", encounter.fight_start),
+            };
+
+            let from = options.start.unwrap_or(100.0) / 100.0;
+            let to = options.end.unwrap_or(0.0) / 100.0;
+
+            let start = log
+                .iter()
+                .find(|entry| entry.p as f64 <= from)
+                .map(|entry| entry.time as i64 * 1000)
+                .unwrap_or(0);
+            let end = log
+                .iter()
+                .find(|entry| entry.p as f64 <= to)
+                .map(|entry| entry.time as i64 * 1000)
+                .unwrap_or(duration);
+
+            (start, end.max(start))
+        },
+    };
+
+    Ok(window)
+}
+
+/// same classification as the live meter uses for support buff attribution
+fn get_support_buffs(stats: &EncounterDamageStats) -> SupportBuffs {
+    let mut support_buffs = SupportBuffs::default();
+
+    for (id, buff) in stats.buffs.iter() {
+        let Some(skill) = buff.source.skill.as_ref() else {
+            continue;
+        };
+
+        let is_party_damage_buff = is_support_class_id(skill.class_id)
+            && buff.buff_type & StatusEffectBuffTypeFlags::DMG.bits() != 0
+            && buff.target == StatusEffectTarget::PARTY;
+
+        if is_party_damage_buff && !is_hat_buff(id)
+            && (buff.buff_category == "classskill" || buff.buff_category == "arkpassive") {
+            support_buffs.attack_power.insert(*id);
+        }
+
+        if is_party_damage_buff && buff.buff_category == "identity" {
+            support_buffs.identity.insert(*id);
+        }
+    }
+
+    for (id, debuff) in stats.debuffs.iter() {
+        let Some(skill) = debuff.source.skill.as_ref() else {
+            continue;
+        };
+
+        if is_support_class_id(skill.class_id)
+            && debuff.buff_type & StatusEffectBuffTypeFlags::DMG.bits() != 0
+            && debuff.target == StatusEffectTarget::PARTY {
+            support_buffs.brand.insert(*id);
+        }
+    }
+
+    support_buffs
+}
+
+fn get_window_stats(skills: &[&Skill], support_buffs: &SupportBuffs, start: i64, end: i64) -> WindowStats {
+    let mut stats = WindowStats::default();
+    let mut crits = 0;
+    let mut buffed = 0;
+    let mut identity = 0;
+    let mut debuffed = 0;
+
+    for skill in skills {
+        for cast in skill.skill_cast_log.iter() {
+            if (start..=end).contains(&cast.recorded_on) {
+                stats.casts += 1;
+            }
+
+            for hit in cast.hits.iter().filter(|hit| (start..=end).contains(&hit.recorded_on)) {
+                stats.damage += hit.damage;
+                stats.hits += 1;
+
+                if hit.crit {
+                    crits += 1;
+                }
+                if hit.buffed_by.iter().any(|id| support_buffs.attack_power.contains(id)) {
+                    buffed += hit.damage;
+                }
+                if hit.buffed_by.iter().any(|id| support_buffs.identity.contains(id)) {
+                    identity += hit.damage;
+                }
+                if hit.debuffed_by.iter().any(|id| support_buffs.brand.contains(id)) {
+                    debuffed += hit.damage;
+                }
+            }
+        }
+    }
+
+    let seconds = ((end - start) / 1000).max(1);
+    stats.dps = stats.damage / seconds;
+
+    if stats.hits > 0 {
+        stats.crit_rate = crits as f64 / stats.hits as f64;
+    }
+
+    if stats.damage > 0 {
+        stats.support_buff_uptime = buffed as f64 / stats.damage as f64;
+        stats.identity_uptime = identity as f64 / stats.damage as f64;
+        stats.brand_uptime = debuffed as f64 / stats.damage as f64;
+    }
+
+    stats
+}
+
+fn to_entries(stats: impl Iterator<Item = Option<WindowStats>>) -> Vec<Option<ComparedStats>> {
+    let stats: Vec<Option<WindowStats>> = stats.collect();
+    let baseline = stats.first().copied().flatten();
+
+    stats
+        .into_iter()
+        .map(|stats| stats.map(|stats| ComparedStats {
+            stats,
+            delta: baseline.map(|baseline| stats.delta(&baseline)),
+        }))
+        .collect()
+}
+
+fn max_damage(entries: &[Option<ComparedStats>]) -> i64 {
+    entries
+        .iter()
+        .flatten()
+        .map(|entry| entry.stats.damage)
+        .max()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Map, Value};
+
+    use crate::database::{create_test_database, test_export, TestEncounter};
+
+    use super::*;
+
+    const BOSS: &str = "Thaemine, the Lightqueller";
+
+    /// one cast per hit, `hits` are (relative ms, damage, crit)
+    fn player(name: &str, hits: &[(i64, i64, bool)]) -> Map<String, Value> {
+        let casts: Vec<Value> = hits
+            .iter()
+            .map(|(recorded_on, damage, crit)| json!({
+                "recordedOn": recorded_on,
+                "lastRecordedOn": recorded_on,
+                "hits": [{
+                    "recordedOn": recorded_on,
+                    "damage": damage,
+                    "crit": crit,
+                    "backAttack": false,
+                    "frontAttack": false,
+                    "buffedBy": [],
+                    "debuffedBy": [],
+                    "rdpsDamageReceived": 0,
+                    "rdpsDamageReceivedSupport": 0
+                }]
+            }))
+            .collect();
+
+        let mut player = TestEncounter::player(name, "Bard", "", 1680.0);
+        player.insert("class_id".to_string(), json!(204));
+        player.insert("skills".to_string(), json!({ "1": { "id": 1, "name": "Sound Shock", "skillCastLog": casts } }));
+        player
+    }
+
+    /// 30 seconds, the boss is at half hp after 10 and dead after 20
+    async fn load(encounters: Vec<(i64, &str, Vec<Map<String, Value>>)>) -> Vec<Encounter> {
+        let database = create_test_database();
+
+        let entries = encounters
+            .into_iter()
+            .map(|(fight_start, boss, entities)| {
+                let mut entry = TestEncounter {
+                    fight_start,
+                    duration: 30_000,
+                    boss: boss.to_string(),
+                    entities,
+                    ..Default::default()
+                }
+                .build();
+                entry.encounter.insert("boss_hp_log".to_string(), json!({
+                    boss: [{ "time": 0, "hp": 100, "p": 1.0 }, { "time": 10, "hp": 50, "p": 0.5 }, { "time": 20, "hp": 0, "p": 0.0 }]
+                }));
+                entry
+            })
+            .collect();
+        let ids = database.import_encounters(test_export(entries)).await.unwrap().imported;
+
+        let mut loaded = vec![];
+        for id in ids {
+            loaded.push(database.load_encounter(id.to_string()).await.unwrap());
+        }
+        loaded
+    }
+
+    fn options(alignment: Alignment, start: Option<f64>, end: Option<f64>) -> CompareOptions {
+        CompareOptions { alignment, start, end }
+    }
+
+    #[tokio::test]
+    async fn should_window_by_time() {
+        let encounters = load(vec![(1_700_000_000_000, BOSS, vec![])]).await;
+        let encounter = &encounters[0];
+
+        assert_eq!(get_window(encounter, &options(Alignment::Time, None, None)).unwrap(), (0, 30_000));
+        assert_eq!(get_window(encounter, &options(Alignment::Time, Some(10.0), Some(20.0))).unwrap(), (10_000, 20_000));
+        assert_eq!(get_window(encounter, &options(Alignment::Time, Some(40.0), Some(5.0))).unwrap(), (30_000, 30_000));
+    }
+
+    #[tokio::test]
+    async fn should_window_by_boss_hp() {
+        let mut encounters = load(vec![(1_700_000_000_000, BOSS, vec![])]).await;
+
+        assert_eq!(get_window(&encounters[0], &options(Alignment::BossHp, None, None)).unwrap(), (0, 20_000));
+        assert_eq!(get_window(&encounters[0], &options(Alignment::BossHp, Some(50.0), None)).unwrap(), (10_000, 20_000));
+        assert_eq!(get_window(&encounters[0], &options(Alignment::BossHp, Some(100.0), Some(50.0))).unwrap(), (0, 10_000));
+
+        encounters[0].encounter_damage_stats.boss_hp_log.clear();
+        assert!(get_window(&encounters[0], &options(Alignment::BossHp, None, None)).is_err());
+    }
+
+    #[tokio::test]
+    async fn should_compare_against_the_first_encounter() {
+        let encounters = load(vec![
+            (1_700_000_000_000, BOSS, vec![player("Alice", &[(1_000, 100, false), (15_000, 100, true)])]),
+            (1_700_100_000_000, BOSS, vec![player("Alice", &[(1_000, 300, true)]), player("Bob", &[(1_000, 50, false)])]),
+        ])
+        .await;
+
+        let comparison = compare_encounters(&encounters, &CompareOptions::default()).unwrap();
+        assert_eq!(comparison.boss_name, BOSS);
+        assert_eq!(comparison.encounters.len(), 2);
+        assert_eq!(comparison.players.iter().map(|player| player.name.as_str()).collect::<Vec<_>>(), ["Alice", "Bob"]);
+
+        let alice = &comparison.players[0];
+        let baseline = alice.entries[0].as_ref().unwrap();
+        assert_eq!(baseline.stats.damage, 200);
+        assert_eq!(baseline.stats.hits, 2);
+        assert_eq!(baseline.stats.crit_rate, 0.5);
+        assert_eq!(baseline.delta.unwrap().damage, 0);
+
+        let delta = alice.entries[1].as_ref().unwrap().delta.unwrap();
+        assert_eq!(delta.damage, 100);
+        assert_eq!(delta.casts, -1);
+        assert_eq!(delta.crit_rate, 0.5);
+        assert_eq!(alice.skills[0].entries[1].as_ref().unwrap().delta.unwrap().damage, 100);
+
+        // no baseline to compare to
+        let bob = &comparison.players[1];
+        assert!(bob.entries[0].is_none());
+        assert!(bob.entries[1].as_ref().unwrap().delta.is_none());
+
+        // only the hits in the first 10 seconds
+        let comparison = compare_encounters(&encounters, &options(Alignment::Time, None, Some(10.0))).unwrap();
+        assert_eq!(comparison.players[0].entries[0].as_ref().unwrap().stats.damage, 100);
+        assert_eq!(comparison.players[0].entries[0].as_ref().unwrap().stats.dps, 10);
+    }
+
+    #[tokio::test]
+    async fn should_reject_invalid_selections() {
+        let encounters = load(vec![
+            (1_700_000_000_000, BOSS, vec![]),
+            (1_700_100_000_000, "Echidna", vec![]),
+        ])
+        .await;
+
+        assert!(compare_encounters(&encounters[..1], &CompareOptions::default()).is_err());
+        assert!(compare_encounters(&encounters, &CompareOptions::default()).is_err());
+    }
+}
@@ -0,0 +1,232 @@
+use anyhow::{anyhow, Result};
+use log::*;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, EventTarget, Listener};
+use tokio::sync::{mpsc, oneshot, watch};
+
+use crate::constants::{PORT, WORKER_ACK_TIMEOUT_MS};
+use crate::sniffer::SnifferKind;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "camelCase")]
+pub enum WorkerCommand {
+    Reset,
+    TogglePause,
+    /// saves the ongoing encounter right away
+    Save,
+    SetBossOnlyDamage(bool),
+    /// milliseconds between two live updates
+    SetUpdateInterval(u64),
+    SwitchSniffer(SnifferKind),
+    SetPort(u16),
+    StartRecording,
+    StopRecording,
+    Shutdown,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "state", content = "message", rename_all = "camelCase")]
+pub enum WorkerState {
+    Starting,
+    WaitingForAssets,
+    Running,
+    /// the worker keeps taking commands, switching the sniffer or port may fix it
+    SnifferError(String),
+    Stopped,
+    /// the worker stopped on an error and only comes back with a restart
+    Failed(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkerStatus {
+    #[serde(flatten)]
+    pub state: WorkerState,
+    pub sniffer: SnifferKind,
+    pub port: u16,
+    pub paused: bool,
+    pub boss_only_damage: bool,
+    pub update_interval_ms: u64,
+    /// file name of the running recording
+    pub recording: Option<String>,
+}
+
+impl Default for WorkerStatus {
+    fn default() -> Self {
+        Self {
+            state: WorkerState::Starting,
+            sniffer: SnifferKind::default(),
+            port: PORT,
+            paused: false,
+            boss_only_damage: false,
+            update_interval_ms: 0,
+            recording: None,
+        }
+    }
+}
+
+type Ack = oneshot::Sender<Result<(), String>>;
+
+/// the side of the channel that commands and the ui use
+#[derive(Clone)]
+pub struct WorkerHandle {
+    commands: mpsc::UnboundedSender<(WorkerCommand, Ack)>,
+    status: watch::Receiver<WorkerStatus>,
+}
+
+/// the side owned by the background worker
+pub struct WorkerControl {
+    commands: mpsc::UnboundedReceiver<(WorkerCommand, Ack)>,
+    status: watch::Sender<WorkerStatus>,
+}
+
+pub fn channel() -> (WorkerHandle, WorkerControl) {
+    let (commands_sender, commands) = mpsc::unbounded_channel();
+    let (status_sender, status) = watch::channel(WorkerStatus::default());
+
+    (
+        WorkerHandle { commands: commands_sender, status },
+        WorkerControl { commands, status: status_sender },
+    )
+}
+
+impl WorkerHandle {
+    /// resolves once the worker has applied the command, or with the reason it couldn't
+    pub async fn send(&self, command: WorkerCommand) -> Result<()> {
+        let (ack, acked) = oneshot::channel();
+
+        self.commands
+            .send((command, ack))
+            .map_err(|_| anyhow!("background worker is not running"))?;
+
+        let timeout = std::time::Duration::from_millis(WORKER_ACK_TIMEOUT_MS);
+        tokio::time::timeout(timeout, acked)
+            .await
+            .map_err(|_| anyhow!("background worker did not respond"))?
+            .map_err(|_| anyhow!("background worker stopped"))?
+            .map_err(|err| anyhow!(err))
+    }
+
+    pub fn status(&self) -> WorkerStatus {
+        self.status.borrow().clone()
+    }
+
+    /// emits `worker-status` on every change
+    pub fn forward_status(&self, app_handle: AppHandle) {
+        let mut status = self.status.clone();
+
+        tauri::async_runtime::spawn(async move {
+            while status.changed().await.is_ok() {
+                let current = status.borrow_and_update().clone();
+                app_handle.emit_to(EventTarget::Any, "worker-status", &current).ok();
+            }
+        });
+    }
+
+    /// keeps the events the meter windows and shortcuts already emit working
+    pub fn setup_listeners(&self, app_handle: &AppHandle) {
+        let events = [
+            ("reset-request", "reset-encounter"),
+            ("save-request", "save-encounter"),
+            ("pause-request", "pause-encounter"),
+        ];
+
+        for (request, response) in events {
+            let handle = self.clone();
+            let app_handle_clone = app_handle.clone();
+
+            app_handle.listen_any(request, move |_event| {
+                let command = match request {
+                    "reset-request" => WorkerCommand::Reset,
+                    "save-request" => WorkerCommand::Save,
+                    _ => WorkerCommand::TogglePause,
+                };
+
+                handle.send_detached(command);
+                app_handle_clone.emit_to(EventTarget::Any, response, "").ok();
+            });
+        }
+
+        let handle = self.clone();
+        app_handle.listen_any("boss-only-damage-request", move |event| {
+            match event.payload() {
+                "true" => handle.send_detached(WorkerCommand::SetBossOnlyDamage(true)),
+                "false" => handle.send_detached(WorkerCommand::SetBossOnlyDamage(false)),
+                _ => {}
+            }
+        });
+    }
+
+    /// for event listeners, which have nobody to report the result to
+    fn send_detached(&self, command: WorkerCommand) {
+        let handle = self.clone();
+
+        tauri::async_runtime::spawn(async move {
+            if let Err(err) = handle.send(command.clone()).await {
+                warn!("worker command {:?} failed: {}", command, err);
+            }
+        });
+    }
+}
+
+impl WorkerControl {
+    pub fn try_recv(&mut self) -> Option<(WorkerCommand, Ack)> {
+        self.commands.try_recv().ok()
+    }
+
+    pub fn status(&self) -> WorkerStatus {
+        self.status.borrow().clone()
+    }
+
+    pub fn update_status(&self, update: impl FnOnce(&mut WorkerStatus)) {
+        self.status.send_modify(update);
+    }
+
+    /// reports how the worker ended after `self` was moved into it
+    pub fn status_sender(&self) -> watch::Sender<WorkerStatus> {
+        self.status.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn should_acknowledge_commands() {
+        let (handle, mut control) = channel();
+
+        let worker = tokio::spawn(async move {
+            loop {
+                let Some((command, ack)) = control.try_recv() else {
+                    tokio::task::yield_now().await;
+                    continue;
+                };
+
+                let result = match command {
+                    WorkerCommand::SetPort(0) => Err("port must not be 0".to_string()),
+                    WorkerCommand::SetPort(port) => {
+                        control.update_status(|status| status.port = port);
+                        Ok(())
+                    }
+                    _ => Ok(()),
+                };
+
+                let shutdown = command == WorkerCommand::Shutdown;
+                ack.send(result).ok();
+
+                if shutdown {
+                    break;
+                }
+            }
+        });
+
+        handle.send(WorkerCommand::SetPort(6050)).await.unwrap();
+        assert_eq!(handle.status().port, 6050);
+        assert!(handle.send(WorkerCommand::SetPort(0)).await.is_err());
+
+        handle.send(WorkerCommand::Shutdown).await.unwrap();
+        worker.await.unwrap();
+        assert!(handle.send(WorkerCommand::Reset).await.is_err());
+    }
+}
@@ -0,0 +1,146 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use anyhow::Result;
+use meter_core::decryption::DamageEncryptionHandler;
+use meter_core::packets::structures::SkillDamageEvent;
+use thiserror::Error;
+
+use crate::sniffer::SnifferKind;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum DecryptionError {
+    #[error("no key for the current zone")]
+    MissingKey,
+    #[error("damage could not be decrypted")]
+    Rejected,
+    #[error("decrypted damage is out of range")]
+    InvalidValue,
+}
+
+/// the damage in damage packets is encrypted per zone, `NewTransit` carries the zone to derive the key from
+pub trait DamageDecryptor {
+    fn update_zone_instance_id(&self, zone_instance_id: u32);
+    fn decrypt_damage_event(&self, event: &mut SkillDamageEvent) -> Result<(), DecryptionError>;
+}
+
+impl DamageDecryptor for DamageEncryptionHandler {
+    fn update_zone_instance_id(&self, zone_instance_id: u32) {
+        DamageEncryptionHandler::update_zone_instance_id(self, zone_instance_id);
+    }
+
+    fn decrypt_damage_event(&self, event: &mut SkillDamageEvent) -> Result<(), DecryptionError> {
+        match DamageEncryptionHandler::decrypt_damage_event(self, event) {
+            true => Ok(()),
+            false => Err(DecryptionError::Rejected),
+        }
+    }
+}
+
+/// takes damage as it comes
+pub struct NoopDecryptor;
+
+impl DamageDecryptor for NoopDecryptor {
+    fn update_zone_instance_id(&self, _zone_instance_id: u32) {}
+
+    fn decrypt_damage_event(&self, _event: &mut SkillDamageEvent) -> Result<(), DecryptionError> {
+        Ok(())
+    }
+}
+
+/// stand-in for the game's encryption used by the simulator, damage and hp are xored with a key of the zone
+#[derive(Default)]
+pub struct XorDecryptor(AtomicU32);
+
+impl XorDecryptor {
+    pub fn new() -> Self {
+        Self(AtomicU32::new(0))
+    }
+
+    fn key(zone_instance_id: u32) -> i64 {
+        (zone_instance_id as i64).wrapping_mul(0x9E37_79B9_7F4A_7C15u64 as i64)
+    }
+
+    pub fn encrypt(event: &mut SkillDamageEvent, zone_instance_id: u32) {
+        let key = Self::key(zone_instance_id);
+        event.damage ^= key;
+        event.cur_hp ^= key;
+    }
+}
+
+impl DamageDecryptor for XorDecryptor {
+    fn update_zone_instance_id(&self, zone_instance_id: u32) {
+        self.0.store(zone_instance_id, Ordering::Relaxed);
+    }
+
+    fn decrypt_damage_event(&self, event: &mut SkillDamageEvent) -> Result<(), DecryptionError> {
+        let zone_instance_id = self.0.load(Ordering::Relaxed);
+
+        if zone_instance_id == 0 {
+            return Err(DecryptionError::MissingKey);
+        }
+
+        let key = Self::key(zone_instance_id);
+        let damage = event.damage ^ key;
+        let cur_hp = event.cur_hp ^ key;
+
+        if damage < 0 || cur_hp < 0 || cur_hp > event.max_hp {
+            return Err(DecryptionError::InvalidValue);
+        }
+
+        event.damage = damage;
+        event.cur_hp = cur_hp;
+
+        Ok(())
+    }
+}
+
+/// the fake sniffer replays simulator packets, which are encrypted with `XorDecryptor`
+pub fn create(kind: SnifferKind) -> Result<Box<dyn DamageDecryptor>> {
+    match kind {
+        SnifferKind::Fake => Ok(Box::new(XorDecryptor::new())),
+        SnifferKind::Windivert => {
+            let handler = DamageEncryptionHandler::new();
+            handler.start()?;
+
+            Ok(Box::new(handler))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event() -> SkillDamageEvent {
+        SkillDamageEvent {
+            damage: 12_345,
+            cur_hp: 1_000_000,
+            max_hp: 2_000_000,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn should_decrypt_with_zone_key() {
+        let decryptor = XorDecryptor::new();
+        let mut encrypted = event();
+        XorDecryptor::encrypt(&mut encrypted, 42);
+
+        assert_eq!(decryptor.decrypt_damage_event(&mut encrypted.clone()), Err(DecryptionError::MissingKey));
+
+        decryptor.update_zone_instance_id(42);
+        decryptor.decrypt_damage_event(&mut encrypted).unwrap();
+        assert_eq!(encrypted.damage, 12_345);
+        assert_eq!(encrypted.cur_hp, 1_000_000);
+    }
+
+    #[test]
+    fn should_reject_wrong_zone_key() {
+        let decryptor = XorDecryptor::new();
+        let mut encrypted = event();
+        XorDecryptor::encrypt(&mut encrypted, 42);
+
+        decryptor.update_zone_instance_id(43);
+        assert_eq!(decryptor.decrypt_damage_event(&mut encrypted), Err(DecryptionError::InvalidValue));
+    }
+}
@@ -0,0 +1,304 @@
+use std::collections::{BTreeSet, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::Utc;
+use hashbrown::HashMap;
+use meter_core::packets::opcodes::Pkt;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::constants::{DIAGNOSTICS_LATENCY_SAMPLES, DIAGNOSTICS_MAX_UNKNOWN_IDS, DIAGNOSTICS_SAMPLE_BYTES};
+
+#[derive(Debug, Clone, Copy)]
+pub enum UnknownId {
+    Npc(u32),
+    Skill(u32),
+    Buff(u32),
+}
+
+#[derive(Default)]
+struct OpcodeCounter {
+    count: u64,
+    bytes: u64,
+    total_micros: u64,
+    max_micros: u64,
+}
+
+struct DecodeFailure {
+    count: u64,
+    message: String,
+    sample: Vec<u8>,
+    last_seen: i64,
+}
+
+/// counters for everything the parser couldn't make sense of, fed by the background worker
+struct Diagnostics {
+    since: i64,
+    opcodes: HashMap<Pkt, OpcodeCounter>,
+    failures: HashMap<Pkt, DecodeFailure>,
+    unknown: BTreeSet<(u8, u32)>,
+    latencies: VecDeque<u64>,
+}
+
+static DIAGNOSTICS: Lazy<Mutex<Diagnostics>> = Lazy::new(|| Mutex::new(Diagnostics::new()));
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpcodeStats {
+    pub opcode: String,
+    pub count: u64,
+    pub bytes: u64,
+    pub avg_micros: u64,
+    pub max_micros: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodeError {
+    pub opcode: String,
+    pub count: u64,
+    pub message: String,
+    /// hex of the start of the last payload that failed
+    pub sample: String,
+    pub last_seen: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencyPercentiles {
+    pub samples: usize,
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticsReport {
+    pub since: i64,
+    pub opcodes: Vec<OpcodeStats>,
+    pub decode_errors: Vec<DecodeError>,
+    pub unknown_npcs: Vec<u32>,
+    pub unknown_skills: Vec<u32>,
+    pub unknown_buffs: Vec<u32>,
+    /// handler time in microseconds over the recent packets
+    pub latency: LatencyPercentiles,
+}
+
+impl Diagnostics {
+    fn new() -> Self {
+        Self {
+            since: Utc::now().timestamp_millis(),
+            opcodes: HashMap::new(),
+            failures: HashMap::new(),
+            unknown: BTreeSet::new(),
+            latencies: VecDeque::with_capacity(DIAGNOSTICS_LATENCY_SAMPLES),
+        }
+    }
+
+    fn record_packet(&mut self, op: Pkt, len: usize, elapsed: Duration) {
+        let micros = elapsed.as_micros() as u64;
+
+        let counter = self.opcodes.entry(op).or_default();
+        counter.count += 1;
+        counter.bytes += len as u64;
+        counter.total_micros += micros;
+        counter.max_micros = counter.max_micros.max(micros);
+
+        if self.latencies.len() == DIAGNOSTICS_LATENCY_SAMPLES {
+            self.latencies.pop_front();
+        }
+        self.latencies.push_back(micros);
+    }
+
+    fn record_error(&mut self, op: Pkt, err: &anyhow::Error, data: &[u8]) {
+        let failure = self.failures.entry(op).or_insert_with(|| DecodeFailure {
+            count: 0,
+            message: String::new(),
+            sample: vec![],
+            last_seen: 0,
+        });
+        failure.count += 1;
+        failure.message = format!("{:#}", err);
+        failure.sample = data[..data.len().min(DIAGNOSTICS_SAMPLE_BYTES)].to_vec();
+        failure.last_seen = Utc::now().timestamp_millis();
+    }
+
+    fn record_unknown(&mut self, id: UnknownId) {
+        let key = match id {
+            UnknownId::Npc(id) => (0, id),
+            UnknownId::Skill(id) => (1, id),
+            UnknownId::Buff(id) => (2, id),
+        };
+
+        if self.unknown.len() < DIAGNOSTICS_MAX_UNKNOWN_IDS {
+            self.unknown.insert(key);
+        }
+    }
+
+    fn report(&self) -> DiagnosticsReport {
+        let mut opcodes: Vec<OpcodeStats> = self.opcodes
+            .iter()
+            .map(|(op, counter)| OpcodeStats {
+                opcode: format!("{:?}", op),
+                count: counter.count,
+                bytes: counter.bytes,
+                avg_micros: counter.total_micros / counter.count.max(1),
+                max_micros: counter.max_micros,
+            })
+            .collect();
+        opcodes.sort_unstable_by(|a, b| b.count.cmp(&a.count));
+
+        let mut decode_errors: Vec<DecodeError> = self.failures
+            .iter()
+            .map(|(op, failure)| DecodeError {
+                opcode: format!("{:?}", op),
+                count: failure.count,
+                message: failure.message.clone(),
+                sample: failure.sample.iter().map(|byte| format!("{:02x}", byte)).collect(),
+                last_seen: failure.last_seen,
+            })
+            .collect();
+        decode_errors.sort_unstable_by(|a, b| b.count.cmp(&a.count));
+
+        let unknown = |wanted: u8| self.unknown
+            .iter()
+            .filter(|(kind, _)| *kind == wanted)
+            .map(|(_, id)| *id)
+            .collect::<Vec<_>>();
+
+        DiagnosticsReport {
+            since: self.since,
+            opcodes,
+            decode_errors,
+            unknown_npcs: unknown(0),
+            unknown_skills: unknown(1),
+            unknown_buffs: unknown(2),
+            latency: percentiles(self.latencies.iter().copied().collect()),
+        }
+    }
+}
+
+pub fn record_packet(op: Pkt, len: usize, elapsed: Duration) {
+    if let Ok(mut diagnostics) = DIAGNOSTICS.lock() {
+        diagnostics.record_packet(op, len, elapsed);
+    }
+}
+
+pub fn record_error(op: Pkt, err: &anyhow::Error, data: &[u8]) {
+    if let Ok(mut diagnostics) = DIAGNOSTICS.lock() {
+        diagnostics.record_error(op, err, data);
+    }
+}
+
+/// bounded, a new game patch can bring in thousands of ids the data files don't know yet
+pub fn record_unknown(id: UnknownId) {
+    if let Ok(mut diagnostics) = DIAGNOSTICS.lock() {
+        diagnostics.record_unknown(id);
+    }
+}
+
+pub fn report() -> DiagnosticsReport {
+    DIAGNOSTICS.lock().unwrap_or_else(|err| err.into_inner()).report()
+}
+
+pub fn reset() {
+    let mut diagnostics = DIAGNOSTICS.lock().unwrap_or_else(|err| err.into_inner());
+    *diagnostics = Diagnostics::new();
+}
+
+fn percentiles(mut samples: Vec<u64>) -> LatencyPercentiles {
+    if samples.is_empty() {
+        return LatencyPercentiles::default();
+    }
+
+    samples.sort_unstable();
+    let at = |percentile: usize| samples[(samples.len() - 1) * percentile / 100];
+
+    LatencyPercentiles {
+        samples: samples.len(),
+        p50: at(50),
+        p90: at(90),
+        p99: at(99),
+        max: samples[samples.len() - 1],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_compute_percentiles() {
+        let latency = percentiles((1..=100).rev().collect());
+
+        assert_eq!(latency.samples, 100);
+        assert_eq!(latency.p50, 50);
+        assert_eq!(latency.p90, 90);
+        assert_eq!(latency.p99, 99);
+        assert_eq!(latency.max, 100);
+        assert_eq!(percentiles(vec![]).samples, 0);
+    }
+
+    // a local instance, the handler tests feed the global one concurrently
+    #[test]
+    fn should_count_packets_per_opcode() {
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.record_packet(Pkt::SkillDamageNotify, 100, Duration::from_micros(10));
+        diagnostics.record_packet(Pkt::SkillDamageNotify, 50, Duration::from_micros(30));
+        diagnostics.record_packet(Pkt::NewPC, 20, Duration::from_micros(5));
+
+        let report = diagnostics.report();
+
+        assert_eq!(report.opcodes.len(), 2);
+        let damage = &report.opcodes[0];
+        assert_eq!(damage.opcode, format!("{:?}", Pkt::SkillDamageNotify));
+        assert_eq!(damage.count, 2);
+        assert_eq!(damage.bytes, 150);
+        assert_eq!(damage.avg_micros, 20);
+        assert_eq!(damage.max_micros, 30);
+        assert_eq!(report.latency.samples, 3);
+        assert_eq!(report.latency.max, 30);
+    }
+
+    #[test]
+    fn should_keep_the_start_of_the_last_failed_payload() {
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.record_error(Pkt::NewPC, &anyhow::anyhow!("first"), &[1, 2, 3]);
+        diagnostics.record_error(Pkt::NewPC, &anyhow::anyhow!("second"), &[0xab; DIAGNOSTICS_SAMPLE_BYTES * 2]);
+
+        let report = diagnostics.report();
+
+        assert_eq!(report.decode_errors.len(), 1);
+        let error = &report.decode_errors[0];
+        assert_eq!(error.count, 2);
+        assert_eq!(error.message, "second");
+        assert_eq!(error.sample, "ab".repeat(DIAGNOSTICS_SAMPLE_BYTES));
+        assert!(error.last_seen > 0);
+    }
+
+    #[test]
+    fn should_cap_unknown_ids() {
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.record_unknown(UnknownId::Npc(1));
+        diagnostics.record_unknown(UnknownId::Npc(1));
+        diagnostics.record_unknown(UnknownId::Skill(2));
+        diagnostics.record_unknown(UnknownId::Buff(3));
+
+        let report = diagnostics.report();
+        assert_eq!(report.unknown_npcs, vec![1]);
+        assert_eq!(report.unknown_skills, vec![2]);
+        assert_eq!(report.unknown_buffs, vec![3]);
+
+        for id in 0..DIAGNOSTICS_MAX_UNKNOWN_IDS as u32 * 2 {
+            diagnostics.record_unknown(UnknownId::Skill(id));
+        }
+
+        let report = diagnostics.report();
+        let total = report.unknown_npcs.len() + report.unknown_skills.len() + report.unknown_buffs.len();
+        assert_eq!(total, DIAGNOSTICS_MAX_UNKNOWN_IDS);
+        assert_eq!(report.unknown_npcs, vec![1]);
+    }
+}
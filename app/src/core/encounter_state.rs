@@ -6,7 +6,6 @@ use meter_core::packets::common::SkillMoveOptionData;
 use meter_core::packets::definitions::*;
 use meter_core::packets::structures::*;
 use moka::sync::Cache;
-use rsntp::SntpClient;
 use tokio::task;
 use std::cmp::max;
 use std::collections::BTreeMap;
@@ -16,6 +15,7 @@ use std::rc::Rc;
 
 use crate::constants::WORKSHOP_BUFF_ID;
 use crate::constants::{DB_NAME, TIMEOUT_DELAY_MS};
+use crate::misc::crash::EncounterSummary;
 use crate::misc::data::*;
 use crate::database::SaveToDb;
 use crate::entity::npc::Boss;
@@ -24,6 +24,9 @@ use crate::entity::{self, Entity, EntityVariant};
 use crate::models::*;
 use crate::models::TripodIndex;
 use crate::models::TripodLevel;
+use crate::core::decryption::DecryptionError;
+use crate::core::diagnostics::{self, UnknownId};
+use crate::core::timing::Clock;
 use crate::core::utils::*;
 
 pub type StatusEffectRegistry = HashMap<u32, StatusEffectDetails>;
@@ -92,6 +95,10 @@ pub struct EncounterState {
     pub saved: bool,
     pub party_freeze: bool,
     pub raid_clear: bool,
+    /// when the last boss died, combat after it doesn't count towards the clear time
+    cleared_on: Option<DateTime<Utc>>,
+    /// a trigger signal decided clear or wipe, the raid result doesn't overrule it
+    outcome_triggered: bool,
     pub valid_zone: bool,
     damage_log: HashMap<u64, Vec<(i64, i64)>>,
     cast_log: HashMap<u64, HashMap<u32, Vec<i32>>>,
@@ -100,12 +107,14 @@ pub struct EncounterState {
     pub raid_difficulty: RaidDifficulty,
     pub boss_only_damage: bool,
     pub region: Option<String>,
-    sntp_client: SntpClient,
-    ntp_fight_start: i64,
+    /// the key of the damage encryption, handed to a decryptor that is swapped in later
+    pub zone_instance_id: Option<u32>,
+    pub clock: Clock,
     pub rdps_valid: bool,
     custom_id_map: HashMap<u32, u32>,
     pub raid_end_cd: DateTime<Utc>,
     pub damage_is_valid: bool,
+    damage_invalid_reason: Option<String>,
     entity_id_to_party_id: HashMap<u64, u32>,
     players_by_character_id: HashMap<u64, PlayerSlim>,
     local_status_effect_registry: HashMap<u64, StatusEffectRegistry>,
@@ -154,6 +163,8 @@ impl EncounterState {
             party_freeze: false,
             is_resetting: false,
             raid_clear: false,
+            cleared_on: None,
+            outcome_triggered: false,
             boss_dead_update: false,
             saved: false,
             damage_log: HashMap::new(),
@@ -163,11 +174,12 @@ impl EncounterState {
             raid_difficulty: RaidDifficulty::Unknown,
             boss_only_damage: false,
             region: None,
-            sntp_client: SntpClient::new(),
-            ntp_fight_start: 0,
+            zone_instance_id: None,
+            clock: Clock::new(),
             rdps_valid: false,
             custom_id_map: HashMap::new(),
             damage_is_valid: true,
+            damage_invalid_reason: None,
             damage_stats: EncounterDamageStats::default(),
             current_boss: CurrentBoss { 
                 id: 0,
@@ -310,7 +322,8 @@ impl EncounterState {
             return None
         }
 
-        let duration = self.updated_on - self.started_on;
+        let ended_on = self.ended_on();
+        let duration = ended_on - self.started_on;
     
         let entities: Vec<EncounterEntity> = self.entities.values().filter_map(|pr | {
             match pr.deref() {
@@ -370,13 +383,15 @@ impl EncounterState {
             
         }).collect();
 
+        // a manual save can happen before any boss showed up
         let boss_max_hp = self.entities
             .get(&self.current_boss.id)
             .and_then(|pr| pr.as_boss())
-            .unwrap()
-            .encounter_stats.max_hp;
+            .map(|boss| boss.encounter_stats.max_hp)
+            .unwrap_or_default();
 
-        let duration_seconds = max(duration.num_seconds() / 1000, 1);
+        let duration_seconds = max(duration.num_seconds(), 1);
+        let ntp_fight_start = self.clock.to_ntp(self.started_on).map(|started_on| started_on.timestamp_millis());
         let dps = self.damage_stats.total_damage_dealt / duration_seconds;
         let skill_cast_log = self.get_cast_log();
         let current_boss_name = get_main_boss_name(&self.current_boss.name);
@@ -398,8 +413,9 @@ impl EncounterState {
             } else {
                 Some("invalid_stats".to_string())
             },
-            ntp_fight_start: Some(self.ntp_fight_start),
+            ntp_fight_start,
             manual_save: Some(is_manual),
+            damage_invalid_reason: self.damage_invalid_reason.clone(),
             ..Default::default()
         };
 
@@ -412,19 +428,22 @@ impl EncounterState {
             current_boss_name: self.current_boss.name.to_string(),
             local_player: self.local.name.clone(),
             started_on: self.started_on,
-            updated_on: self.updated_on,
-            encounter_damage_stats: self.damage_stats.clone(),
+            updated_on: ended_on,
+            encounter_damage_stats: EncounterDamageStats {
+                dps,
+                ..self.damage_stats.clone()
+            },
             misc, 
             damage_log: self.damage_log.clone(),
             cast_log: self.cast_log.clone(),
             boss_hp_log: self.boss_hp_log.clone(),
             raid_clear: self.raid_clear,
-            party_info: todo!(),
-            player_info: todo!(),
+            party_info: self.get_party(),
+            player_info: None,
             raid_difficulty: self.raid_difficulty,
-            region: self.region,
-            version: self.version,
-            ntp_fight_start: 0,
+            region: self.region.clone(),
+            version: self.version.clone(),
+            ntp_fight_start,
             rdps_valid: self.rdps_valid,
             is_manual: is_manual,
             skill_cast_log
@@ -450,6 +469,85 @@ impl EncounterState {
         self.started_on != DateTime::<Utc>::MIN_UTC
     }
 
+    pub fn on_boss_kill(&mut self, killed_on: DateTime<Utc>) {
+        self.raid_clear = true;
+        self.cleared_on = Some(killed_on);
+    }
+
+    /// damage that can't be decrypted is dropped, the first reason is kept for the saved encounter
+    pub fn on_damage_rejected(&mut self, err: DecryptionError) {
+        if self.damage_is_valid {
+            warn!("dropping damage: {}", err);
+        }
+
+        self.damage_is_valid = false;
+        self.damage_invalid_reason.get_or_insert_with(|| err.to_string());
+    }
+
+    pub fn damage_invalid_reason(&self) -> Option<&str> {
+        self.damage_invalid_reason.as_deref()
+    }
+
+    pub fn reset_damage_validity(&mut self) {
+        self.damage_is_valid = true;
+        self.damage_invalid_reason = None;
+    }
+
+    /// clear and wipe signals of the game's triggers
+    pub fn on_trigger_outcome(&mut self, clear: bool) {
+        self.raid_clear = clear;
+        self.outcome_triggered = true;
+    }
+
+    /// the result decides over clear or wipe unless a trigger already did
+    pub fn on_raid_result(&mut self, ended_on: DateTime<Utc>, outcome: RaidOutcome) {
+        if self.outcome_triggered {
+            return;
+        }
+
+        match outcome {
+            RaidOutcome::Clear => {
+                self.raid_clear = true;
+                self.cleared_on.get_or_insert(ended_on);
+            }
+            RaidOutcome::Wipe => {
+                self.raid_clear = false;
+                self.cleared_on = None;
+            }
+            RaidOutcome::Unknown(value) => warn!("unknown raid result {}", value),
+        }
+    }
+
+    fn ended_on(&self) -> DateTime<Utc> {
+        self.cleared_on
+            .filter(|cleared_on| self.raid_clear && *cleared_on > self.started_on && *cleared_on < self.updated_on)
+            .unwrap_or(self.updated_on)
+    }
+
+    /// the part of the state that goes into crash reports
+    pub fn summary(&self, paused: bool) -> Option<EncounterSummary> {
+        if !self.has_fight_started() {
+            return None;
+        }
+
+        Some(EncounterSummary {
+            current_boss: self.current_boss.name.to_string(),
+            local_player: self.local.name.clone(),
+            raid_difficulty: self.raid_difficulty.as_ref().to_string(),
+            region: self.region.clone(),
+            started_on: self.started_on.timestamp_millis(),
+            entities: self.entities.len(),
+            total_damage_dealt: self.damage_stats.total_damage_dealt,
+            saved: self.saved,
+            paused,
+        })
+    }
+
+    /// what would be lost if the app stopped right now
+    pub fn has_unsaved_fight(&self) -> bool {
+        self.has_fight_started() && !self.saved && self.damage_stats.total_damage_dealt > 0
+    }
+
     // use this to make sure damage packets are not tracked after a raid just wiped
     pub fn has_restarted(&self, now: DateTime<Utc>) -> bool {
         now - self.raid_end_cd < self.ignore_damage_timeout
@@ -877,7 +975,7 @@ impl EncounterState {
         let mut local_player = self
             .entities
             .remove(&self.local.id)
-            .unwrap_or_else(|| Entity::unknown_local(id, Utc::now()));
+            .unwrap_or_else(|| Entity::unknown_local(id, now));
 
         info!("init env: eid: {}->{}", self.local.id, id);
 
@@ -886,6 +984,7 @@ impl EncounterState {
         
         self.entities.insert(id, local_player);
         self.clear();
+        self.clock.reanchor();
     }
 
     pub fn init_pc(
@@ -1536,17 +1635,22 @@ impl EncounterState {
 
         self.started_on = DateTime::<Utc>::MIN_UTC;
         self.boss_only_damage = self.boss_only_damage;
-        self.entities = HashMap::new();
+        self.entities.retain(|_, entity| match &**entity {
+            EntityVariant::Player(_) | EntityVariant::Esther(_) => true,
+            EntityVariant::Boss(_) => keep_bosses,
+            _ => false,
+        });
         self.current_boss.id = 0;
         self.current_boss.name = String::from("").into();
         self.damage_stats = Default::default();
         self.raid_clear = false;
+        self.cleared_on = None;
+        self.outcome_triggered = false;
 
         self.damage_log = HashMap::new();
         self.cast_log = HashMap::new();
         self.boss_hp_log = HashMap::new();
         self.party_info = HashMap::new();
-        self.ntp_fight_start = 0;
         self.rdps_valid = false;
 
         self.custom_id_map = HashMap::new();
@@ -1853,6 +1957,7 @@ impl EncounterState {
                     damage_stats.buffs.insert(*buff_id, status_effect);
                 } else {
                     damage_stats.unknown_buffs.insert(*buff_id);
+                    diagnostics::record_unknown(UnknownId::Buff(original_buff_id));
                 }   
             }
 
@@ -1904,6 +2009,7 @@ impl EncounterState {
                     damage_stats.debuffs.insert(*buff_id, status_effect);
                 } else {
                     damage_stats.unknown_buffs.insert(*buff_id);
+                    diagnostics::record_unknown(UnknownId::Buff(original_buff_id));
                 }   
             }
 
@@ -2033,4 +2139,63 @@ impl EncounterState {
         self.entities.get_mut(&id).and_then(|pr| pr.as_player_mut())
     }
 
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_variant(id: u64, variant: EntityVariant) -> Entity {
+        let mut entity = Entity::unknown(id, Utc::now());
+        *entity = variant;
+        entity
+    }
+
+    #[test]
+    fn should_keep_players_and_esthers_across_soft_resets() {
+        let mut state = EncounterState::new("test".to_string());
+
+        let mut local = Player { id: 1, is_local: true, class_id: 102, ..Default::default() };
+        local.encounter_stats.damage_stats.dealt = 100;
+        let mut other = Player { id: 2, class_id: 102, ..Default::default() };
+        other.encounter_stats.damage_stats.dealt = 100;
+        let esther = entity::npc::Esther { id: 3, damage_dealt: 100, ..Default::default() };
+        let mut boss = Boss { id: 4, ..Default::default() };
+        boss.encounter_stats.max_hp = 1000;
+        boss.encounter_stats.dealt = 100;
+
+        state.entities.insert(1, with_variant(1, EntityVariant::Player(local)));
+        state.entities.insert(2, with_variant(2, EntityVariant::Player(other)));
+        state.entities.insert(3, with_variant(3, EntityVariant::Esther(esther)));
+        state.entities.insert(4, with_variant(4, EntityVariant::Boss(boss)));
+        state.entities.insert(5, Entity::unknown(5, Utc::now()));
+
+        state.soft_reset(true);
+        let mut kept: Vec<u64> = state.entities.keys().copied().collect();
+        kept.sort();
+        assert_eq!(kept, vec![1, 2, 3, 4]);
+
+        state.soft_reset(false);
+        assert!(state.get_entity_mut(&4).is_none());
+
+        // the kept entities start the next fight without damage, only the local player is saved
+        state.started_on = Utc::now();
+        state.updated_on = state.started_on + Duration::seconds(60);
+        let model = state.get_encounter(true).unwrap();
+        let saved: Vec<u64> = model.entities.iter().map(|entity| entity.id).collect();
+        assert_eq!(saved, vec![1]);
+    }
+
+    #[test]
+    fn should_save_duration_in_seconds() {
+        let mut state = EncounterState::new("test".to_string());
+        state.started_on = Utc::now();
+        state.updated_on = state.started_on + Duration::seconds(90);
+        state.damage_stats.total_damage_dealt = 900_000;
+
+        let model = state.get_encounter(true).unwrap();
+
+        assert_eq!(model.duration_seconds, 90);
+        assert_eq!(model.encounter_damage_stats.dps, 10_000);
+    }
+}
@@ -8,13 +8,13 @@ use std::any::type_name;
 use chrono::{DateTime, Utc};
 use hashbrown::HashMap;
 use log::*;
-use meter_core::decryption::DamageEncryptionHandler;
 use meter_core::packets::structures::NpcStruct;
 use meter_core::packets::{definitions::*, opcodes::Pkt};
-use tauri::{AppHandle, Emitter, EventTarget, Manager};
+use tauri::{AppHandle, Emitter, EventTarget, Manager, Runtime};
 use anyhow::Result;
 use tokio::task;
 
+use crate::core::decryption::DamageDecryptor;
 use crate::core::stats_api::StatsApi;
 use crate::misc::data::VALID_ZONES;
 use crate::database::{Database, SaveToDb};
@@ -28,13 +28,13 @@ use crate::misc::region::RegionManager;
 use crate::models::TripodIndex;
 use crate::models::TripodLevel;
 
-pub fn handle(
+pub fn handle<R: Runtime>(
     recorded_on: DateTime<Utc>,
     op: Pkt,
-    app: AppHandle,
+    app: AppHandle<R>,
     state: &mut EncounterState,
     data: &[u8],
-    damage_handler: &DamageEncryptionHandler,
+    damage_decryptor: &dyn DamageDecryptor,
     local_manager: &mut LocalManager,
     region_manager: &mut RegionManager,
     stats_api: &Arc<StatsApi>,
@@ -70,7 +70,7 @@ pub fn handle(
             let PKTInitEnv { player_id} = PKTInitEnv::new(data)?;
             
             state.raid_difficulty = RaidDifficulty::Unknown;
-            state.damage_is_valid = true;
+            state.reset_damage_validity();
 
             state.init_env(recorded_on, player_id);
 
@@ -255,15 +255,27 @@ pub fn handle(
             state.valid_zone = VALID_ZONES.contains(&raid_id);
         }
         Pkt::RaidBossKillNotify => {
+            let PKTRaidBossKillNotify { npc_id, kill_time } = PKTRaidBossKillNotify::new(data)?;
 
             app.emit_to(EventTarget::Any, "phase-transition", 1)?;
 
-            state.raid_clear = true;
+            // the server stamps the kill, the packet can arrive a while after it
+            let killed_on = DateTime::from_timestamp_millis(kill_time)
+                .and_then(|killed_on| state.clock.ntp_to_local(killed_on))
+                .unwrap_or(recorded_on);
+            state.on_boss_kill(killed_on);
 
-            info!("phase: 1 - RaidBossKillNotify");
+            info!("phase: 1 - RaidBossKillNotify - npc: {} at {}", npc_id, kill_time);
         }
         Pkt::RaidResult => {
-            
+            let PKTRaidResult { raid_result, total_time, .. } = PKTRaidResult::new(data)?;
+
+            // only used without a kill, the result comes after the rewards screen
+            let ended_on = (total_time > 0 && state.has_fight_started())
+                .then(|| state.started_on.checked_add_signed(chrono::Duration::milliseconds(total_time)))
+                .flatten()
+                .unwrap_or(recorded_on);
+            state.on_raid_result(ended_on, raid_result.into());
             state.party_freeze = true;
             // state.party_info = state.get_party();
 
@@ -356,8 +368,8 @@ pub fn handle(
             for mut event in skill_damage_abnormal_move_events.into_iter() {
                 let target_id = event.skill_damage_event.target_id;
 
-                if !damage_handler.decrypt_damage_event(&mut event.skill_damage_event) {
-                    state.damage_is_valid = false;
+                if let Err(err) = damage_decryptor.decrypt_damage_event(&mut event.skill_damage_event) {
+                    state.on_damage_rejected(err);
                     continue;
                 }
 
@@ -412,8 +424,8 @@ pub fn handle(
             for mut event in skill_damage_events.into_iter() {
                 let target_id = event.target_id;
 
-                if !damage_handler.decrypt_damage_event(&mut event) {
-                    state.damage_is_valid = false;
+                if let Err(err) = damage_decryptor.decrypt_damage_event(&mut event) {
+                    state.on_damage_rejected(err);
                     continue;
                 }
 
@@ -506,7 +518,12 @@ pub fn handle(
             state.on_status_effect_remove(object_id, reason, instance_ids, recorded_on);
         }
         Pkt::TriggerBossBattleStatus => {
+            let PKTTriggerBossBattleStatus { step, skip_battle_phase, .. } = PKTTriggerBossBattleStatus::new(data)?;
             
+            if skip_battle_phase {
+                debug!("boss battle step {} skipped", step);
+            }
+
             if state.is_saydon_glitch() {
                 app.emit_to(EventTarget::Any, "phase-transition", 3)?;
                 
@@ -518,7 +535,7 @@ pub fn handle(
                 state.is_resetting = true;
 
                 info!(
-                    "phase: 3 - resetting encounter - TriggerBossBattleStatus"
+                    "phase: 3 - resetting encounter - TriggerBossBattleStatus - step: {}", step
                 );
             }
         }
@@ -529,7 +546,7 @@ pub fn handle(
                 57 | 59 | 61 | 63 | 74 | 76 => {
                     state.party_freeze = true;
                     // state.party_info = state.get_party();
-                    state.raid_clear = true;
+                    state.on_trigger_outcome(true);
 
                     app.emit_to(EventTarget::Any, "phase-transition", 2)?;
 
@@ -547,7 +564,7 @@ pub fn handle(
                 58 | 60 | 62 | 64 | 75 | 77 => {
                     state.party_freeze = true;
                     // state.party_info = state.get_party();
-                    state.raid_clear = false;
+                    state.on_trigger_outcome(false);
 
                     app.emit_to(EventTarget::Any, "phase-transition", 4)?;
         
@@ -632,10 +649,306 @@ pub fn handle(
         }
         Pkt::NewTransit => {
             let packet = PKTNewTransit::new(data)?;
-            damage_handler.update_zone_instance_id(packet.channel_id);
+            damage_decryptor.update_zone_instance_id(packet.channel_id);
+            state.zone_instance_id = Some(packet.channel_id);
         }
         _ => {}
     }
     
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use bincode::{config, encode_to_vec, Encode};
+    use proptest::prelude::*;
+    use proptest::strategy::Union;
+    use tauri::test::{mock_app, MockRuntime};
+
+    use crate::constants::{LOCAL_PLAYERS_NAME, REGION_NAME};
+    use crate::core::decryption::{DecryptionError, NoopDecryptor, XorDecryptor};
+    use crate::database::{create_test_database, create_test_database_manager, DatabaseManager, TempDatabase};
+    use crate::misc::settings::StatsApiSettings;
+    use crate::models::{HitFlag, HitOption};
+    use crate::simulator::packet::*;
+    use crate::simulator::Packet;
+
+    use super::*;
+
+    /// everything `handle` needs next to the packet, the files are removed on drop
+    struct Harness {
+        app: tauri::App<MockRuntime>,
+        dir: PathBuf,
+        state: EncounterState,
+        damage_decryptor: Box<dyn DamageDecryptor>,
+        local_manager: LocalManager,
+        region_manager: RegionManager,
+        stats_api: Arc<StatsApi>,
+        database: TempDatabase<Arc<Database>>,
+        _database_manager: TempDatabase<Arc<DatabaseManager>>,
+        now: DateTime<Utc>,
+    }
+
+    impl Harness {
+        fn new(damage_decryptor: Box<dyn DamageDecryptor>) -> Self {
+            let dir = std::env::temp_dir().join(format!("drama-meter-handler-{}", uuid::Uuid::new_v4()));
+            std::fs::create_dir_all(&dir).unwrap();
+
+            let settings = StatsApiSettings { offline: true, ..Default::default() };
+            let database_manager = create_test_database_manager();
+
+            Self {
+                app: mock_app(),
+                state: EncounterState::new("test".to_string()),
+                damage_decryptor,
+                local_manager: LocalManager::new(dir.join(LOCAL_PLAYERS_NAME)).unwrap(),
+                region_manager: RegionManager::new(dir.join(REGION_NAME)),
+                stats_api: Arc::new(StatsApi::new(settings, database_manager.clone())),
+                database: create_test_database(),
+                _database_manager: database_manager,
+                now: Utc::now(),
+                dir,
+            }
+        }
+
+        /// each packet arrives 100ms after the previous one
+        fn handle(&mut self, (op, data): Packet) -> Result<()> {
+            self.now += chrono::Duration::milliseconds(100);
+
+            handle(
+                self.now,
+                op,
+                self.app.handle().clone(),
+                &mut self.state,
+                &data,
+                self.damage_decryptor.as_ref(),
+                &mut self.local_manager,
+                &mut self.region_manager,
+                &self.stats_api,
+                self.database.clone())
+        }
+    }
+
+    impl Drop for Harness {
+        fn drop(&mut self) {
+            std::fs::remove_dir_all(&self.dir).ok();
+        }
+    }
+
+    fn encode_init_env(player_id: u64) -> Packet {
+        (Pkt::InitEnv, encode_to_vec(PKTInitEnv { player_id }, config::standard()).unwrap())
+    }
+
+    fn encode_trigger_start_notify(signal: u32) -> Packet {
+        (Pkt::TriggerStartNotify, encode_to_vec(PKTTriggerStartNotify { signal }, config::standard()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn should_drop_damage_without_the_zone_key() {
+        let mut harness = Harness::new(Box::new(XorDecryptor::new()));
+        let damage = encode_skill_damage_packet(1, 0, 2, HitFlag::Normal, HitOption::None, 900, 1000, 100);
+
+        harness.handle(damage.clone()).unwrap();
+        assert!(!harness.state.damage_is_valid);
+        assert_eq!(harness.state.damage_invalid_reason(), Some(DecryptionError::MissingKey.to_string().as_str()));
+
+        // the next zone starts over and brings its key
+        harness.handle(encode_init_env(1)).unwrap();
+        harness.handle(encode_new_transit(ZONE_INSTANCE_ID)).unwrap();
+        harness.handle(damage.clone()).unwrap();
+        assert!(harness.state.damage_is_valid);
+        assert_eq!(harness.state.damage_invalid_reason(), None);
+
+        harness.handle(encode_new_transit(ZONE_INSTANCE_ID + 1)).unwrap();
+        harness.handle(damage).unwrap();
+        assert!(!harness.state.damage_is_valid);
+        assert_eq!(harness.state.damage_invalid_reason(), Some(DecryptionError::InvalidValue.to_string().as_str()));
+    }
+
+    #[tokio::test]
+    async fn should_end_a_clear_at_the_kill() {
+        let mut harness = Harness::new(Box::new(XorDecryptor::new()));
+        harness.state.clock.set_ntp_offset(chrono::Duration::zero());
+        let damage = encode_skill_damage_packet(1, 0, 2, HitFlag::Normal, HitOption::None, 900, 1000, 100);
+
+        harness.handle(encode_new_transit(ZONE_INSTANCE_ID)).unwrap();
+        harness.handle(damage.clone()).unwrap();
+        let started_on = harness.state.started_on;
+
+        // the kill notify arrives a second after the server saw the boss die
+        let killed_on = DateTime::from_timestamp_millis((started_on + chrono::Duration::seconds(90)).timestamp_millis()).unwrap();
+        harness.now = killed_on + chrono::Duration::seconds(1);
+        harness.handle(encode_raid_boss_kill_notify(2, killed_on.timestamp_millis())).unwrap();
+        harness.handle(damage).unwrap();
+        harness.handle(encode_raid_result(1, 0, 90_000)).unwrap();
+
+        let model = harness.state.get_encounter(true).unwrap();
+        assert!(model.raid_clear);
+        assert_eq!(model.updated_on, killed_on);
+        assert_eq!(model.duration, killed_on - started_on);
+    }
+
+    #[tokio::test]
+    async fn should_not_clear_a_wipe_after_a_kill() {
+        let mut harness = Harness::new(Box::new(XorDecryptor::new()));
+        let damage = encode_skill_damage_packet(1, 0, 2, HitFlag::Normal, HitOption::None, 900, 1000, 100);
+
+        harness.handle(encode_new_transit(ZONE_INSTANCE_ID)).unwrap();
+        harness.handle(damage.clone()).unwrap();
+        harness.handle(encode_raid_boss_kill_notify(2, 0)).unwrap();
+        assert!(harness.state.raid_clear);
+
+        harness.handle(damage).unwrap();
+        let last_hit = harness.now;
+        harness.handle(encode_raid_result(1, 1, 0)).unwrap();
+
+        let model = harness.state.get_encounter(true).unwrap();
+        assert!(!model.raid_clear);
+        assert_eq!(model.updated_on, last_hit);
+    }
+
+    #[tokio::test]
+    async fn should_keep_a_triggered_clear_over_a_wipe_result() {
+        let mut harness = Harness::new(Box::new(XorDecryptor::new()));
+        let damage = encode_skill_damage_packet(1, 0, 2, HitFlag::Normal, HitOption::None, 900, 1000, 100);
+
+        harness.handle(encode_new_transit(ZONE_INSTANCE_ID)).unwrap();
+        harness.handle(damage).unwrap();
+        harness.handle(encode_trigger_start_notify(57)).unwrap();
+        harness.handle(encode_raid_result(1, 1, 0)).unwrap();
+        assert!(harness.state.raid_clear);
+
+        // the next fight is decided by its own result again
+        harness.state.soft_reset(true);
+        harness.handle(encode_raid_result(1, 1, 0)).unwrap();
+        assert!(!harness.state.raid_clear);
+    }
+
+    const OPCODES: [Pkt; 32] = [
+        Pkt::CounterAttackNotify,
+        Pkt::DeathNotify,
+        Pkt::IdentityGaugeChangeNotify,
+        Pkt::InitEnv,
+        Pkt::InitPC,
+        Pkt::NewPC,
+        Pkt::NewNpc,
+        Pkt::NewNpcSummon,
+        Pkt::NewProjectile,
+        Pkt::NewTrap,
+        Pkt::RaidBegin,
+        Pkt::RaidBossKillNotify,
+        Pkt::RaidResult,
+        Pkt::RemoveObject,
+        Pkt::SkillCastNotify,
+        Pkt::SkillStartNotify,
+        Pkt::SkillDamageAbnormalMoveNotify,
+        Pkt::SkillDamageNotify,
+        Pkt::PartyInfo,
+        Pkt::PartyLeaveResult,
+        Pkt::PartyStatusEffectAddNotify,
+        Pkt::PartyStatusEffectRemoveNotify,
+        Pkt::PartyStatusEffectResultNotify,
+        Pkt::StatusEffectAddNotify,
+        Pkt::StatusEffectRemoveNotify,
+        Pkt::TriggerBossBattleStatus,
+        Pkt::TriggerStartNotify,
+        Pkt::ZoneMemberLoadStatusNotify,
+        Pkt::ZoneObjectUnpublishNotify,
+        Pkt::StatusEffectSyncDataNotify,
+        Pkt::TroopMemberUpdateMinNotify,
+        Pkt::NewTransit,
+    ];
+
+    fn packet() -> impl Strategy<Value = Packet> {
+        (prop::sample::select(OPCODES.to_vec()), prop::collection::vec(any::<u8>(), 0..256))
+    }
+
+    fn encoded<T: Arbitrary + Encode + 'static>(op: Pkt) -> BoxedStrategy<Packet> {
+        any::<T>().prop_map(move |packet| (op, encode_to_vec(packet, config::standard()).unwrap())).boxed()
+    }
+
+    /// damage that decrypts to sane values, arbitrary damage events are almost always rejected
+    fn damage_packet() -> BoxedStrategy<Packet> {
+        (1..16u64, 1..16u64, 0..1_000_000i64, 0..1_000_000i64)
+            .prop_map(|(source_id, target_id, damage, current_hp)| {
+                encode_skill_damage_packet(source_id, 0, target_id, HitFlag::Normal, HitOption::None, current_hp, 1_000_000, damage)
+            })
+            .boxed()
+    }
+
+    /// packets that decode, status effects carry shield values that are summed up
+    /// and stay with `should_survive_random_packets`
+    fn well_formed_packet() -> impl Strategy<Value = Packet> {
+        Union::new(vec![
+            encoded::<PKTCounterAttackNotify>(Pkt::CounterAttackNotify),
+            encoded::<PKTDeathNotify>(Pkt::DeathNotify),
+            encoded::<PKTIdentityGaugeChangeNotify>(Pkt::IdentityGaugeChangeNotify),
+            encoded::<PKTInitEnv>(Pkt::InitEnv),
+            encoded::<PKTInitPC>(Pkt::InitPC),
+            encoded::<PKTNewPC>(Pkt::NewPC),
+            encoded::<PKTNewNpc>(Pkt::NewNpc),
+            encoded::<PKTNewNpcSummon>(Pkt::NewNpcSummon),
+            encoded::<PKTNewProjectile>(Pkt::NewProjectile),
+            encoded::<PKTNewTrap>(Pkt::NewTrap),
+            encoded::<PKTRaidBegin>(Pkt::RaidBegin),
+            encoded::<PKTRaidBossKillNotify>(Pkt::RaidBossKillNotify),
+            encoded::<PKTRaidResult>(Pkt::RaidResult),
+            encoded::<PKTRemoveObject>(Pkt::RemoveObject),
+            encoded::<PKTSkillCastNotify>(Pkt::SkillCastNotify),
+            encoded::<PKTSkillStartNotify>(Pkt::SkillStartNotify),
+            encoded::<PKTPartyInfo>(Pkt::PartyInfo),
+            encoded::<PKTPartyLeaveResult>(Pkt::PartyLeaveResult),
+            encoded::<PKTTriggerBossBattleStatus>(Pkt::TriggerBossBattleStatus),
+            encoded::<PKTTriggerStartNotify>(Pkt::TriggerStartNotify),
+            encoded::<PKTZoneMemberLoadStatusNotify>(Pkt::ZoneMemberLoadStatusNotify),
+            encoded::<PKTZoneObjectUnpublishNotify>(Pkt::ZoneObjectUnpublishNotify),
+            encoded::<PKTTroopMemberUpdateMinNotify>(Pkt::TroopMemberUpdateMinNotify),
+            damage_packet(),
+        ])
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn should_survive_random_packets(packets in prop::collection::vec(packet(), 1..64)) {
+            // saving spawns tasks, they are dropped with the runtime
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+            let _guard = runtime.enter();
+            let mut harness = Harness::new(Box::new(NoopDecryptor));
+
+            for packet in packets {
+                // decode errors are expected, panics are not
+                let _ = harness.handle(packet);
+                harness.state.get_ongoing_encounter(harness.now);
+            }
+
+            harness.state.get_encounter(true);
+
+            // whatever came before, a zone change must still go through
+            prop_assert!(harness.handle(encode_init_env(1)).is_ok());
+        }
+
+        #[test]
+        fn should_keep_state_consistent(packets in prop::collection::vec(well_formed_packet(), 1..64)) {
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+            let _guard = runtime.enter();
+            let mut harness = Harness::new(Box::new(XorDecryptor::new()));
+            harness.handle(encode_new_transit(ZONE_INSTANCE_ID)).unwrap();
+
+            for packet in packets {
+                let _ = harness.handle(packet);
+                harness.state.get_ongoing_encounter(harness.now);
+
+                prop_assert!(harness.state.damage_stats.total_damage_dealt >= 0);
+                prop_assert!(harness.state.damage_stats.top_damage_dealt <= harness.state.damage_stats.total_damage_dealt);
+            }
+
+            // the local player survives the zone change and is the only entity left
+            harness.handle(encode_init_env(1)).unwrap();
+            prop_assert!(harness.state.get_entity_mut(&1).is_some());
+        }
+    }
+}
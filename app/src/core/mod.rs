@@ -2,4 +2,15 @@ pub mod encounter_state;
 pub mod stats_api;
 pub mod utils;
 pub mod handler;
-pub mod background_worker;
\ No newline at end of file
+pub mod background_worker;
+pub mod statistics;
+pub mod comparison;
+pub mod notifications;
+pub mod retention;
+pub mod backup;
+pub mod recompression;
+pub mod control;
+pub mod checkpoint;
+pub mod decryption;
+pub mod timing;
+pub mod diagnostics;
\ No newline at end of file
@@ -0,0 +1,399 @@
+use std::collections::HashMap as StdHashMap;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use log::*;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use strfmt::strfmt;
+use tokio::sync::mpsc;
+
+use crate::constants::{NOTIFICATION_MAX_ATTEMPTS, NOTIFICATION_RETRY_BASE_MS};
+use crate::core::utils::{boss_to_raid_map, is_valid_player};
+use crate::database::SaveToDb;
+
+pub const DEFAULT_TEMPLATE: &str = "{boss} ({difficulty}) cleared in {duration}, top dps {top_player} {top_dps}, {local_player} #{rank} with {local_dps}";
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WebhookFormat {
+    /// `{ "content": message }`, understood by discord
+    #[default]
+    Discord,
+    /// the message together with every template field
+    Json,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WebhookSettings {
+    pub name: String,
+    pub url: String,
+    pub enabled: bool,
+    pub format: WebhookFormat,
+    pub template: String,
+    /// raid names as in the raid map, empty for all
+    pub raids: Vec<String>,
+    pub cleared_only: bool,
+    pub personal_best_only: bool,
+}
+
+impl Default for WebhookSettings {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            url: String::new(),
+            enabled: true,
+            format: WebhookFormat::Discord,
+            template: DEFAULT_TEMPLATE.to_string(),
+            raids: vec![],
+            cleared_only: true,
+            personal_best_only: false,
+        }
+    }
+}
+
+impl WebhookSettings {
+    pub fn matches(&self, context: &NotificationContext) -> bool {
+        self.enabled
+            && !self.url.is_empty()
+            && (!self.cleared_only || context.cleared)
+            && (!self.personal_best_only || context.personal_best)
+            && (self.raids.is_empty() || self.raids.contains(&context.raid))
+    }
+
+    /// falls back to the default template if the configured one has unknown fields
+    pub fn render(&self, context: &NotificationContext) -> String {
+        let fields = context.fields();
+
+        match strfmt(&self.template, &fields) {
+            Ok(message) => message,
+            Err(err) => {
+                warn!("invalid template for webhook {}: {}", self.name, err);
+                strfmt(DEFAULT_TEMPLATE, &fields).unwrap_or_default()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationContext {
+    pub encounter_id: i64,
+    pub boss: String,
+    pub raid: String,
+    pub difficulty: String,
+    pub duration_seconds: i64,
+    pub cleared: bool,
+    pub top_player: String,
+    pub top_dps: i64,
+    pub local_player: String,
+    pub local_dps: i64,
+    /// position of the local player by damage, 0 if not part of the encounter
+    pub rank: usize,
+    pub personal_best: bool,
+    pub personal_percentile: Option<f64>,
+}
+
+impl NotificationContext {
+    pub fn new(model: &SaveToDb) -> Self {
+        let mut players: Vec<_> = model.entities.iter().filter(|e| is_valid_player(e)).collect();
+        players.sort_unstable_by_key(|e| std::cmp::Reverse(e.damage_stats.damage_dealt));
+
+        let top = players.first();
+        let rank = players
+            .iter()
+            .position(|e| e.name == model.local_player)
+            .map(|index| index + 1)
+            .unwrap_or_default();
+        let local_dps = players
+            .iter()
+            .find(|e| e.name == model.local_player)
+            .map(|e| e.damage_stats.dps)
+            .unwrap_or_default();
+
+        Self {
+            encounter_id: 0,
+            boss: model.current_boss_name.clone(),
+            raid: boss_to_raid_map(&model.current_boss_name, model.boss_max_hp).unwrap_or_default(),
+            difficulty: model.raid_difficulty.as_ref().to_string(),
+            duration_seconds: model.duration_seconds,
+            cleared: model.raid_clear,
+            top_player: top.map(|e| e.name.clone()).unwrap_or_default(),
+            top_dps: top.map(|e| e.damage_stats.dps).unwrap_or_default(),
+            local_player: model.local_player.clone(),
+            local_dps,
+            rank,
+            personal_best: false,
+            personal_percentile: None,
+        }
+    }
+
+    pub fn fields(&self) -> StdHashMap<String, String> {
+        let duration = format!("{}:{:02}", self.duration_seconds / 60, self.duration_seconds % 60);
+        let percentile = self
+            .personal_percentile
+            .map(|percentile| format!("{:.0}", percentile))
+            .unwrap_or_default();
+
+        StdHashMap::from([
+            ("encounter_id".to_string(), self.encounter_id.to_string()),
+            ("boss".to_string(), self.boss.clone()),
+            ("raid".to_string(), self.raid.clone()),
+            ("difficulty".to_string(), self.difficulty.clone()),
+            ("duration".to_string(), duration),
+            ("top_player".to_string(), self.top_player.clone()),
+            ("top_dps".to_string(), abbreviate(self.top_dps)),
+            ("local_player".to_string(), self.local_player.clone()),
+            ("local_dps".to_string(), abbreviate(self.local_dps)),
+            ("rank".to_string(), self.rank.to_string()),
+            ("personal_best".to_string(), if self.personal_best { "PB".to_string() } else { String::new() }),
+            ("percentile".to_string(), percentile),
+        ])
+    }
+}
+
+fn abbreviate(value: i64) -> String {
+    match value {
+        value if value >= 1_000_000_000 => format!("{:.2}b", value as f64 / 1e9),
+        value if value >= 1_000_000 => format!("{:.2}m", value as f64 / 1e6),
+        value if value >= 1_000 => format!("{:.1}k", value as f64 / 1e3),
+        value => value.to_string(),
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Delivery {
+    webhook: String,
+    url: String,
+    body: serde_json::Value,
+    attempts: u32,
+}
+
+/// queues webhook deliveries, failed ones are retried in the background with exponential backoff
+pub struct Notifier {
+    sender: mpsc::UnboundedSender<Delivery>,
+}
+
+impl Notifier {
+    pub fn new() -> Self {
+        Self::with_retry(Duration::from_millis(NOTIFICATION_RETRY_BASE_MS), NOTIFICATION_MAX_ATTEMPTS)
+    }
+
+    pub fn with_retry(base: Duration, max_attempts: u32) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        tauri::async_runtime::spawn(deliver(Client::new(), sender.clone(), receiver, base, max_attempts));
+
+        Self { sender }
+    }
+
+    /// returns the number of queued deliveries
+    pub fn notify(&self, webhooks: &[WebhookSettings], context: &NotificationContext) -> usize {
+        let mut queued = 0;
+
+        for webhook in webhooks.iter().filter(|webhook| webhook.matches(context)) {
+            let message = webhook.render(context);
+            let body = match webhook.format {
+                WebhookFormat::Discord => json!({ "content": message }),
+                WebhookFormat::Json => json!({ "message": message, "encounter": context }),
+            };
+
+            let delivery = Delivery {
+                webhook: webhook.name.clone(),
+                url: webhook.url.clone(),
+                body,
+                attempts: 0,
+            };
+
+            if self.sender.send(delivery).is_ok() {
+                queued += 1;
+            }
+        }
+
+        queued
+    }
+}
+
+async fn deliver(
+    client: Client,
+    sender: mpsc::UnboundedSender<Delivery>,
+    mut receiver: mpsc::UnboundedReceiver<Delivery>,
+    base: Duration,
+    max_attempts: u32) {
+    while let Some(mut delivery) = receiver.recv().await {
+        let retry_after = match post(&client, &delivery).await {
+            Ok(()) => continue,
+            Err(DeliveryError::Retry(retry_after)) => retry_after,
+            Err(DeliveryError::Rejected) => {
+                warn!("dropping notification for webhook {}", delivery.webhook);
+                continue;
+            }
+        };
+
+        delivery.attempts += 1;
+
+        if delivery.attempts >= max_attempts {
+            warn!("giving up on notification for webhook {} after {} attempts", delivery.webhook, delivery.attempts);
+            continue;
+        }
+
+        let delay = retry_after.unwrap_or(base * 2u32.pow(delivery.attempts.min(10)));
+        let sender = sender.clone();
+
+        // waiting on its own task keeps other deliveries flowing
+        tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(delay).await;
+            sender.send(delivery).ok();
+        });
+    }
+}
+
+enum DeliveryError {
+    /// optionally after the delay the server asked for
+    Retry(Option<Duration>),
+    Rejected,
+}
+
+async fn post(client: &Client, delivery: &Delivery) -> Result<(), DeliveryError> {
+    let response = match client.post(&delivery.url).json(&delivery.body).send().await {
+        Ok(response) => response,
+        Err(err) => {
+            debug!("webhook {} is unreachable: {}", delivery.webhook, err);
+            return Err(DeliveryError::Retry(None));
+        }
+    };
+
+    let status = response.status();
+
+    if status.is_success() {
+        return Ok(());
+    }
+
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        let retry_after = response
+            .headers()
+            .get("Retry-After")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<f64>().ok())
+            .map(Duration::from_secs_f64);
+
+        return Err(DeliveryError::Retry(retry_after));
+    }
+
+    if status.is_server_error() {
+        return Err(DeliveryError::Retry(None));
+    }
+
+    let text = response.text().await.unwrap_or_default();
+    warn!("webhook {} responded with {}: {}", delivery.webhook, status, text);
+
+    Err(DeliveryError::Rejected)
+}
+
+pub fn validate_webhook(webhook: &WebhookSettings) -> Result<()> {
+    if !webhook.url.starts_with("http://") && !webhook.url.starts_with("https://") {
+        return Err(anyhow!("webhook url must start with http:// or https://"));
+    }
+
+    strfmt(&webhook.template, &NotificationContext::default().fields())
+        .map_err(|err| anyhow!("invalid template: {}", err))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use axum::http::StatusCode as AxumStatusCode;
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use tokio::net::TcpListener;
+    use tokio::sync::Mutex;
+
+    use super::*;
+
+    fn create_context() -> NotificationContext {
+        NotificationContext {
+            encounter_id: 1,
+            boss: "Thaemine, the Lightqueller".to_string(),
+            raid: "Thaemine G1".to_string(),
+            difficulty: "Hard".to_string(),
+            duration_seconds: 305,
+            cleared: true,
+            top_player: "Bob".to_string(),
+            top_dps: 12_500_000,
+            local_player: "Alice".to_string(),
+            local_dps: 9_000_000,
+            rank: 2,
+            personal_best: false,
+            personal_percentile: Some(50.0),
+        }
+    }
+
+    #[test]
+    fn should_render_and_filter() {
+        let context = create_context();
+        let webhook = WebhookSettings {
+            url: "http://localhost".to_string(),
+            template: "{boss} {duration} {local_player} #{rank} {top_dps}".to_string(),
+            ..Default::default()
+        };
+
+        assert_eq!(webhook.render(&context), "Thaemine, the Lightqueller 5:05 Alice #2 12.50m");
+        assert!(webhook.matches(&context));
+
+        let pb_only = WebhookSettings { personal_best_only: true, ..webhook.clone() };
+        assert!(!pb_only.matches(&context));
+
+        let other_raid = WebhookSettings { raids: vec!["Echidna G1".to_string()], ..webhook.clone() };
+        assert!(!other_raid.matches(&context));
+
+        let invalid = WebhookSettings { template: "{unknown}".to_string(), ..webhook };
+        assert!(invalid.render(&context).starts_with("Thaemine, the Lightqueller (Hard)"));
+    }
+
+    #[tokio::test]
+    async fn should_retry_failed_deliveries() {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let received = Arc::new(Mutex::new(vec![]));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let router = Router::new().route("/webhook", post({
+            let hits = hits.clone();
+            let received = received.clone();
+            move |Json(body): Json<serde_json::Value>| async move {
+                // the first delivery fails to exercise the retry
+                if hits.fetch_add(1, Ordering::SeqCst) == 0 {
+                    return AxumStatusCode::INTERNAL_SERVER_ERROR;
+                }
+
+                received.lock().await.push(body);
+                AxumStatusCode::NO_CONTENT
+            }
+        }));
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        let notifier = Notifier::with_retry(Duration::from_millis(10), 3);
+        let webhook = WebhookSettings {
+            url: format!("http://{address}/webhook"),
+            ..Default::default()
+        };
+        assert_eq!(notifier.notify(&[webhook], &create_context()), 1);
+
+        for _ in 0..100 {
+            if !received.lock().await.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        let received = received.lock().await;
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+        assert!(received[0]["content"].as_str().unwrap().contains("Alice #2"));
+    }
+}
@@ -0,0 +1,113 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::*;
+
+use crate::constants::{RECOMPRESS_BATCH_SIZE, RECOMPRESS_PAUSE_MS, RECOMPRESS_STARTUP_DELAY_SECS};
+use crate::database::codec::BlobTable;
+use crate::database::Database;
+
+/// moves existing blobs to the current codec in small batches so the meter stays responsive,
+/// trains the dictionary first if there is none yet
+pub async fn run_recompression(database: Arc<Database>) {
+    tokio::time::sleep(Duration::from_secs(RECOMPRESS_STARTUP_DELAY_SECS)).await;
+
+    if !database.has_blob_dictionary() {
+        match database.train_blob_dictionary() {
+            Ok(Some(_)) => {}
+            Ok(None) => info!("not enough encounters to train a blob dictionary yet"),
+            Err(err) => warn!("could not train blob dictionary: {:?}", err),
+        }
+    }
+
+    let mut total = 0;
+
+    for table in [BlobTable::Entity, BlobTable::Encounter] {
+        match recompress(&database, table).await {
+            Ok(rewritten) => total += rewritten,
+            Err(err) => {
+                error!("failed to recompress {:?} blobs: {:?}", table, err);
+                return;
+            }
+        }
+    }
+
+    if total > 0 {
+        // space is only returned to the os by a vacuum, which the user runs from the settings
+        info!("recompressed {} rows", total);
+    }
+}
+
+async fn recompress(database: &Database, table: BlobTable) -> anyhow::Result<usize> {
+    let mut after = 0;
+    let mut total = 0;
+
+    while let Some((last, rewritten)) = database.recompress_blobs(table, after, RECOMPRESS_BATCH_SIZE)? {
+        after = last;
+        total += rewritten;
+
+        tokio::time::sleep(Duration::from_millis(RECOMPRESS_PAUSE_MS)).await;
+    }
+
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::database::codec;
+    use crate::database::{create_test_database, migration_path, test_export, TestEncounter};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn should_recompress_legacy_rows() {
+        let database = create_test_database();
+
+        // plain json text as written by versions before 1.13.5
+        let skills = json!({ "1": { "id": 1, "name": "Skill", "totalDamage": 1000 } });
+        let mut alice = TestEncounter::player("Alice", "Bard", "", 1680.0);
+        alice.insert("class_id".to_string(), json!(204));
+        alice.insert("skills".to_string(), json!(skills.to_string()));
+
+        let mut entry = TestEncounter { entities: vec![alice], ..Default::default() }.build();
+        entry.encounter.insert("buffs".to_string(), json!("{}"));
+        let id = database.import_encounters(test_export(vec![entry])).await.unwrap().imported[0];
+
+        assert_eq!(recompress(&database, BlobTable::Entity).await.unwrap(), 1);
+        assert_eq!(recompress(&database, BlobTable::Encounter).await.unwrap(), 1);
+        // already in the current format
+        assert_eq!(recompress(&database, BlobTable::Entity).await.unwrap(), 0);
+
+        let encounter = database.load_encounter(id.to_string()).await.unwrap();
+        assert_eq!(encounter.entities["Alice"].skills[&1].total_damage, 1000);
+    }
+
+    #[tokio::test]
+    async fn should_read_blobs_recompressed_by_another_instance() {
+        let database = create_test_database();
+        let skills = json!({ "1": { "id": 1, "name": "Skill", "totalDamage": 1000 } });
+        let mut alice = TestEncounter::player("Alice", "Bard", "", 1680.0);
+        alice.insert("skills".to_string(), json!(skills.to_string()));
+        let entry = TestEncounter { entities: vec![alice], ..Default::default() }.build();
+        let id = database.import_encounters(test_export(vec![entry])).await.unwrap().imported[0];
+
+        // a profile switch can leave two instances on the same file
+        let other = Database::new(database.path().clone());
+        other.setup(migration_path()).unwrap();
+
+        let samples: Vec<Vec<u8>> = (0..1000)
+            .map(|index| {
+                let skill = json!({ "id": index, "name": format!("Skill {}", index % 13), "totalDamage": index * 1000 });
+                serde_json::to_vec(&json!({ (index % 7).to_string(): skill })).unwrap()
+            })
+            .collect();
+        let dictionary = codec::train_dictionary(&samples, 16 * 1024).unwrap();
+        other.add_blob_dictionary(&dictionary, samples.len()).unwrap();
+        assert_eq!(recompress(&other, BlobTable::Entity).await.unwrap(), 1);
+
+        let encounter = database.load_encounter(id.to_string()).await.unwrap();
+        assert_eq!(encounter.entities["Alice"].skills[&1].total_damage, 1000);
+    }
+}
@@ -0,0 +1,210 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use hashbrown::{HashMap, HashSet};
+use log::*;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, EventTarget, Manager};
+
+use crate::constants::RETENTION_STARTUP_DELAY_SECS;
+use crate::database::{Database, DatabaseManager};
+use crate::misc::settings::{RetentionSettings, SettingsManager};
+
+#[derive(Debug, Clone)]
+pub struct RetentionRow {
+    pub id: i64,
+    pub fight_start: i64,
+    pub boss: String,
+    pub favorite: bool,
+    pub cleared: bool,
+    pub personal_best: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RetentionReason {
+    MaxAge,
+    MaxPerBoss,
+    MaxSize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionEntry {
+    pub id: i64,
+    pub fight_start: i64,
+    pub boss: String,
+    pub reason: RetentionReason,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionReport {
+    pub dry_run: bool,
+    pub total: usize,
+    pub entries: Vec<RetentionEntry>,
+}
+
+impl RetentionSettings {
+    pub fn has_rules(&self) -> bool {
+        self.max_age_days.is_some() || self.max_per_boss.is_some() || self.max_size_mb.is_some()
+    }
+}
+
+/// applies the retention policy from the settings periodically, runs until the app exits
+pub async fn run_retention(app_handle: AppHandle, manager: Arc<DatabaseManager>) {
+    tokio::time::sleep(Duration::from_secs(RETENTION_STARTUP_DELAY_SECS)).await;
+
+    loop {
+        let policy = match app_handle.state::<Mutex<SettingsManager>>().lock().unwrap().get() {
+            Ok(settings) => settings.retention.clone(),
+            Err(_) => RetentionSettings::default(),
+        };
+
+        if policy.enabled && policy.has_rules() {
+            match apply(&manager.get(), &policy).await {
+                Ok(report) if !report.entries.is_empty() => {
+                    app_handle.emit_to(EventTarget::Any, "retention-applied", &report).ok();
+                }
+                Ok(_) => {}
+                Err(err) => error!("failed to apply retention: {:?}", err),
+            }
+        }
+
+        let hours = policy.interval_hours.max(1) as u64;
+        tokio::time::sleep(Duration::from_secs(hours * 3600)).await;
+    }
+}
+
+/// deletes and optimizes, the freed pages are only reclaimed by the vacuum in `optimize`
+pub async fn apply(database: &Database, policy: &RetentionSettings) -> anyhow::Result<RetentionReport> {
+    let report = database.apply_retention(policy, false).await?;
+
+    if !report.entries.is_empty() {
+        database.optimize().await?;
+    }
+
+    Ok(report)
+}
+
+fn is_protected(row: &RetentionRow, policy: &RetentionSettings) -> bool {
+    (policy.keep_favorites && row.favorite)
+        || (policy.keep_cleared && row.cleared)
+        || (policy.keep_personal_bests && row.personal_best)
+}
+
+/// rules apply in order age, per boss, size; each encounter is reported once with the first matching rule
+pub fn plan(mut rows: Vec<RetentionRow>, policy: &RetentionSettings, now: i64, db_size: u64) -> Vec<RetentionEntry> {
+    rows.sort_unstable_by_key(|row| std::cmp::Reverse(row.fight_start));
+
+    let total = rows.len();
+    let mut deleted: HashSet<i64> = HashSet::new();
+    let mut entries = vec![];
+
+    let mut delete = |row: &RetentionRow, reason: RetentionReason, deleted: &mut HashSet<i64>| {
+        if deleted.insert(row.id) {
+            entries.push(RetentionEntry {
+                id: row.id,
+                fight_start: row.fight_start,
+                boss: row.boss.clone(),
+                reason,
+            });
+        }
+    };
+
+    if let Some(days) = policy.max_age_days {
+        let min_fight_start = now - days as i64 * 86_400_000;
+
+        for row in rows.iter().filter(|row| row.fight_start < min_fight_start && !is_protected(row, policy)) {
+            delete(row, RetentionReason::MaxAge, &mut deleted);
+        }
+    }
+
+    if let Some(max_per_boss) = policy.max_per_boss {
+        let mut kept: HashMap<&str, u32> = HashMap::new();
+
+        for row in rows.iter() {
+            if deleted.contains(&row.id) {
+                continue;
+            }
+
+            let count = kept.entry(row.boss.as_str()).or_default();
+
+            if *count < max_per_boss || is_protected(row, policy) {
+                *count += 1;
+            } else {
+                delete(row, RetentionReason::MaxPerBoss, &mut deleted);
+            }
+        }
+    }
+
+    if let Some(max_size_mb) = policy.max_size_mb.filter(|_| total > 0) {
+        // rough estimate, encounters vary a lot in size but it converges over many deletions
+        let average_size = db_size / total as u64;
+        let max_size = max_size_mb * 1024 * 1024;
+        let mut remaining = (total - deleted.len()) as u64;
+
+        for row in rows.iter().rev() {
+            if remaining * average_size <= max_size {
+                break;
+            }
+
+            if deleted.contains(&row.id) || is_protected(row, policy) {
+                continue;
+            }
+
+            delete(row, RetentionReason::MaxSize, &mut deleted);
+            remaining -= 1;
+        }
+    }
+
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(id: i64, days_ago: i64, boss: &str, favorite: bool) -> RetentionRow {
+        RetentionRow {
+            id,
+            fight_start: 100 * 86_400_000 - days_ago * 86_400_000,
+            boss: boss.to_string(),
+            favorite,
+            cleared: false,
+            personal_best: false,
+        }
+    }
+
+    #[test]
+    fn should_plan_deletions() {
+        let now = 100 * 86_400_000;
+        let rows = vec![
+            row(1, 40, "Thaemine", false),
+            row(2, 40, "Thaemine", true),
+            row(3, 3, "Thaemine", false),
+            row(4, 2, "Thaemine", false),
+            row(5, 0, "Thaemine", false),
+            row(6, 1, "Echidna", false),
+        ];
+        let policy = RetentionSettings {
+            max_age_days: Some(30),
+            max_per_boss: Some(2),
+            keep_favorites: true,
+            ..Default::default()
+        };
+
+        let entries = plan(rows.clone(), &policy, now, 0);
+        let ids: Vec<(i64, RetentionReason)> = entries.iter().map(|entry| (entry.id, entry.reason)).collect();
+        assert_eq!(ids, vec![(1, RetentionReason::MaxAge), (3, RetentionReason::MaxPerBoss)]);
+
+        let policy = RetentionSettings {
+            max_size_mb: Some(2),
+            keep_favorites: true,
+            ..Default::default()
+        };
+        let entries = plan(rows, &policy, now, 6 * 1024 * 1024);
+        let ids: Vec<i64> = entries.iter().map(|entry| entry.id).collect();
+        assert_eq!(ids, vec![1, 3, 4, 6]);
+    }
+}
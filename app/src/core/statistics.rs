@@ -0,0 +1,213 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use hashbrown::HashMap;
+use log::*;
+use serde::Serialize;
+
+use crate::constants::{RANKING_BACKFILL_BATCH_SIZE, RANKING_BACKFILL_PAUSE_MS, RANKING_BACKFILL_STARTUP_DELAY_SECS};
+use crate::database::Database;
+use crate::models::PlayerProgression;
+
+/// number of most recent logs used for the rolling average
+pub const RECENT_WINDOW: usize = 5;
+
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DpsSummary {
+    pub difficulty: String,
+    pub spec: Option<String>,
+    pub count: usize,
+    pub best: i64,
+    pub median: i64,
+    pub recent_average: i64,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct EncounterRanking {
+    pub personal_best: bool,
+    pub personal_percentile: Option<f64>,
+    pub local_percentile: Option<f64>,
+}
+
+/// a first log has nothing to beat, so it is never flagged as a personal best
+pub fn rank(dps: i64, personal_history: &[i64], local_history: &[i64]) -> EncounterRanking {
+    EncounterRanking {
+        personal_best: !personal_history.is_empty() && is_personal_best(personal_history, dps),
+        personal_percentile: percentile_rank(personal_history, dps),
+        local_percentile: percentile_rank(local_history, dps),
+    }
+}
+
+pub fn median(values: &[i64]) -> Option<i64> {
+    if values.is_empty() {
+        return None;
+    }
+
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable();
+    let middle = sorted.len() / 2;
+
+    if sorted.len() % 2 == 0 {
+        Some((sorted[middle - 1] + sorted[middle]) / 2)
+    } else {
+        Some(sorted[middle])
+    }
+}
+
+/// average of the last `window` values, expects chronological order
+pub fn recent_average(values: &[i64], window: usize) -> Option<i64> {
+    if values.is_empty() || window == 0 {
+        return None;
+    }
+
+    let recent = &values[values.len().saturating_sub(window)..];
+    Some(recent.iter().sum::<i64>() / recent.len() as i64)
+}
+
+/// share of `history` below `value` in percent, ties count as half
+pub fn percentile_rank(history: &[i64], value: i64) -> Option<f64> {
+    if history.is_empty() {
+        return None;
+    }
+
+    let below = history.iter().filter(|&&v| v < value).count() as f64;
+    let equal = history.iter().filter(|&&v| v == value).count() as f64;
+
+    Some((below + equal * 0.5) / history.len() as f64 * 100.0)
+}
+
+pub fn is_personal_best(history: &[i64], value: i64) -> bool {
+    history.iter().all(|&v| value > v)
+}
+
+/// groups the progression by difficulty and spec, expects chronological order
+pub fn summarize(progression: &[PlayerProgression]) -> Vec<DpsSummary> {
+    let mut groups: HashMap<(String, Option<String>), Vec<i64>> = HashMap::new();
+
+    for entry in progression {
+        groups
+            .entry((entry.difficulty.clone(), entry.spec.clone()))
+            .or_default()
+            .push(entry.dps);
+    }
+
+    let mut summaries: Vec<DpsSummary> = groups
+        .into_iter()
+        .map(|((difficulty, spec), values)| DpsSummary {
+            difficulty,
+            spec,
+            count: values.len(),
+            best: values.iter().copied().max().unwrap_or_default(),
+            median: median(&values).unwrap_or_default(),
+            recent_average: recent_average(&values, RECENT_WINDOW).unwrap_or_default(),
+        })
+        .collect();
+
+    summaries.sort_by(|a, b| (&a.difficulty, &a.spec).cmp(&(&b.difficulty, &b.spec)));
+
+    summaries
+}
+
+/// adds the stats of encounters saved before they were recorded, which changes the history
+/// of every later encounter, so all of them are ranked again
+pub async fn run_ranking_backfill(database: Arc<Database>) {
+    tokio::time::sleep(Duration::from_secs(RANKING_BACKFILL_STARTUP_DELAY_SECS)).await;
+
+    match backfill_rankings(&database).await {
+        Ok(0) => {}
+        Ok(backfilled) => info!("backfilled the stats of {} encounters", backfilled),
+        Err(err) => error!("failed to backfill rankings: {:?}", err),
+    }
+}
+
+async fn backfill_rankings(database: &Database) -> anyhow::Result<usize> {
+    let mut after = 0;
+    let mut total = 0;
+
+    while let Some((last, backfilled)) = database.backfill_encounter_stats(after, RANKING_BACKFILL_BATCH_SIZE)? {
+        after = last;
+        total += backfilled;
+
+        tokio::time::sleep(Duration::from_millis(RANKING_BACKFILL_PAUSE_MS)).await;
+    }
+
+    if total == 0 {
+        return Ok(0);
+    }
+
+    let mut after = 0;
+
+    while let Some((last, _)) = database.rank_encounters(after, RANKING_BACKFILL_BATCH_SIZE)? {
+        after = last;
+
+        tokio::time::sleep(Duration::from_millis(RANKING_BACKFILL_PAUSE_MS)).await;
+    }
+
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::database::{create_test_database, test_export, TestEncounter};
+
+    use super::*;
+
+    #[test]
+    fn should_rank_against_history() {
+        let history = [100, 200, 300, 400];
+
+        assert_eq!(median(&history), Some(250));
+        assert_eq!(recent_average(&history, 2), Some(350));
+        assert_eq!(percentile_rank(&history, 300), Some(62.5));
+        assert_eq!(percentile_rank(&[], 300), None);
+        assert!(is_personal_best(&history, 401));
+        assert!(!is_personal_best(&history, 400));
+        assert!(!rank(500, &[], &history).personal_best);
+    }
+
+    #[tokio::test]
+    async fn should_backfill_rankings_of_old_encounters() {
+        let database = create_test_database();
+        let day = 24 * 60 * 60 * 1000;
+
+        // imported rows have no stats, like the ones saved before the stats tables existed
+        let encounters = [100, 300, 200]
+            .into_iter()
+            .enumerate()
+            .map(|(index, dps)| {
+                let mut alice = TestEncounter::player("Alice", "Bard", "Desperate Salvation", 1680.0);
+                alice.insert("class_id".to_string(), json!(204));
+                alice.insert("character_id".to_string(), json!(1));
+                alice.insert("damage_stats".to_string(), json!({ "damageDealt": dps * 300, "dps": dps }));
+
+                TestEncounter {
+                    fight_start: 1_700_000_000_000 + index as i64 * day,
+                    boss: "Thaemine the Lightqueller".to_string(),
+                    dps,
+                    entities: vec![alice],
+                    ..Default::default()
+                }
+                .build()
+            })
+            .collect();
+        let ids = database.import_encounters(test_export(encounters)).await.unwrap().imported;
+
+        assert_eq!(backfill_rankings(&database).await.unwrap(), 3);
+
+        let rankings: Vec<EncounterRanking> = ids
+            .iter()
+            .map(|id| database.get_encounter_ranking(*id).unwrap())
+            .collect();
+        assert!(!rankings[0].personal_best);
+        assert_eq!(rankings[0].personal_percentile, None);
+        assert!(rankings[1].personal_best);
+        assert_eq!(rankings[1].personal_percentile, Some(100.0));
+        assert!(!rankings[2].personal_best);
+        assert_eq!(rankings[2].personal_percentile, Some(50.0));
+
+        assert_eq!(backfill_rankings(&database).await.unwrap(), 0);
+    }
+}
@@ -1,5 +1,8 @@
 use crate::core::utils::{boss_to_raid_map, is_valid_player};
+use crate::database::DatabaseManager;
+use crate::misc::settings::StatsApiSettings;
 use crate::models::{ArkPassiveData, Encounter, EntityType};
+use chrono::Utc;
 use hashbrown::HashMap;
 use log::{info, warn};
 use moka::sync::Cache;
@@ -8,8 +11,9 @@ use serde::de::{MapAccess, Visitor};
 use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::json;
 use std::fmt;
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
 
-// pub const API_URL: &str = "http://localhost:5180";
 pub const API_URL: &str = "https://api.snow.xyz";
 
 #[derive(Clone)]
@@ -17,16 +21,22 @@ pub struct StatsApi {
     pub client_id: String,
     client: Client,
     pub valid_zone: bool,
+    settings: StatsApiSettings,
+    database: Arc<DatabaseManager>,
     stats_cache: Cache<String, PlayerStats>,
 }
 
 impl StatsApi {
-    pub fn new() -> Self {
+    pub fn new(settings: StatsApiSettings, database: Arc<DatabaseManager>) -> Self {
+        let ttl = StdDuration::from_secs(settings.cache_ttl_hours as u64 * 3600);
+
         Self {
             client_id: String::new(),
             client: Client::new(),
             valid_zone: false,
-            stats_cache: Cache::builder().max_capacity(64).build(),
+            settings,
+            database,
+            stats_cache: Cache::builder().max_capacity(64).time_to_live(ttl).build(),
         }
     }
 
@@ -38,54 +48,153 @@ impl StatsApi {
                 || difficulty == "Trial")
     }
 
-    pub async fn get_character_info(
-        &self,
-        version: &str,
-        region: String,
-        raid_name: String,
-        difficulty: &str,
-        cleared: bool,
-        current_boss_name: &str,
-        player_names: Vec<String>
-    ) -> Option<HashMap<String, PlayerStats>> {
-     
+    pub fn endpoint(&self, region: &str) -> &str {
+        self.settings
+            .region_endpoints
+            .get(region)
+            .unwrap_or(&self.settings.endpoint)
+    }
+
+    pub fn settings(&self) -> &StatsApiSettings {
+        &self.settings
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.settings.offline
+    }
+
+    /// memory cache first, then the sqlite cache and only then the inspect endpoint
+    pub async fn get_character_info(&self, request: &InspectRequest) -> Option<HashMap<String, PlayerStats>> {
+        let now = Utc::now().timestamp_millis();
+        let region = &request.region;
+        let mut result = HashMap::new();
+        let mut missing = vec![];
+
+        for name in request.names.iter() {
+            match self.stats_cache.get(&cache_key(region, name)) {
+                Some(stats) => {
+                    result.insert(name.clone(), stats);
+                },
+                None => missing.push(name.clone()),
+            }
+        }
+
+        if !missing.is_empty() {
+            // offline sessions take whatever was fetched before, regardless of age
+            let min_fetched_on = if self.settings.offline {
+                0
+            } else {
+                now - self.settings.cache_ttl_hours as i64 * 3_600_000
+            };
+
+            self.load_cached(region, &mut missing, min_fetched_on, &mut result);
+        }
+
+        if !missing.is_empty() && !self.settings.offline {
+            let request = InspectRequest {
+                names: missing.clone(),
+                ..request.clone()
+            };
+
+            match self.fetch_character_info(&request).await {
+                Ok(data) => result.extend(data),
+                Err(err) => {
+                    warn!("failed to get inspect data: {:?}", err);
+                    self.load_cached(region, &mut missing, 0, &mut result);
+                }
+            }
+        }
+
+        if result.is_empty() {
+            None
+        } else {
+            Some(result)
+        }
+    }
+
+    /// always hits the inspect endpoint, used by the retry queue
+    pub async fn fetch_character_info(&self, request: &InspectRequest) -> anyhow::Result<HashMap<String, PlayerStats>> {
+        let now = Utc::now().timestamp_millis();
+        let region = &request.region;
 
         let request_body = json!({
             "clientId": self.client_id,
-            "version": version.to_string(),
+            "version": request.version,
             "region": region,
-            "raidName": raid_name,
-            "boss": current_boss_name,
-            "characters": player_names,
-            "difficulty": difficulty,
-            "cleared": cleared,
+            "raidName": request.raid_name,
+            "boss": request.boss,
+            "characters": request.names,
+            "difficulty": request.difficulty,
+            "cleared": request.cleared,
         });
 
-        match self
+        let data = self.fetch(region, &request_body).await?;
+        info!("received player stats");
+
+        if let Err(err) = self.database.get().cache_player_stats(region, &data, now) {
+            warn!("failed to cache player stats: {:?}", err);
+        }
+
+        for (name, stats) in data.iter() {
+            self.stats_cache.insert(cache_key(region, name), stats.clone());
+        }
+
+        Ok(data)
+    }
+
+    async fn fetch(&self, region: &str, request_body: &serde_json::Value) -> anyhow::Result<HashMap<String, PlayerStats>> {
+        let url = format!("{}/inspect", self.endpoint(region));
+
+        let data = self
             .client
-            .post(format!("{API_URL}/inspect"))
-            .json(&request_body)
+            .post(url)
+            .json(request_body)
             .send()
-            .await
-        {
-            Ok(res) => match res.json::<HashMap<String, PlayerStats>>().await {
-                Ok(data) => {
-                    info!("received player stats");
-                    Some(data)
-                }
-                Err(e) => {
-                    warn!("failed to parse player stats: {:?}", e);
-                    None
+            .await?
+            .error_for_status()?
+            .json::<HashMap<String, PlayerStats>>()
+            .await?;
+
+        Ok(data)
+    }
+
+    fn load_cached(
+        &self,
+        region: &str,
+        missing: &mut Vec<String>,
+        min_fetched_on: i64,
+        result: &mut HashMap<String, PlayerStats>) {
+        match self.database.get().get_cached_player_stats(region, missing, min_fetched_on) {
+            Ok(cached) => {
+                for (name, stats) in cached {
+                    self.stats_cache.insert(cache_key(region, &name), stats.clone());
+                    result.insert(name, stats);
                 }
-            },
-            Err(e) => {
-                warn!("failed to get inspect data: {:?}", e);
-                None
             }
+            Err(err) => warn!("failed to read cached player stats: {:?}", err),
         }
+
+        missing.retain(|name| !result.contains_key(name));
     }
 }
 
+fn cache_key(region: &str, name: &str) -> String {
+    format!("{region}:{name}")
+}
+
+/// everything needed to repeat an inspect call, persisted by the retry queue
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct InspectRequest {
+    pub version: String,
+    pub region: String,
+    pub raid_name: String,
+    pub difficulty: String,
+    pub cleared: bool,
+    pub boss: String,
+    pub names: Vec<String>,
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct Stats {
     pub crit: u32,
@@ -96,7 +205,7 @@ pub struct Stats {
     pub add_dmg: u32,
 }
 
-#[derive(Debug, Default, Clone, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase", default)]
 pub struct PlayerStats {
     pub ark_passive_enabled: bool,
@@ -119,7 +228,7 @@ pub struct ElixirEntry {
     pub level: u8,
 }
 
-#[derive(Debug, Default, Clone, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase", default)]
 pub struct GemData {
     pub tier: u8,
@@ -184,3 +293,117 @@ impl<'de> Deserialize<'de> for Stats {
         deserializer.deserialize_map(StatsVisitor)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use tokio::net::TcpListener;
+
+    use crate::database::create_test_database_manager;
+
+    use super::*;
+
+    async fn start_mock_server(hits: Arc<AtomicUsize>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let router = Router::new().route("/inspect", post(move || {
+            hits.fetch_add(1, Ordering::SeqCst);
+            async move {
+                Json(json!({
+                    "Alice": { "arkPassiveEnabled": true, "engravings": [118] }
+                }))
+            }
+        }));
+
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        format!("http://{address}")
+    }
+
+    fn inspect_request() -> InspectRequest {
+        InspectRequest {
+            version: "0.0.1".to_string(),
+            region: "EUC".to_string(),
+            raid_name: "Thaemine G1".to_string(),
+            difficulty: "Normal".to_string(),
+            cleared: true,
+            boss: "Thaemine, the Lightqueller".to_string(),
+            names: vec!["Alice".to_string()],
+        }
+    }
+
+    async fn get_info(stats_api: &StatsApi) -> Option<HashMap<String, PlayerStats>> {
+        stats_api.get_character_info(&inspect_request()).await
+    }
+
+    #[tokio::test]
+    async fn should_cache_player_stats() {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let database = create_test_database_manager();
+        let settings = StatsApiSettings {
+            endpoint: start_mock_server(hits.clone()).await,
+            ..Default::default()
+        };
+
+        let stats_api = StatsApi::new(settings.clone(), database.clone());
+        let info = get_info(&stats_api).await.unwrap();
+        assert!(info["Alice"].ark_passive_enabled);
+        get_info(&stats_api).await.unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        let offline = StatsApiSettings {
+            offline: true,
+            ..settings.clone()
+        };
+        let stats_api = StatsApi::new(offline, database.clone());
+        let info = get_info(&stats_api).await.unwrap();
+        assert_eq!(info["Alice"].engravings, Some(vec![118]));
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn should_fall_back_to_stale_cache() {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let database = create_test_database_manager();
+        let settings = StatsApiSettings {
+            endpoint: start_mock_server(hits.clone()).await,
+            cache_ttl_hours: 0,
+            ..Default::default()
+        };
+
+        get_info(&StatsApi::new(settings, database.clone())).await.unwrap();
+
+        let unreachable = StatsApiSettings {
+            endpoint: "http://127.0.0.1:1".to_string(),
+            cache_ttl_hours: 0,
+            ..Default::default()
+        };
+        let info = get_info(&StatsApi::new(unreachable, database.clone())).await;
+        assert!(info.is_some());
+    }
+
+    #[tokio::test]
+    async fn should_not_treat_stale_cache_as_fetched() {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let database = create_test_database_manager();
+        let settings = StatsApiSettings {
+            endpoint: start_mock_server(hits.clone()).await,
+            ..Default::default()
+        };
+
+        let stats_api = StatsApi::new(settings, database.clone());
+        let info = stats_api.fetch_character_info(&inspect_request()).await.unwrap();
+        assert!(info.contains_key("Alice"));
+
+        let unreachable = StatsApiSettings {
+            endpoint: "http://127.0.0.1:1".to_string(),
+            ..Default::default()
+        };
+        let stats_api = StatsApi::new(unreachable, database.clone());
+        assert!(stats_api.fetch_character_info(&inspect_request()).await.is_err());
+    }
+}
@@ -0,0 +1,168 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::Instant;
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use log::{info, warn};
+use rsntp::{Config, SntpClient};
+
+use crate::constants::NTP_TIMEOUT_SECS;
+
+const UNKNOWN_OFFSET: i64 = i64::MIN;
+
+/// packets are stamped with the wall time read at the last zone change plus a monotonic clock,
+/// so adjusting the system clock mid fight can't stretch or shrink the fight
+#[derive(Clone)]
+pub struct Clock {
+    anchor: Instant,
+    anchored_on: DateTime<Utc>,
+    /// microseconds from local to ntp time, shared with the task querying it
+    ntp_offset: Arc<AtomicI64>,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        Self {
+            anchor: Instant::now(),
+            anchored_on: Utc::now(),
+            ntp_offset: Arc::new(AtomicI64::new(UNKNOWN_OFFSET)),
+        }
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        self.anchored_on + Duration::from_std(self.anchor.elapsed()).unwrap_or(Duration::zero())
+    }
+
+    /// picks up system clock changes, only called between fights
+    pub fn reanchor(&mut self) {
+        self.anchor = Instant::now();
+        self.anchored_on = Utc::now();
+    }
+
+    pub fn ntp_offset(&self) -> Option<Duration> {
+        match self.ntp_offset.load(Ordering::Relaxed) {
+            UNKNOWN_OFFSET => None,
+            micros => Some(Duration::microseconds(micros)),
+        }
+    }
+
+    pub fn set_ntp_offset(&self, offset: Duration) {
+        let micros = offset.num_microseconds().unwrap_or(UNKNOWN_OFFSET);
+        self.ntp_offset.store(micros, Ordering::Relaxed);
+    }
+
+    /// the same moment on the ntp clock, comparable between the logs of different raid members
+    pub fn to_ntp(&self, local: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.ntp_offset().map(|offset| local + offset)
+    }
+
+    /// the local moment of a server timestamp, the game servers are assumed to keep ntp time
+    pub fn ntp_to_local(&self, ntp: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.ntp_offset().and_then(|offset| ntp.checked_sub_signed(offset))
+    }
+
+    /// the offset is filled in once the server answers, until then fights only have a local start
+    pub fn sync(&self, server: String) {
+        let clock = self.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let timeout = std::time::Duration::from_secs(NTP_TIMEOUT_SECS);
+
+            match query_offset(&server, timeout) {
+                Ok(offset) => {
+                    info!("ntp offset: {}ms", offset.num_milliseconds());
+                    clock.set_ntp_offset(offset);
+                }
+                Err(err) => warn!("could not query {}: {:#}", server, err),
+            }
+        });
+    }
+}
+
+pub fn query_offset(server: &str, timeout: std::time::Duration) -> Result<Duration> {
+    let client = SntpClient::with_config(Config::default().timeout(timeout));
+    let result = client.synchronize(server)?;
+    let micros = (result.clock_offset().as_secs_f64() * 1_000_000.0) as i64;
+
+    Ok(Duration::microseconds(micros))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::UdpSocket;
+
+    use super::*;
+
+    const NTP_EPOCH_OFFSET: i64 = 2_208_988_800;
+
+    fn to_ntp_timestamp(time: DateTime<Utc>) -> [u8; 8] {
+        let seconds = (time.timestamp() + NTP_EPOCH_OFFSET) as u32;
+        let fraction = ((time.timestamp_subsec_nanos() as u64) << 32) / 1_000_000_000;
+
+        let mut bytes = [0; 8];
+        bytes[..4].copy_from_slice(&seconds.to_be_bytes());
+        bytes[4..].copy_from_slice(&(fraction as u32).to_be_bytes());
+        bytes
+    }
+
+    /// answers a single request with a clock that is `offset` ahead
+    fn start_fake_server(offset: Duration) -> String {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = socket.local_addr().unwrap().to_string();
+
+        std::thread::spawn(move || {
+            let mut request = [0u8; 48];
+            let (_, client) = socket.recv_from(&mut request).unwrap();
+            let now = to_ntp_timestamp(Utc::now() + offset);
+
+            let mut response = [0u8; 48];
+            // no leap second, version 4, server mode
+            response[0] = 0b00_100_100;
+            response[1] = 1;
+            response[12..16].copy_from_slice(b"TEST");
+            response[16..24].copy_from_slice(&now);
+            response[24..32].copy_from_slice(&request[40..48]);
+            response[32..40].copy_from_slice(&now);
+            response[40..48].copy_from_slice(&now);
+
+            socket.send_to(&response, client).unwrap();
+        });
+
+        address
+    }
+
+    #[test]
+    fn should_query_offset_from_server() {
+        let address = start_fake_server(Duration::seconds(10));
+        let offset = query_offset(&address, std::time::Duration::from_secs(2)).unwrap();
+
+        assert!((offset - Duration::seconds(10)).abs() < Duration::milliseconds(500));
+
+        let clock = Clock::new();
+        assert_eq!(clock.to_ntp(clock.now()), None);
+
+        clock.set_ntp_offset(offset);
+        let now = clock.now();
+        assert_eq!(clock.to_ntp(now), Some(now + offset));
+        assert_eq!(clock.ntp_to_local(now + offset), Some(now));
+    }
+
+    #[test]
+    fn should_not_go_backwards() {
+        let clock = Clock::new();
+        let mut previous = clock.now();
+
+        for _ in 0..1000 {
+            let now = clock.now();
+            assert!(now >= previous);
+            previous = now;
+        }
+    }
+}
@@ -1,20 +1,25 @@
-use crate::constants::{WINDOW_MS, WINDOW_S};
-use crate::core::stats_api::{PlayerStats, StatsApi};
+use crate::constants::*;
+use crate::core::diagnostics::{self, UnknownId};
+use crate::core::notifications::{NotificationContext, Notifier};
+use crate::core::stats_api::{InspectRequest, PlayerStats, StatsApi};
+use crate::misc::settings::SettingsManager;
 use crate::database::SaveToDb;
-use crate::{constants::TIMEOUT_DELAY_MS, database::Database};
+use crate::database::{Database, DatabaseManager};
 use crate::models::*;
 use crate::misc::data::*;
 use chrono::{DateTime, Duration, Utc};
 use hashbrown::HashMap;
-use log::{error, warn};
+use log::{error, info, warn};
+use rand::Rng;
 use meter_core::packets::structures::{StatPair, StatusEffectData};
 use moka::sync::Cache;
-use tauri::{AppHandle, Emitter, EventTarget};
+use tauri::{AppHandle, Emitter, EventTarget, Manager, Runtime};
+use tokio::sync::watch;
 use tokio::task;
 use std::collections::BTreeMap;
 use std::{cmp::{max, Ordering, Reverse}, sync::Arc};
 
-pub async fn get_player_info(stats_api: Arc<StatsApi>, model: &SaveToDb) -> Option<HashMap<String, PlayerStats>> {
+pub fn build_inspect_request(model: &SaveToDb) -> Option<InspectRequest> {
     if model.raid_difficulty == RaidDifficulty::Unknown 
         || model.current_boss_name.is_empty() {
         return None
@@ -44,47 +49,237 @@ pub async fn get_player_info(stats_api: Arc<StatsApi>, model: &SaveToDb) -> Opti
         return None;
     }
 
-    let info = stats_api.get_character_info(
-        &model.version,
+    Some(InspectRequest {
+        version: model.version.clone(),
         region,
         raid_name,
-        model.raid_difficulty.as_ref(),
-        model.raid_clear,
-        &model.current_boss_name,
-        player_names).await;
+        difficulty: model.raid_difficulty.as_ref().to_string(),
+        cleared: model.raid_clear,
+        boss: model.current_boss_name.clone(),
+        names: player_names,
+    })
+}
 
-    info
+pub fn save_to_db<R: Runtime>(
+    app_handle: AppHandle<R>,
+    stats_api: Arc<StatsApi>,
+    database: Arc<Database>,
+    model: SaveToDb) {
+    task::spawn(persist(app_handle, stats_api, database, model, true));
 }
 
-pub fn save_to_db(
-    app_handle: AppHandle,
+/// without `lookup` the stats api is skipped and the players are queued for `retry_player_info`,
+/// used on shutdown where waiting for the api isn't an option
+pub async fn persist<R: Runtime>(
+    app_handle: AppHandle<R>,
     stats_api: Arc<StatsApi>,
     database: Arc<Database>,
-    mut model: SaveToDb) {
-    let app_handle = app_handle.clone();
-    
-    task::spawn(async move {
-
-        let player_info = get_player_info(stats_api, &model).await;
-
-        calculate_stats(
-            &mut model.entities,
-            model.started_on.timestamp_millis(),
-            model.updated_on.timestamp_millis(),
-            model.duration_seconds,
-            &model.cast_log,
-            &model.skill_cast_log,
-            player_info,
-            &model.encounter_damage_stats,
-            &model.damage_log);
-
-        match database.insert_data(model) {
-            Ok(encounter_id) => {
-                app_handle.emit_to(EventTarget::Any, "clear-encounter", encounter_id).unwrap();
-            },
-            Err(err) => error!("An error occurred whilst saving to database: {}", err),
-        };
+    mut model: SaveToDb,
+    lookup: bool) -> Option<i64> {
+    let request = build_inspect_request(&model);
+    let player_info = match request.as_ref() {
+        Some(request) if lookup => stats_api.get_character_info(request).await,
+        _ => None,
+    };
+
+    // players without any info are retried later and backfilled into the saved rows
+    let pending = request.filter(|request| {
+        !stats_api.is_offline() && request.names.iter().any(|name| {
+            !player_info.as_ref().is_some_and(|info| info.contains_key(name))
+        })
     });
+
+    calculate_stats(
+        &mut model.entities,
+        model.started_on.timestamp_millis(),
+        model.updated_on.timestamp_millis(),
+        model.duration_seconds,
+        &model.cast_log,
+        &model.skill_cast_log,
+        player_info,
+        &model.encounter_damage_stats,
+        &model.damage_log);
+
+    let notification = model.raid_clear.then(|| NotificationContext::new(&model));
+
+    match database.insert_data(model) {
+        Ok(encounter_id) => {
+            if let Some(context) = notification {
+                notify(&app_handle, &database, encounter_id, context);
+            }
+
+            if let Some(request) = pending {
+                let next_attempt_on = Utc::now() + player_info_backoff(0);
+
+                if let Err(err) = database.enqueue_player_info(encounter_id, &request, next_attempt_on.timestamp_millis()) {
+                    warn!("failed to queue player info for {}: {:?}", encounter_id, err);
+                }
+            }
+
+            app_handle.emit_to(EventTarget::Any, "clear-encounter", encounter_id).ok();
+            Some(encounter_id)
+        },
+        Err(err) => {
+            error!("An error occurred whilst saving to database: {}", err);
+            None
+        }
+    }
+}
+
+fn notify<R: Runtime>(app_handle: &AppHandle<R>, database: &Database, encounter_id: i64, mut context: NotificationContext) {
+    let Some(notifier) = app_handle.try_state::<Arc<Notifier>>() else {
+        return;
+    };
+
+    let webhooks = match app_handle.state::<std::sync::Mutex<SettingsManager>>().lock().unwrap().get() {
+        Ok(settings) => settings.notifications.webhooks.clone(),
+        Err(_) => return,
+    };
+
+    if webhooks.is_empty() {
+        return;
+    }
+
+    // ranking is only known once the encounter is stored
+    if let Ok(ranking) = database.get_encounter_ranking(encounter_id) {
+        context.personal_best = ranking.personal_best;
+        context.personal_percentile = ranking.personal_percentile;
+    }
+    context.encounter_id = encounter_id;
+
+    notifier.notify(&webhooks, &context);
+}
+
+/// exponential backoff with up to 20% jitter either way
+pub fn player_info_backoff(attempts: u32) -> Duration {
+    let base = PLAYER_INFO_RETRY_BASE_SECS.saturating_mul(1 << attempts.min(16));
+    let seconds = base.min(PLAYER_INFO_RETRY_MAX_SECS) as f64;
+    let jitter = rand::rng().random_range(0.8..=1.2);
+
+    Duration::milliseconds((seconds * jitter * 1000.0) as i64)
+}
+
+/// retries queued inspect requests and backfills the saved entities, runs until the app exits,
+/// the worker replaces the stats api when its settings change
+pub async fn retry_player_info(app_handle: AppHandle, stats_api: watch::Receiver<Arc<StatsApi>>, manager: Arc<DatabaseManager>) {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(PLAYER_INFO_RETRY_POLL_SECS));
+
+    loop {
+        interval.tick().await;
+        let stats_api = stats_api.borrow().clone();
+
+        if stats_api.is_offline() {
+            continue;
+        }
+
+        // the queue lives in the database of the active profile
+        let database = manager.get();
+        let now = Utc::now();
+        let due = match database.get_due_player_info(now.timestamp_millis()) {
+            Ok(due) => due,
+            Err(err) => {
+                warn!("failed to read player info queue: {:?}", err);
+                continue;
+            }
+        };
+
+        for (encounter_id, request, attempts) in due {
+            let result = match stats_api.fetch_character_info(&request).await {
+                Ok(info) => database.backfill_player_stats(encounter_id, &info),
+                Err(err) => Err(err),
+            };
+
+            match result {
+                Ok(updated) => {
+                    info!("backfilled player info of {} players for encounter {}", updated, encounter_id);
+                    database.remove_player_info(encounter_id).ok();
+                    app_handle.emit_to(EventTarget::Any, "player-info-backfilled", encounter_id).ok();
+                },
+                Err(err) if attempts + 1 >= PLAYER_INFO_MAX_ATTEMPTS => {
+                    warn!("giving up on player info for encounter {}: {:?}", encounter_id, err);
+                    database.remove_player_info(encounter_id).ok();
+                },
+                Err(err) => {
+                    let next_attempt_on = now + player_info_backoff(attempts + 1);
+
+                    if let Err(err) = database.reschedule_player_info(
+                        encounter_id,
+                        attempts + 1,
+                        next_attempt_on.timestamp_millis(),
+                        &err.to_string()) {
+                        warn!("failed to reschedule player info for {}: {:?}", encounter_id, err);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// applies gems, engravings and ark passive from the inspect data, expects the buff based spec to be set
+pub fn apply_player_stats(entity: &mut EncounterEntity, info: &PlayerStats) {
+    let spec = entity.spec.clone().unwrap_or_else(|| "Unknown".to_string());
+
+    for gem in info.gems.iter().flatten() {
+        let skill_ids = if matches!(gem.gem_type, 34 | 35 | 65 | 63 | 61) {
+            GEM_SKILL_MAP
+                .get(&gem.skill_id)
+                .cloned()
+                .unwrap_or_default()
+        } else {
+            vec![gem.skill_id]
+        };
+
+        for skill_id in skill_ids {
+            if let Some(skill) = entity.skills.get_mut(&skill_id) {
+                match gem.gem_type {
+                    27 | 35 => { // cooldown gems
+                        skill.gem_cooldown =
+                            Some(cooldown_gem_value_to_level(gem.value, gem.tier));
+                        skill.gem_tier = Some(gem.tier);
+                    }
+                    64 | 65 => { // support effect damage gems
+                        skill.gem_damage =
+                            Some(support_damage_gem_value_to_level(gem.value));
+                        skill.gem_tier_dmg = Some(gem.tier);
+                    }
+                    _ => { // damage gems
+                        skill.gem_damage =
+                            Some(damage_gem_value_to_level(gem.value, gem.tier));
+                        skill.gem_tier_dmg = Some(gem.tier);
+                    }
+                }
+            }
+        }
+    }
+
+    entity.ark_passive_active = Some(info.ark_passive_enabled);
+
+    let engravings = get_engravings(&info.engravings);
+    if entity.class_id == 104
+        && engravings.as_ref().is_some_and(|engravings| {
+            engravings
+                .iter()
+                .any(|e| e == "Awakening" || e == "Drops of Ether")
+        })
+    {
+        entity.spec = Some("Princess".to_string());
+    } else if spec == "Unknown" {
+        // not reliable enough to be used on its own
+        if let Some(tree) = info.ark_passive_data.as_ref() {
+            if let Some(enlightenment) = tree.enlightenment.as_ref() {
+                for node in enlightenment.iter() {
+                    let spec = get_spec_from_ark_passive(node);
+                    if spec != "Unknown" {
+                        entity.spec = Some(spec);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    entity.engraving_data = engravings;
+    entity.ark_passive_data = info.ark_passive_data.clone();
 }
 
 pub fn calculate_stats(
@@ -122,75 +317,13 @@ pub fn calculate_stats(
                     calculate_average_dps(damage_log, fight_start_sec, fight_end_sec);
             }
 
-            let spec = get_player_spec(entity, &encounter_damage_stats.buffs);
-
-            entity.spec = Some(spec.clone());
+            entity.spec = Some(get_player_spec(entity, &encounter_damage_stats.buffs));
 
             if let Some(info) = player_info
                 .as_ref()
                 .and_then(|stats| stats.get(&entity.name))
             {
-                for gem in info.gems.iter().flatten() {
-                    let skill_ids = if matches!(gem.gem_type, 34 | 35 | 65 | 63 | 61) {
-                        GEM_SKILL_MAP
-                            .get(&gem.skill_id)
-                            .cloned()
-                            .unwrap_or_default()
-                    } else {
-                        vec![gem.skill_id]
-                    };
-
-                    for skill_id in skill_ids {
-                        if let Some(skill) = entity.skills.get_mut(&skill_id) {
-                            match gem.gem_type {
-                                27 | 35 => { // cooldown gems
-                                    skill.gem_cooldown =
-                                        Some(cooldown_gem_value_to_level(gem.value, gem.tier));
-                                    skill.gem_tier = Some(gem.tier);
-                                }
-                                64 | 65 => { // support effect damage gems
-                                    skill.gem_damage =
-                                        Some(support_damage_gem_value_to_level(gem.value));
-                                    skill.gem_tier_dmg = Some(gem.tier);
-                                }
-                                _ => { // damage gems
-                                    skill.gem_damage =
-                                        Some(damage_gem_value_to_level(gem.value, gem.tier));
-                                    skill.gem_tier_dmg = Some(gem.tier);
-                                }
-                            }
-                        }
-                    }
-                }
-
-                entity.ark_passive_active = Some(info.ark_passive_enabled);
-
-                let engravings = get_engravings(&info.engravings);
-                if entity.class_id == 104
-                    && engravings.as_ref().is_some_and(|engravings| {
-                        engravings
-                            .iter()
-                            .any(|e| e == "Awakening" || e == "Drops of Ether")
-                    })
-                {
-                    entity.spec = Some("Princess".to_string());
-                } else if spec == "Unknown" {
-                    // not reliable enough to be used on its own
-                    if let Some(tree) = info.ark_passive_data.as_ref() {
-                        if let Some(enlightenment) = tree.enlightenment.as_ref() {
-                            for node in enlightenment.iter() {
-                                let spec = get_spec_from_ark_passive(node);
-                                if spec != "Unknown" {
-                                    entity.spec = Some(spec);
-                                    break;
-                                }
-                            }
-                        }
-                    }
-                }
-
-                entity.engraving_data = engravings;
-                entity.ark_passive_data = info.ark_passive_data.clone();
+                apply_player_stats(entity, info);
             }
         }
 
@@ -605,6 +738,7 @@ pub fn get_skill(
             skill.skill_type.to_string()
         )
     } else {
+        diagnostics::record_unknown(UnknownId::Skill(*skill_id));
         (skill_name, "".to_string(), None, "".to_string())
     }
 }
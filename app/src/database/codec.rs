@@ -0,0 +1,242 @@
+use std::io::{Read, Write};
+use std::sync::{Arc, RwLock};
+
+use anyhow::{bail, Result};
+use flate2::read::GzDecoder;
+use hashbrown::HashMap;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use zstd::dict::{DecoderDictionary, EncoderDictionary};
+
+/// first bytes of every blob written by the codec, neither valid json nor gzip
+const MAGIC: [u8; 2] = [0xDB, 0xC0];
+const HEADER_LEN: usize = 8;
+
+/// bumped when the json shape of a blob column changes in an incompatible way
+pub const BLOB_SCHEMA_VERSION: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum Codec {
+    // 1 is left free, blobs from before the header are headerless gzip
+    Zstd = 2,
+}
+
+/// `magic(2) codec(1) schema(1) dictionary id(4, le)` followed by the payload
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlobHeader {
+    pub codec: Codec,
+    pub schema: u8,
+    /// 0 if no dictionary was used
+    pub dictionary: u32,
+}
+
+impl BlobHeader {
+    pub fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        let mut bytes = [0; HEADER_LEN];
+        bytes[..2].copy_from_slice(&MAGIC);
+        bytes[2] = self.codec as u8;
+        bytes[3] = self.schema;
+        bytes[4..].copy_from_slice(&self.dictionary.to_le_bytes());
+
+        bytes
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Option<Self>> {
+        if bytes.len() < HEADER_LEN || bytes[..2] != MAGIC {
+            return Ok(None);
+        }
+
+        let codec = match bytes[2] {
+            2 => Codec::Zstd,
+            codec => bail!("unknown blob codec {}", codec),
+        };
+
+        Ok(Some(Self {
+            codec,
+            schema: bytes[3],
+            dictionary: u32::from_le_bytes(bytes[4..HEADER_LEN].try_into()?),
+        }))
+    }
+}
+
+/// the blob was written with a dictionary this codec hasn't loaded,
+/// another `Database` on the same file may have trained it since
+#[derive(Debug, Clone, Copy, PartialEq, thiserror::Error)]
+#[error("missing blob dictionary {0}")]
+pub struct MissingDictionary(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlobTable {
+    Entity,
+    Encounter,
+}
+
+struct Dictionary {
+    encoder: EncoderDictionary<'static>,
+    decoder: DecoderDictionary<'static>,
+}
+
+/// encodes new blobs with zstd and the active dictionary, decodes every format ever written
+pub struct BlobCodec {
+    level: i32,
+    dictionaries: RwLock<HashMap<u32, Arc<Dictionary>>>,
+    active: RwLock<u32>,
+}
+
+impl BlobCodec {
+    pub fn new(level: i32) -> Self {
+        Self {
+            level,
+            dictionaries: RwLock::new(HashMap::new()),
+            active: RwLock::new(0),
+        }
+    }
+
+    pub fn add_dictionary(&self, id: u32, raw: &[u8]) {
+        let dictionary = Dictionary {
+            encoder: EncoderDictionary::copy(raw, self.level),
+            decoder: DecoderDictionary::copy(raw),
+        };
+
+        self.dictionaries.write().unwrap().insert(id, Arc::new(dictionary));
+    }
+
+    /// new blobs use this dictionary, 0 disables it
+    pub fn set_active(&self, id: u32) {
+        *self.active.write().unwrap() = id;
+    }
+
+    /// what a blob written right now starts with, anything else is due for recompression
+    pub fn current_header(&self) -> BlobHeader {
+        BlobHeader {
+            codec: Codec::Zstd,
+            schema: BLOB_SCHEMA_VERSION,
+            dictionary: *self.active.read().unwrap(),
+        }
+    }
+
+    pub fn encode<T: ?Sized + Serialize>(&self, value: &T) -> Result<Vec<u8>> {
+        let json = serde_json::to_vec(value)?;
+        self.encode_bytes(&json)
+    }
+
+    pub fn encode_bytes(&self, json: &[u8]) -> Result<Vec<u8>> {
+        let header = self.current_header();
+        let mut bytes = header.to_bytes().to_vec();
+
+        let dictionary = match header.dictionary {
+            0 => None,
+            id => self.dictionaries.read().unwrap().get(&id).cloned(),
+        };
+
+        match dictionary {
+            Some(dictionary) => {
+                let mut encoder = zstd::stream::write::Encoder::with_prepared_dictionary(bytes, &dictionary.encoder)?;
+                encoder.write_all(json)?;
+                bytes = encoder.finish()?;
+            }
+            None => {
+                bytes[4..HEADER_LEN].copy_from_slice(&0u32.to_le_bytes());
+                bytes.extend(zstd::bulk::compress(json, self.level)?);
+            }
+        }
+
+        Ok(bytes)
+    }
+
+    /// returns the json bytes of a blob in any format, including headerless gzip and plain json
+    pub fn decode_bytes(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        let Some(header) = BlobHeader::parse(bytes)? else {
+            // written before the codec existed
+            if bytes.starts_with(&[0x1f, 0x8b]) {
+                let mut decompressed = vec![];
+                GzDecoder::new(bytes).read_to_end(&mut decompressed)?;
+                return Ok(decompressed);
+            }
+
+            return Ok(bytes.to_vec());
+        };
+
+        let payload = &bytes[HEADER_LEN..];
+        let mut decompressed = vec![];
+
+        match (header.codec, header.dictionary) {
+            (Codec::Zstd, 0) => {
+                decompressed = zstd::stream::decode_all(payload)?;
+            }
+            (Codec::Zstd, id) => {
+                let dictionary = self.dictionaries.read().unwrap().get(&id).cloned()
+                    .ok_or(MissingDictionary(id))?;
+                zstd::stream::read::Decoder::with_prepared_dictionary(payload, &dictionary.decoder)?
+                    .read_to_end(&mut decompressed)?;
+            }
+        }
+
+        Ok(decompressed)
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T> {
+        Ok(serde_json::from_slice(&self.decode_bytes(bytes)?)?)
+    }
+}
+
+/// trains a dictionary from json samples, zstd needs a few hundred of them to be useful
+pub fn train_dictionary(samples: &[Vec<u8>], size: usize) -> Result<Vec<u8>> {
+    Ok(zstd::dict::from_samples(samples, size)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use crate::misc::utils::compress_json;
+
+    use super::*;
+
+    fn sample(index: usize) -> Value {
+        json!({
+            "skills": {
+                (index % 7).to_string(): { "id": index, "name": format!("Skill {}", index % 13), "totalDamage": index * 1000, "hits": index % 31 }
+            },
+            "damageStats": { "damageDealt": index * 4242, "deaths": 0, "buffedBySupport": index * 12 }
+        })
+    }
+
+    #[test]
+    fn should_decode_every_format() {
+        let codec = BlobCodec::new(3);
+        let value = sample(42);
+        let json = serde_json::to_vec(&value).unwrap();
+
+        assert_eq!(codec.decode::<Value>(&json).unwrap(), value);
+        assert_eq!(codec.decode::<Value>(&compress_json(&value).unwrap()).unwrap(), value);
+
+        let encoded = codec.encode(&value).unwrap();
+        assert_eq!(BlobHeader::parse(&encoded).unwrap(), Some(codec.current_header()));
+        assert_eq!(codec.decode::<Value>(&encoded).unwrap(), value);
+    }
+
+    #[test]
+    fn should_use_trained_dictionary() {
+        let samples: Vec<Vec<u8>> = (0..1000).map(|index| serde_json::to_vec(&sample(index)).unwrap()).collect();
+        let dictionary = train_dictionary(&samples, 16 * 1024).unwrap();
+
+        let codec = BlobCodec::new(3);
+        let value = sample(5000);
+        let plain = codec.encode(&value).unwrap();
+
+        codec.add_dictionary(1, &dictionary);
+        codec.set_active(1);
+        let encoded = codec.encode(&value).unwrap();
+
+        assert_eq!(BlobHeader::parse(&encoded).unwrap().unwrap().dictionary, 1);
+        assert!(encoded.len() < plain.len());
+        assert_eq!(codec.decode::<Value>(&encoded).unwrap(), value);
+
+        // blobs from another install can't be decoded without their dictionary
+        let other = BlobCodec::new(3);
+        let err = other.decode::<Value>(&encoded).unwrap_err();
+        assert_eq!(err.downcast_ref::<MissingDictionary>(), Some(&MissingDictionary(1)));
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+use anyhow::Result;
+use log::*;
+
+use crate::database::Database;
+
+/// the database of the active profile, swapped when the profile changes
+pub struct DatabaseManager {
+    migration_path: PathBuf,
+    current: RwLock<(String, Arc<Database>)>,
+    /// one instance per profile, so switching back doesn't open a second pool on the same file
+    opened: Mutex<HashMap<String, Arc<Database>>>,
+    /// bumped on every switch so long running tasks notice they hold a stale database
+    generation: AtomicU64,
+    setup_error: Option<String>,
+}
+
+impl DatabaseManager {
+    /// a failed migration is kept for `setup_error` rather than returned, the app should still start
+    pub fn open(migration_path: PathBuf, profile: String, path: PathBuf) -> Self {
+        let database = Database::new(path);
+
+        let setup_error = database.setup(migration_path.clone()).err().map(|err| {
+            error!("error setting up database: {:?}", err);
+            format!("{:#}", err)
+        });
+
+        let database = Arc::new(database);
+
+        Self {
+            migration_path,
+            current: RwLock::new((profile.clone(), database.clone())),
+            opened: Mutex::new(HashMap::from([(profile, database)])),
+            generation: AtomicU64::new(0),
+            setup_error,
+        }
+    }
+
+    /// why the database opened at startup isn't fully migrated, shown to the user on launch
+    pub fn setup_error(&self) -> Option<&str> {
+        self.setup_error.as_deref()
+    }
+
+    pub fn get(&self) -> Arc<Database> {
+        self.current.read().unwrap().1.clone()
+    }
+
+    pub fn profile(&self) -> String {
+        self.current.read().unwrap().0.clone()
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// the current database stays active if the new one can't be migrated,
+    /// returns the database only when this call opened it so its startup work runs once
+    pub fn switch(&self, profile: String, path: PathBuf) -> Result<Option<Arc<Database>>> {
+        let mut opened = self.opened.lock().unwrap();
+
+        let (database, created) = match opened.get(&profile) {
+            Some(database) => (database.clone(), false),
+            None => {
+                let database = Database::new(path);
+                database.setup(self.migration_path.clone())?;
+                let database = Arc::new(database);
+                opened.insert(profile.clone(), database.clone());
+
+                (database, true)
+            }
+        };
+
+        *self.current.write().unwrap() = (profile, database.clone());
+        self.generation.fetch_add(1, Ordering::AcqRel);
+
+        Ok(created.then_some(database))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use crate::constants::{DEFAULT_PROFILE, INITIAL_MIGRATION};
+    use crate::database::{create_test_database, create_test_database_manager, migration_path};
+
+    use super::*;
+
+    fn create_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("drama-meter-migration-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("migration")).unwrap();
+        std::fs::copy(migration_path().join(INITIAL_MIGRATION), dir.join("migration").join(INITIAL_MIGRATION)).unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn should_reuse_database_when_switching_back() {
+        let manager = create_test_database_manager();
+        let other = create_test_database();
+        let default = manager.get();
+
+        assert!(manager.switch("other".to_string(), other.path().clone()).unwrap().is_some());
+        assert_eq!(manager.profile(), "other");
+
+        let switched = manager.switch(DEFAULT_PROFILE.to_string(), manager.path().clone()).unwrap();
+        assert!(switched.is_none());
+        assert!(Arc::ptr_eq(&manager.get(), &default));
+        assert!(manager.switch("other".to_string(), other.path().clone()).unwrap().is_none());
+        assert_eq!(manager.generation(), 3);
+    }
+
+    #[tokio::test]
+    async fn should_migrate_from_the_first_release() {
+        let dir = create_dir();
+        let path = dir.join("encounters.db");
+        Database::new(path.clone()).setup(dir.join("migration")).unwrap();
+
+        // an encounter as the first release wrote it, with plain json text
+        let connection = Connection::open(&path).unwrap();
+        connection.execute_batch(r#"
+            INSERT INTO encounter (id, last_combat_packet, total_damage_dealt, top_damage_dealt,
+                total_damage_taken, top_damage_taken, dps, buffs, debuffs, misc)
+            VALUES (1, 1700000300000, 1000, 1000, 0, 0, 3, '{}', '{}', '{}');
+            INSERT INTO encounter_preview (id, fight_start, current_boss, duration, players, local_player, my_dps, cleared)
+            VALUES (1, 1700000000000, 'Thaemine the Lightqueller', 300000, '204:Alice', 'Alice', 3, 1);
+            INSERT INTO entity (name, encounter_id, npc_id, entity_type, class_id, class, gear_score,
+                current_hp, max_hp, is_dead, skills, damage_stats)
+            VALUES ('Alice', 1, 0, 'PLAYER', 204, 'Bard', 1680.0, 0, 0, 0,
+                '{"1":{"id":1,"totalDamage":1000}}', '{"damageDealt":1000}');
+        "#).unwrap();
+        drop(connection);
+
+        let manager = DatabaseManager::open(migration_path(), DEFAULT_PROFILE.to_string(), path.clone());
+        assert_eq!(manager.setup_error(), None);
+
+        let migrations = Connection::open(&path)
+            .unwrap()
+            .prepare("SELECT file_name FROM Migration")
+            .unwrap()
+            .query_map([], |row| row.get::<_, String>(0))
+            .unwrap()
+            .count();
+        assert_eq!(migrations, std::fs::read_dir(migration_path()).unwrap().count());
+
+        let database = manager.get();
+        let encounter = database.load_encounter("1".to_string()).await.unwrap();
+        assert_eq!(encounter.current_boss_name, "Thaemine the Lightqueller");
+        assert_eq!(encounter.entities["Alice"].skills[&1].total_damage, 1000);
+        database.insert_data(Default::default()).unwrap();
+
+        drop(database);
+        drop(manager);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn should_keep_the_migration_error() {
+        let dir = create_dir();
+        std::fs::write(dir.join("migration").join("2_broken.sql"), "ALTER TABLE missing ADD COLUMN value INTEGER;").unwrap();
+
+        let manager = DatabaseManager::open(dir.join("migration"), DEFAULT_PROFILE.to_string(), dir.join("encounters.db"));
+        assert!(manager.setup_error().is_some_and(|err| err.contains("2_broken.sql")));
+
+        drop(manager);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -1,8 +1,196 @@
 
 pub mod wrapper;
 pub mod queries;
+pub mod codec;
+mod manager;
 mod utils;
 mod models;
+mod search;
 
 pub use wrapper::Database;
-pub use models::*;
\ No newline at end of file
+pub use manager::DatabaseManager;
+pub use models::*;
+pub use search::{SearchQuery, SortColumn, SortOrder};
+
+#[cfg(test)]
+mod test_utils {
+    use std::ops::Deref;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use serde_json::{json, Map, Value};
+
+    use crate::constants::{DB_VERSION, DEFAULT_PROFILE, EXPORT_VERSION};
+
+    use super::*;
+
+    fn create_path() -> PathBuf {
+        std::env::temp_dir().join(format!("drama-meter-{}.db", uuid::Uuid::new_v4()))
+    }
+
+    pub fn migration_path() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("assets/migration")
+    }
+
+    /// deletes the database files once the test is done with them
+    pub struct TempDatabase<T> {
+        inner: T,
+        path: PathBuf,
+    }
+
+    impl<T> TempDatabase<T> {
+        pub fn path(&self) -> &PathBuf {
+            &self.path
+        }
+    }
+
+    impl<T> Deref for TempDatabase<T> {
+        type Target = T;
+
+        fn deref(&self) -> &T {
+            &self.inner
+        }
+    }
+
+    impl<T> Drop for TempDatabase<T> {
+        fn drop(&mut self) {
+            for suffix in ["", "-wal", "-shm"] {
+                let mut path = self.path.clone().into_os_string();
+                path.push(suffix);
+                std::fs::remove_file(path).ok();
+            }
+        }
+    }
+
+    pub fn create_test_database() -> TempDatabase<Arc<Database>> {
+        let path = create_path();
+        let database = Database::new(path.clone());
+        database.setup(migration_path()).expect("could not run migrations");
+
+        TempDatabase { inner: Arc::new(database), path }
+    }
+
+    pub fn create_test_database_manager() -> TempDatabase<Arc<DatabaseManager>> {
+        let path = create_path();
+        let manager = DatabaseManager::open(migration_path(), DEFAULT_PROFILE.to_string(), path.clone());
+
+        TempDatabase { inner: Arc::new(manager), path }
+    }
+
+    /// an encounter the way `import_encounters` takes it, tests only set what they look at
+    pub struct TestEncounter {
+        pub fight_start: i64,
+        pub duration: i64,
+        pub boss: String,
+        pub difficulty: String,
+        pub region: Option<String>,
+        pub local_player: String,
+        pub dps: i64,
+        pub favorite: bool,
+        pub cleared: bool,
+        pub recovered: bool,
+        pub entities: Vec<Map<String, Value>>,
+    }
+
+    impl Default for TestEncounter {
+        fn default() -> Self {
+            Self {
+                fight_start: 1_700_000_000_000,
+                duration: 300_000,
+                boss: "Thaemine, the Lightqueller".to_string(),
+                difficulty: "Normal".to_string(),
+                region: None,
+                local_player: "Alice".to_string(),
+                dps: 3_333,
+                favorite: false,
+                cleared: true,
+                recovered: false,
+                entities: vec![],
+            }
+        }
+    }
+
+    impl TestEncounter {
+        pub fn player(name: &str, class: &str, spec: &str, gear_score: f64) -> Map<String, Value> {
+            json!({
+                "name": name,
+                "npc_id": 0,
+                "entity_type": "PLAYER",
+                "class": class,
+                "spec": spec,
+                "gear_score": gear_score,
+                "current_hp": 0,
+                "max_hp": 0,
+                "is_dead": false
+            })
+            .as_object()
+            .cloned()
+            .unwrap()
+        }
+
+        pub fn build(self) -> EncounterExportEntry {
+            let mut misc = json!({ "version": "1.20.0" });
+
+            if let Some(region) = self.region {
+                misc["region"] = json!(region);
+            }
+
+            if self.recovered {
+                misc["recovered"] = json!(true);
+            }
+
+            let total_damage = self.dps * self.duration / 1000;
+            let encounter = json!({
+                "last_combat_packet": self.fight_start + self.duration,
+                "total_damage_dealt": total_damage,
+                "top_damage_dealt": total_damage,
+                "total_damage_taken": 0,
+                "top_damage_taken": 0,
+                "dps": self.dps,
+                "buffs": {},
+                "debuffs": {},
+                "applied_shield_buffs": {},
+                "boss_hp_log": {},
+                "misc": misc
+            });
+
+            let players = match self.entities.is_empty() {
+                true => self.local_player.clone(),
+                false => self.entities.iter().filter_map(|entity| entity["name"].as_str()).collect::<Vec<_>>().join(","),
+            };
+
+            let preview = json!({
+                "fight_start": self.fight_start,
+                "current_boss": self.boss,
+                "duration": self.duration,
+                "players": players,
+                "difficulty": self.difficulty,
+                "local_player": self.local_player,
+                "my_dps": self.dps,
+                "favorite": self.favorite,
+                "cleared": self.cleared,
+                "boss_only_damage": true
+            });
+
+            EncounterExportEntry {
+                encounter: encounter.as_object().cloned().unwrap(),
+                preview: preview.as_object().cloned().unwrap(),
+                entities: self.entities,
+                sync: None,
+            }
+        }
+    }
+
+    pub fn test_export(encounters: Vec<EncounterExportEntry>) -> EncounterExport {
+        EncounterExport {
+            version: EXPORT_VERSION,
+            app_version: "test".to_string(),
+            db_version: DB_VERSION,
+            exported_on: 0,
+            encounters,
+        }
+    }
+}
+
+#[cfg(test)]
+pub use test_utils::*;
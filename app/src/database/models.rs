@@ -2,6 +2,8 @@ use std::collections::BTreeMap;
 
 use chrono::{DateTime, TimeDelta, Utc};
 use hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
 use crate::{entity::Entity, core::stats_api::PlayerStats, models::*};
 
@@ -34,6 +36,11 @@ pub struct EncounterPreviewDb {
     pub local_player_dps: i64,
     pub raid_clear: bool,
     pub boss_only_damage: bool,
+    pub personal_best: bool,
+    pub personal_percentile: Option<f64>,
+    pub local_percentile: Option<f64>,
+    /// comparable between the logs of different raid members, unknown until the ntp server answered
+    pub ntp_fight_start: Option<i64>,
 }
 
 pub struct EntityDb {
@@ -59,6 +66,7 @@ pub struct EntityDb {
     pub ark_passive_data: serde_json::Value,
 }
 
+#[derive(Default)]
 pub struct SaveToDb {
     pub duration: TimeDelta,
     pub duration_seconds: i64,
@@ -80,8 +88,34 @@ pub struct SaveToDb {
     pub region: Option<String>,
     pub player_info: Option<HashMap<String, PlayerStats>>,
     pub version: String,
-    pub ntp_fight_start: i64,
+    pub ntp_fight_start: Option<i64>,
     pub rdps_valid: bool,
     pub is_manual: bool,
     pub skill_cast_log: HashMap<u64, HashMap<u32, BTreeMap<i64, SkillCast>>>,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncounterExport {
+    pub version: u32,
+    pub app_version: String,
+    pub db_version: i32,
+    pub exported_on: i64,
+    pub encounters: Vec<EncounterExportEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncounterExportEntry {
+    pub encounter: Map<String, Value>,
+    pub preview: Map<String, Value>,
+    pub entities: Vec<Map<String, Value>>,
+    pub sync: Option<Map<String, Value>>,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportSummary {
+    pub imported: Vec<i64>,
+    pub duplicates: usize,
+}
@@ -1,8 +1,6 @@
 
 pub const DELETE_FROM_ENCOUNTERS: &'static str = r#"DELETE FROM encounter"#;
 
-pub const DELETE_FROM_ENCOUNTERS_IDS: &'static str = r#"DELETE FROM encounter WHERE id IN ({})"#;
-
 pub const DELETE_FROM_ENCOUNTERS_UNCLEARED: &'static str = r#"
     DELETE
     FROM encounter
@@ -13,7 +11,7 @@ pub const DELETE_FROM_ENCOUNTERS_UNCLEARED: &'static str = r#"
 )"#;
 
 pub const DELETE_FROM_ENCOUNTERS_UNCLEARED_KEEP_FAVOURITE: &'static str = r#"
-    DELET
+    DELETE
     FROM encounter
     WHERE id IN (
         SELECT id
@@ -104,7 +102,10 @@ pub const SELECT_ENCOUNTER_JOIN_PREVIEW_BY_ID: &'static str = r#"
         total_shielding,
         total_effective_shielding,
         applied_shield_buffs,
-        boss_hp_log
+        boss_hp_log,
+        personal_best,
+        personal_percentile,
+        local_percentile
     FROM encounter
     JOIN encounter_preview
     USING (id)
@@ -122,10 +123,14 @@ pub const INSERT_ENCOUNTER_PREVIEW: &'static str = r#"
         local_player,
         my_dps,
         cleared,
-        boss_only_damage
+        boss_only_damage,
+        personal_best,
+        personal_percentile,
+        local_percentile,
+        ntp_fight_start
     )
     VALUES
-    (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)"
+    (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
 "#;
 
 pub const INSERT_ENCOUNTER: &'static str = r#"
@@ -207,28 +212,21 @@ pub const SELECT_SYNC_LOG_BY_ENCOUNTER_ID: &'static str = r#"
     WHERE encounter_id = ?
         AND failed = false;"#;
 
-pub const SELECT_ENCOUNTER_PREVIEW: &'static str = r#"
-let query = format!(
-    SELECT
-        e.id,
-        e.fight_start,
-        e.current_boss,
-        e.duration,
-        e.difficulty,
-        e.favorite,
-        e.cleared,
-        e.local_player,
-        e.my_dps,
-        e.players
-    FROM encounter_preview e {join}
-    WHERE e.duration > ? {boss}
-    {clear}
-    {favorite}
-    {difficulty}
-    {boss_only_damage}
-    ORDER BY {sort} {order}
-    LIMIT ?
-    OFFSET ?",
+/// built into a full query by `SearchQuery`
+pub const SELECT_ENCOUNTER_PREVIEW_COLUMNS: &'static str = r#"
+    e.id,
+    e.fight_start,
+    e.current_boss,
+    e.duration,
+    e.difficulty,
+    e.favorite,
+    e.cleared,
+    e.local_player,
+    e.my_dps,
+    e.players,
+    e.personal_best,
+    e.personal_percentile,
+    e.local_percentile
 "#;
 
 pub const SELECT_STATS: &'static str = r#"
@@ -239,14 +237,6 @@ pub const SELECT_STATS: &'static str = r#"
 
 pub const SELECT_ENCOUNTER_PREVIEW_COUNT: &'static str = r#"SELECT COUNT(*) FROM encounter_preview"#;
 
-pub const SELECT_ENCOUNTER_PREVIEW_FILTERED_COUNT: &'static str = r#"
-    SELECT
-        COUNT(*)
-    FROM encounter_preview e {join}
-    WHERE duration > ? {}
-    {} {clear} {favorite} {boss_only_damage}
-"#;
-
 pub const SELECT_LATEST_ENCOUNTER_ID: &'static str = "
     SELECT
         id
@@ -270,6 +260,368 @@ pub const SELECT_ENCOUNTER_PREVIEW_JOIN_SYNC_FILTERED: &str = r#"
         ON encounter_id = id
     WHERE cleared = true
         AND boss_only_damage = 1
-        AND upstream_id {}
+        AND upstream_id {upstream}
     ORDER BY fight_start;
-"#;
\ No newline at end of file
+"#;
+
+pub const SELECT_ENCOUNTER_EXPORT_BY_ID: &'static str = r#"SELECT * FROM encounter WHERE id = ?"#;
+
+pub const SELECT_ENCOUNTER_PREVIEW_EXPORT_BY_ID: &'static str = r#"SELECT * FROM encounter_preview WHERE id = ?"#;
+
+pub const SELECT_ENTITY_EXPORT_BY_ENCOUNTER_ID: &'static str = r#"SELECT * FROM entity WHERE encounter_id = ?"#;
+
+pub const SELECT_SYNC_LOG_EXPORT_BY_ENCOUNTER_ID: &'static str = r#"SELECT * FROM sync_logs WHERE encounter_id = ?"#;
+
+pub const SELECT_TABLE_COLUMNS: &'static str = r#"SELECT name FROM pragma_table_info(?)"#;
+
+pub const SELECT_DUPLICATE_ENCOUNTER: &'static str = r#"
+    SELECT id
+    FROM encounter_preview
+    WHERE fight_start = ?
+        AND current_boss IS ?
+        AND local_player IS ?
+    LIMIT 1;
+"#;
+
+pub const CREATE_MIGRATION_TABLE: &'static str = r#"
+    CREATE TABLE IF NOT EXISTS Migration (
+        file_name NVARCHAR(50) NOT NULL PRIMARY KEY,
+        recorded_on INTEGER NOT NULL
+    );
+"#;
+
+pub const SELECT_MIGRATIONS: &'static str = r#"SELECT file_name FROM Migration ORDER BY file_name"#;
+
+pub const INSERT_MIGRATION: &'static str = r#"
+    INSERT INTO Migration
+    (file_name, recorded_on)
+    VALUES
+    (?, ?);
+"#;
+
+pub const UPSERT_PLAYER: &'static str = r#"
+    INSERT INTO Player
+    (character_id, name, class_id, gear_score, created_on, updated_on)
+    VALUES
+    (?1, ?2, ?3, ?4, ?5, ?5)
+    ON CONFLICT(character_id) DO UPDATE SET
+        name = excluded.name,
+        class_id = excluded.class_id,
+        gear_score = excluded.gear_score,
+        updated_on = excluded.updated_on
+    WHERE excluded.updated_on >= Player.updated_on;
+"#;
+
+pub const INSERT_PLAYER_HISTORY: &'static str = r#"
+    INSERT OR REPLACE INTO Player_history
+    (character_id, encounter_id, name, class_id, gear_score, created_on)
+    VALUES
+    (?, ?, ?, ?, ?, ?);
+"#;
+
+pub const UPSERT_RAID: &'static str = r#"
+    INSERT INTO Raid
+    (created_on, name, zone_name, gate, npc_ids)
+    VALUES
+    (?1, ?2, ?3, ?4, ?5)
+    ON CONFLICT(name) DO UPDATE SET
+        npc_ids = COALESCE(excluded.npc_ids, Raid.npc_ids)
+    RETURNING id;
+"#;
+
+pub const INSERT_PLAYER_STATS: &'static str = r#"
+    INSERT OR REPLACE INTO Player_stats (
+        character_id,
+        encounter_id,
+        raid_id,
+        difficulty,
+        class_id,
+        spec,
+        gear_score,
+        cleared,
+        created_on,
+        duration,
+        total_damage,
+        dps,
+        brand_uptime,
+        attack_power_uptime,
+        identity_uptime
+    )
+    VALUES
+    (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)
+"#;
+
+pub const INSERT_DAMAGE_LOG: &'static str = r#"
+    INSERT OR REPLACE INTO Damage_log
+    (encounter_id, character_id, value)
+    VALUES
+    (?, ?, ?);
+"#;
+
+pub const SELECT_PLAYER_PROGRESSION: &'static str = r#"
+    SELECT
+        ps.encounter_id,
+        ps.created_on,
+        ps.difficulty,
+        ps.spec,
+        ps.gear_score,
+        ps.cleared,
+        ps.duration,
+        ps.total_damage,
+        ps.dps,
+        ps.brand_uptime,
+        ps.attack_power_uptime,
+        ps.identity_uptime
+    FROM Player_stats ps
+    JOIN Player p
+        ON p.character_id = ps.character_id
+    JOIN Raid r
+        ON r.id = ps.raid_id
+    WHERE p.name = ?1
+        AND r.name = ?2
+        AND (?3 IS NULL OR ps.difficulty = ?3)
+    ORDER BY ps.created_on
+"#;
+
+pub const SELECT_PLAYER_DPS_HISTORY: &'static str = r#"
+    SELECT
+        current.dps,
+        history.dps
+    FROM Player_stats current
+    LEFT JOIN Player_stats history
+        ON history.character_id = current.character_id
+        AND history.raid_id = current.raid_id
+        AND history.difficulty = current.difficulty
+        AND history.spec IS current.spec
+        AND history.created_on < current.created_on
+    WHERE current.encounter_id = ?1
+        AND current.character_id = ?2
+    ORDER BY history.created_on
+"#;
+
+pub const SELECT_RAID_DPS_HISTORY: &'static str = r#"
+    SELECT
+        history.dps
+    FROM Player_stats current
+    JOIN Player_stats history
+        ON history.raid_id = current.raid_id
+        AND history.difficulty = current.difficulty
+        AND history.spec IS current.spec
+        AND history.created_on < current.created_on
+    WHERE current.encounter_id = ?1
+        AND current.character_id = ?2
+"#;
+
+pub const SELECT_PLAYER_STATS_CACHE: &'static str = r#"
+    SELECT
+        value
+    FROM Player_stats_cache
+    WHERE name = ?
+        AND region = ?
+        AND fetched_on >= ?
+"#;
+
+pub const UPSERT_PLAYER_STATS_CACHE: &'static str = r#"
+    INSERT OR REPLACE INTO Player_stats_cache
+    (name, region, fetched_on, value)
+    VALUES
+    (?, ?, ?, ?);
+"#;
+
+pub const UPSERT_PLAYER_INFO_QUEUE: &'static str = r#"
+    INSERT OR REPLACE INTO Player_info_queue
+    (encounter_id, request, attempts, next_attempt_on, created_on)
+    VALUES
+    (?, ?, 0, ?, ?);
+"#;
+
+pub const SELECT_DUE_PLAYER_INFO_QUEUE: &'static str = r#"
+    SELECT
+        encounter_id,
+        request,
+        attempts
+    FROM Player_info_queue
+    WHERE next_attempt_on <= ?
+    ORDER BY next_attempt_on
+"#;
+
+pub const UPDATE_PLAYER_INFO_QUEUE: &'static str = r#"
+    UPDATE Player_info_queue
+    SET attempts = ?,
+        next_attempt_on = ?,
+        last_error = ?
+    WHERE encounter_id = ?
+"#;
+
+pub const DELETE_PLAYER_INFO_QUEUE: &'static str = r#"
+    DELETE FROM Player_info_queue
+    WHERE encounter_id = ?
+"#;
+
+pub const UPDATE_ENTITY_PLAYER_INFO: &'static str = r#"
+    UPDATE entity
+    SET skills = ?,
+        damage_stats = ?,
+        dps = ?,
+        spec = ?,
+        engravings = ?,
+        ark_passive_active = ?,
+        ark_passive_data = ?
+    WHERE encounter_id = ?
+        AND name = ?
+"#;
+
+pub const UPDATE_PLAYER_STATS_SPEC: &'static str = r#"
+    UPDATE Player_stats
+    SET spec = ?
+    WHERE encounter_id = ?
+        AND character_id = ?
+"#;
+
+pub const UPDATE_ENCOUNTER_PREVIEW_RANKING: &'static str = r#"
+    UPDATE encounter_preview
+    SET personal_best = ?,
+        personal_percentile = ?,
+        local_percentile = ?
+    WHERE id = ?
+"#;
+
+pub const SELECT_ENCOUNTERS_WITHOUT_PLAYER_STATS: &'static str = r#"
+    SELECT
+        preview.id,
+        preview.current_boss,
+        MAX(entity.max_hp)
+    FROM encounter_preview preview
+    LEFT JOIN entity
+        ON entity.encounter_id = preview.id
+        AND entity.name = preview.current_boss
+    WHERE preview.id > ?
+        AND NOT EXISTS (SELECT 1 FROM Player_stats stats WHERE stats.encounter_id = preview.id)
+    GROUP BY preview.id
+    ORDER BY preview.id
+    LIMIT ?
+"#;
+
+pub const SELECT_ENCOUNTERS_TO_RANK: &'static str = r#"
+    SELECT
+        preview.id,
+        stats.character_id
+    FROM encounter_preview preview
+    JOIN entity
+        ON entity.encounter_id = preview.id
+        AND entity.name = preview.local_player
+    JOIN Player_stats stats
+        ON stats.encounter_id = preview.id
+        AND stats.character_id = entity.character_id
+    WHERE preview.id > ?
+    ORDER BY preview.id
+    LIMIT ?
+"#;
+
+pub const SELECT_ENCOUNTER_PREVIEW_RANKING: &'static str = r#"
+    SELECT
+        personal_best,
+        personal_percentile,
+        local_percentile
+    FROM encounter_preview
+    WHERE id = ?
+"#;
+
+pub const SELECT_RETENTION_ROWS: &'static str = r#"
+    SELECT
+        id,
+        fight_start,
+        current_boss,
+        favorite,
+        cleared,
+        personal_best
+    FROM encounter_preview
+"#;
+
+pub const SELECT_BLOB_DICTIONARIES: &'static str = r#"
+    SELECT
+        id,
+        dictionary
+    FROM Blob_dictionary
+    ORDER BY id
+"#;
+
+pub const INSERT_BLOB_DICTIONARY: &'static str = r#"
+    INSERT INTO Blob_dictionary
+    (dictionary, samples, created_on)
+    VALUES
+    (?, ?, ?)
+"#;
+
+pub const SELECT_ENTITY_BLOB_SAMPLES: &'static str = r#"
+    SELECT
+        skills,
+        damage_stats
+    FROM entity
+    ORDER BY rowid DESC
+    LIMIT ?
+"#;
+
+pub const SELECT_ENCOUNTER_BLOB_SAMPLES: &'static str = r#"
+    SELECT
+        buffs,
+        debuffs,
+        applied_shield_buffs,
+        boss_hp_log
+    FROM encounter
+    ORDER BY id DESC
+    LIMIT ?
+"#;
+
+/// ?2 is the header of the current format, rows already in it are skipped without reading their blobs
+pub const SELECT_ENTITY_BLOBS_TO_RECOMPRESS: &'static str = r#"
+    SELECT
+        rowid,
+        skills,
+        damage_stats
+    FROM entity
+    WHERE rowid > ?1
+        AND (
+            (skills IS NOT NULL AND (typeof(skills) != 'blob' OR substr(skills, 1, 8) != ?2))
+            OR (damage_stats IS NOT NULL AND (typeof(damage_stats) != 'blob' OR substr(damage_stats, 1, 8) != ?2))
+        )
+    ORDER BY rowid
+    LIMIT ?3
+"#;
+
+pub const UPDATE_ENTITY_BLOBS: &'static str = r#"
+    UPDATE entity
+    SET
+        skills = ?,
+        damage_stats = ?
+    WHERE rowid = ?
+"#;
+
+pub const SELECT_ENCOUNTER_BLOBS_TO_RECOMPRESS: &'static str = r#"
+    SELECT
+        id,
+        buffs,
+        debuffs,
+        applied_shield_buffs,
+        boss_hp_log
+    FROM encounter
+    WHERE id > ?1
+        AND (
+            (buffs IS NOT NULL AND (typeof(buffs) != 'blob' OR substr(buffs, 1, 8) != ?2))
+            OR (debuffs IS NOT NULL AND (typeof(debuffs) != 'blob' OR substr(debuffs, 1, 8) != ?2))
+            OR (applied_shield_buffs IS NOT NULL AND (typeof(applied_shield_buffs) != 'blob' OR substr(applied_shield_buffs, 1, 8) != ?2))
+            OR (boss_hp_log IS NOT NULL AND (typeof(boss_hp_log) != 'blob' OR substr(boss_hp_log, 1, 8) != ?2))
+        )
+    ORDER BY id
+    LIMIT ?3
+"#;
+
+pub const UPDATE_ENCOUNTER_BLOBS: &'static str = r#"
+    UPDATE encounter
+    SET
+        buffs = ?,
+        debuffs = ?,
+        applied_shield_buffs = ?,
+        boss_hp_log = ?
+    WHERE id = ?
+"#;
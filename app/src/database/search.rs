@@ -0,0 +1,308 @@
+use anyhow::{bail, Result};
+use rusqlite::types::Value;
+
+use crate::models::SearchFilter;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortColumn {
+    Id,
+    FightStart,
+    Duration,
+    MyDps,
+    CurrentBoss,
+}
+
+impl SortColumn {
+    /// the only way a column name reaches the sql, anything else is rejected
+    pub fn parse(value: &str) -> Result<Self> {
+        let column = match value {
+            "" | "id" => SortColumn::Id,
+            "fight_start" => SortColumn::FightStart,
+            "duration" => SortColumn::Duration,
+            "my_dps" => SortColumn::MyDps,
+            "current_boss" => SortColumn::CurrentBoss,
+            _ => bail!("cannot sort by {}", value),
+        };
+
+        Ok(column)
+    }
+
+    fn as_sql(&self) -> &'static str {
+        match self {
+            SortColumn::Id => "e.id",
+            SortColumn::FightStart => "e.fight_start",
+            SortColumn::Duration => "e.duration",
+            SortColumn::MyDps => "e.my_dps",
+            SortColumn::CurrentBoss => "e.current_boss",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "" | "desc" => Ok(SortOrder::Desc),
+            "asc" => Ok(SortOrder::Asc),
+            _ => bail!("invalid order {}", value),
+        }
+    }
+
+    fn as_sql(&self) -> &'static str {
+        match self {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        }
+    }
+}
+
+/// where clause shared by the page and the count query, user input only ever ends up in `params`
+#[derive(Debug, Default)]
+pub struct SearchQuery {
+    joins: Vec<&'static str>,
+    join_params: Vec<Value>,
+    conditions: Vec<String>,
+    params: Vec<Value>,
+    sort: Option<(SortColumn, SortOrder)>,
+}
+
+fn placeholders(count: usize) -> String {
+    vec!["?"; count].join(",")
+}
+
+impl SearchQuery {
+    pub fn new(search: &str, filter: &SearchFilter) -> Result<Self> {
+        let mut query = Self::default();
+
+        if search.len() > 2 {
+            let escaped_search = search
+                .split_whitespace()
+                .map(|word| format!("\"{}\"", word.replace('"', "")))
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            query.join("JOIN encounter_search(?) ON encounter_search.rowid = e.id", vec![escaped_search.into()]);
+        }
+
+        query.push("e.duration > ?", vec![(filter.min_duration as i64 * 1000).into()]);
+
+        if filter.max_duration > 0 {
+            query.push("e.duration <= ?", vec![(filter.max_duration as i64 * 1000).into()]);
+        }
+
+        if !filter.bosses.is_empty() {
+            let condition = format!("e.current_boss IN ({})", placeholders(filter.bosses.len()));
+            query.push(condition, filter.bosses.iter().cloned().map(Value::from).collect());
+        }
+
+        if filter.cleared {
+            query.push("e.cleared = 1", vec![]);
+        }
+
+        if filter.favorite {
+            query.push("e.favorite = 1", vec![]);
+        }
+
+        if filter.boss_only_damage {
+            query.push("e.boss_only_damage = 1", vec![]);
+        }
+
+        if !filter.difficulty.is_empty() {
+            query.push("e.difficulty = ?", vec![filter.difficulty.clone().into()]);
+        }
+
+        if let Some(from) = filter.from {
+            query.push("e.fight_start >= ?", vec![from.into()]);
+        }
+
+        if let Some(to) = filter.to {
+            query.push("e.fight_start <= ?", vec![to.into()]);
+        }
+
+        if !filter.local_player.is_empty() {
+            query.push("e.local_player = ? COLLATE NOCASE", vec![filter.local_player.clone().into()]);
+        }
+
+        if !filter.region.is_empty() {
+            query.join("JOIN encounter enc ON enc.id = e.id", vec![]);
+            query.push("json_extract(enc.misc, '$.region') = ?", vec![filter.region.clone().into()]);
+        }
+
+        query.push_player_filter(filter);
+
+        query.sort = Some((SortColumn::parse(&filter.sort)?, SortOrder::parse(&filter.order)?));
+
+        Ok(query)
+    }
+
+    /// class, name, spec and gear score must all match the same player
+    fn push_player_filter(&mut self, filter: &SearchFilter) {
+        let mut conditions = vec![];
+        let mut params: Vec<Value> = vec![];
+
+        if !filter.classes.is_empty() {
+            conditions.push(format!("p.class IN ({})", placeholders(filter.classes.len())));
+            params.extend(filter.classes.iter().cloned().map(Value::from));
+        }
+
+        if !filter.player_name.is_empty() {
+            conditions.push("p.name = ? COLLATE NOCASE".to_string());
+            params.push(filter.player_name.clone().into());
+        }
+
+        if !filter.spec.is_empty() {
+            conditions.push("p.spec = ?".to_string());
+            params.push(filter.spec.clone().into());
+        }
+
+        if let Some(min_gear_score) = filter.min_gear_score {
+            conditions.push("p.gear_score >= ?".to_string());
+            params.push(min_gear_score.into());
+        }
+
+        if conditions.is_empty() {
+            return;
+        }
+
+        let condition = format!(
+            "EXISTS (SELECT 1 FROM entity p WHERE p.encounter_id = e.id AND p.entity_type = 'PLAYER' AND {})",
+            conditions.join(" AND "));
+        self.push(condition, params);
+    }
+
+    fn join(&mut self, join: &'static str, params: Vec<Value>) {
+        self.joins.push(join);
+        self.join_params.extend(params);
+    }
+
+    fn push(&mut self, condition: impl Into<String>, params: Vec<Value>) {
+        self.conditions.push(condition.into());
+        self.params.extend(params);
+    }
+
+    fn from_where(&self) -> String {
+        format!("FROM encounter_preview e {} WHERE {}", self.joins.join(" "), self.conditions.join(" AND "))
+    }
+
+    pub fn select(&self, columns: &str) -> String {
+        let (column, order) = self.sort.unwrap_or((SortColumn::Id, SortOrder::Desc));
+
+        format!(
+            "SELECT {} {} ORDER BY {} {} LIMIT ? OFFSET ?",
+            columns,
+            self.from_where(),
+            column.as_sql(),
+            order.as_sql())
+    }
+
+    pub fn count(&self) -> String {
+        format!("SELECT COUNT(*) {}", self.from_where())
+    }
+
+    /// join parameters come first, they appear before the where clause
+    pub fn params(&self) -> Vec<Value> {
+        self.join_params.iter().chain(self.params.iter()).cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::database::{create_test_database, test_export, Database, EncounterExportEntry, TempDatabase, TestEncounter};
+
+    use super::*;
+
+    fn entry(day: i64, boss: &str, difficulty: &str, region: &str, players: &[(&str, &str, &str, f64)]) -> EncounterExportEntry {
+        TestEncounter {
+            fight_start: 1_700_000_000_000 + day * 86_400_000,
+            duration: 300_000 + day * 1000,
+            boss: boss.to_string(),
+            difficulty: difficulty.to_string(),
+            region: Some(region.to_string()),
+            local_player: players[0].0.to_string(),
+            dps: 1000 - day,
+            entities: players
+                .iter()
+                .map(|(name, class, spec, gear_score)| TestEncounter::player(name, class, spec, *gear_score))
+                .collect(),
+            ..Default::default()
+        }
+        .build()
+    }
+
+    async fn seed() -> TempDatabase<Arc<Database>> {
+        let database = create_test_database();
+        let encounters = vec![
+            entry(0, "Thaemine", "Hard", "EUC", &[("Alice", "Bard", "Desperate Salvation", 1680.0), ("Bob", "Berserker", "Berserker Technique", 1700.0)]),
+            entry(1, "Thaemine", "Normal", "EUC", &[("Alice", "Bard", "Desperate Salvation", 1680.0)]),
+            entry(2, "Echidna", "Hard", "NAE", &[("Carol", "Sorceress", "Igniter", 1720.0), ("Bob", "Bard", "True Courage", 1650.0)]),
+            entry(3, "Echidna", "Normal", "NAE", &[("Carol", "Sorceress", "Reflux", 1660.0)]),
+        ];
+        database.import_encounters(test_export(encounters)).await.unwrap();
+
+        database
+    }
+
+    async fn search(database: &Database, filter: SearchFilter) -> (Vec<String>, i32) {
+        let (encounters, count) = database.load_encounters_preview(1, 2, String::new(), filter).await.unwrap();
+        let bosses = encounters.into_iter().map(|encounter| format!("{}:{}", encounter.boss_name, encounter.difficulty.unwrap_or_default())).collect();
+
+        (bosses, count)
+    }
+
+    #[tokio::test]
+    async fn should_filter_and_count() {
+        let database = seed().await;
+
+        let filter = SearchFilter { difficulty: "Hard".to_string(), ..Default::default() };
+        assert_eq!(search(&database, filter).await.1, 2);
+
+        let filter = SearchFilter { classes: vec!["Bard".to_string()], ..Default::default() };
+        assert_eq!(search(&database, filter).await.1, 3);
+
+        // class and gear score have to match the same player
+        let filter = SearchFilter {
+            classes: vec!["Bard".to_string()],
+            min_gear_score: Some(1690.0),
+            ..Default::default()
+        };
+        assert_eq!(search(&database, filter).await.1, 0);
+
+        let filter = SearchFilter { player_name: "bob".to_string(), region: "NAE".to_string(), ..Default::default() };
+        assert_eq!(search(&database, filter).await, (vec!["Echidna:Hard".to_string()], 1));
+
+        let filter = SearchFilter { spec: "Reflux".to_string(), local_player: "Carol".to_string(), ..Default::default() };
+        assert_eq!(search(&database, filter).await.1, 1);
+
+        let filter = SearchFilter {
+            from: Some(1_700_000_000_000 + 86_400_000),
+            to: Some(1_700_000_000_000 + 2 * 86_400_000),
+            sort: "duration".to_string(),
+            order: "asc".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(search(&database, filter).await, (vec!["Thaemine:Normal".to_string(), "Echidna:Hard".to_string()], 2));
+
+        // the count ignores paging
+        let (page, count) = search(&database, SearchFilter::default()).await;
+        assert_eq!((page.len(), count), (2, 4));
+    }
+
+    #[tokio::test]
+    async fn should_reject_unknown_sort() {
+        let database = seed().await;
+
+        for (sort, order) in [("id; DROP TABLE encounter", "desc"), ("id", "desc; --")] {
+            let filter = SearchFilter { sort: sort.to_string(), order: order.to_string(), ..Default::default() };
+            assert!(database.load_encounters_preview(1, 10, String::new(), filter).await.is_err());
+        }
+
+        assert_eq!(search(&database, SearchFilter::default()).await.1, 4);
+    }
+}
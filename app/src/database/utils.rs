@@ -1,99 +1,67 @@
 use anyhow::*;
-use flate2::read::GzDecoder;
 use hashbrown::HashMap;
 use r2d2_sqlite::rusqlite;
 use serde_json::json;
-use std::{fs, io::Read, path::PathBuf, str::FromStr};
+use std::{fs, path::{Path, PathBuf}, str::FromStr};
+
+use rusqlite::{params, params_from_iter, types::{Type, Value, ValueRef}, Connection, Row, Transaction};
+use serde::de::DeserializeOwned;
+use serde_json::Map;
+
+use crate::{database::{codec::{BlobCodec, MissingDictionary}, queries::*}, models::*};
+
+pub const ENCOUNTER_BLOB_COLUMNS: [&str; 4] = ["buffs", "debuffs", "applied_shield_buffs", "boss_hp_log"];
+pub const ENCOUNTER_JSON_COLUMNS: [&str; 6] = ["buffs", "debuffs", "applied_shield_buffs", "boss_hp_log", "misc", "stagger_log"];
+pub const ENTITY_BLOB_COLUMNS: [&str; 2] = ["skills", "damage_stats"];
+pub const ENTITY_JSON_COLUMNS: [&str; 5] = ["skills", "damage_stats", "skill_stats", "engravings", "ark_passive_data"];
+
+/// blob columns hold plain json text in old rows and codec blobs (or headerless gzip) in newer ones,
+/// a value that can't be read fails the row instead of showing up as an empty encounter
+fn read_json<T: DeserializeOwned + Default>(row: &Row, index: usize, codec: &BlobCodec) -> rusqlite::Result<T> {
+    match row.get_ref(index) {
+        Ok(ValueRef::Text(value)) if !value.is_empty() => serde_json::from_slice(value)
+            .map_err(|err| rusqlite::Error::FromSqlConversionFailure(index, Type::Text, err.into())),
+        Ok(ValueRef::Blob(value)) if !value.is_empty() => codec.decode(value)
+            .map_err(|err| blob_error(index, err)),
+        _ => Ok(T::default()),
+    }
+}
 
-use log::*;
-use rusqlite::{params, params_from_iter, Connection, Row, Transaction};
+/// keeps `MissingDictionary` as the source so callers can reload the dictionaries and retry
+pub fn blob_error(index: usize, err: Error) -> rusqlite::Error {
+    let source: Box<dyn std::error::Error + Send + Sync> = match err.downcast::<MissingDictionary>() {
+        Ok(missing) => Box::new(missing),
+        Err(err) => err.into(),
+    };
 
-use crate::{database::{queries::*}, models::*, misc::utils::compress_json};
+    rusqlite::Error::FromSqlConversionFailure(index, Type::Blob, source)
+}
 
+pub fn is_missing_dictionary(err: &rusqlite::Error) -> bool {
+    match err {
+        rusqlite::Error::FromSqlConversionFailure(_, _, source) => source.is::<MissingDictionary>(),
+        _ => false,
+    }
+}
 
-pub fn parse_encounter(row: &Row) -> rusqlite::Result<(Encounter, bool)> {
-    
-    let mut compressed = false;
+pub fn parse_encounter(row: &Row, codec: &BlobCodec) -> rusqlite::Result<Encounter> {
     let misc_str: String = row.get(12).unwrap_or_default();
     let misc = serde_json::from_str::<EncounterMisc>(misc_str.as_str())
         .map(Some)
         .unwrap_or_default();
 
-    let mut boss_hp_log: HashMap<String, Vec<BossHpLog>> = HashMap::new();
-
-    if let Some(misc) = misc.as_ref() {
-        let version = misc
-            .version
-            .clone()
-            .unwrap_or_default()
-            .split('.')
-            .map(|x| x.parse::<i32>().unwrap_or_default())
-            .collect::<Vec<_>>();
-
-        if version[0] > 1
-            || (version[0] == 1 && version[1] >= 14)
-            || (version[0] == 1 && version[1] == 13 && version[2] >= 5)
-        {
-            compressed = true;
-        }
+    let buffs: HashMap<u32, StatusEffect> = read_json(row, 10, codec)?;
+    let debuffs: HashMap<u32, StatusEffect> = read_json(row, 11, codec)?;
+    let applied_shield_buffs: HashMap<u32, StatusEffect> = read_json(row, 19, codec)?;
+    let mut boss_hp_log: HashMap<String, Vec<BossHpLog>> = read_json(row, 20, codec)?;
 
-        if !compressed {
-            boss_hp_log = misc.boss_hp_log.clone().unwrap_or_default();
+    // rows without the boss_hp_log column kept it in misc
+    if boss_hp_log.is_empty() {
+        if let Some(log) = misc.as_ref().and_then(|misc| misc.boss_hp_log.clone()) {
+            boss_hp_log = log;
         }
     }
 
-    let buffs: HashMap<u32, StatusEffect>;
-    let debuffs: HashMap<u32, StatusEffect>;
-    let applied_shield_buffs: HashMap<u32, StatusEffect>;
-
-    if compressed {
-        let raw_bytes: Vec<u8> = row.get(10).unwrap_or_default();
-        let mut decompress = GzDecoder::new(raw_bytes.as_slice());
-        let mut buff_string = String::new();
-        decompress.read_to_string(&mut buff_string).unwrap();
-        buffs = serde_json::from_str::<HashMap<u32, StatusEffect>>(buff_string.as_str()).unwrap_or_default();
-
-        let raw_bytes: Vec<u8> = row.get(11).unwrap_or_default();
-        let mut decompress = GzDecoder::new(raw_bytes.as_slice());
-        let mut debuff_bytes = vec![];
-        // decompress.read_to_string(&mut debuff_string)?;
-        decompress.read_to_end(&mut debuff_bytes).unwrap();
-        debuffs = serde_json::from_slice::<HashMap<u32, StatusEffect>>(&debuff_bytes).unwrap_or_default();
-
-        let raw_bytes: Vec<u8> = row.get(19).unwrap_or_default();
-        let mut decompress = GzDecoder::new(raw_bytes.as_slice());
-        let mut applied_shield_buff_string = String::new();
-        decompress
-            .read_to_string(&mut applied_shield_buff_string)
-            .expect("could not decompress applied_shield_buffs");
-        applied_shield_buffs = serde_json::from_str::<HashMap<u32, StatusEffect>>(
-            applied_shield_buff_string.as_str(),
-        )
-        .unwrap_or_default();
-
-        let raw_bytes: Vec<u8> = row.get(20).unwrap_or_default();
-        let mut decompress = GzDecoder::new(raw_bytes.as_slice());
-        let mut boss_string = String::new();
-        decompress
-            .read_to_string(&mut boss_string)
-            .expect("could not decompress boss_hp_log");
-        boss_hp_log =
-            serde_json::from_str::<HashMap<String, Vec<BossHpLog>>>(boss_string.as_str())
-                .unwrap_or_default();
-    } else {
-        let buff_str: String = row.get(10).unwrap_or_default();
-        buffs = serde_json::from_str::<HashMap<u32, StatusEffect>>(buff_str.as_str())
-            .unwrap_or_default();
-        let debuff_str: String = row.get(11).unwrap_or_default();
-        debuffs = serde_json::from_str::<HashMap<u32, StatusEffect>>(debuff_str.as_str())
-            .unwrap_or_default();
-        let applied_shield_buff_str: String = row.get(19).unwrap_or_default();
-        applied_shield_buffs = serde_json::from_str::<HashMap<u32, StatusEffect>>(
-            applied_shield_buff_str.as_str(),
-        )
-        .unwrap_or_default();
-    }
-
     let total_shielding = row.get(17).unwrap_or_default();
     let total_effective_shielding = row.get(18).unwrap_or_default();
 
@@ -122,44 +90,18 @@ pub fn parse_encounter(row: &Row) -> rusqlite::Result<(Encounter, bool)> {
         favorite: row.get(14)?,
         cleared: row.get(15)?,
         boss_only_damage: row.get(16)?,
+        personal_best: row.get(21).unwrap_or_default(),
+        personal_percentile: row.get(22).unwrap_or_default(),
+        local_percentile: row.get(23).unwrap_or_default(),
         ..Default::default()
     };
 
-    rusqlite::Result::Ok((encounter, compressed))
+    rusqlite::Result::Ok(encounter)
 }
 
-pub fn parse_entity(row: &Row, compressed: bool) -> rusqlite::Result<EncounterEntity> {
-    
-    let skills: HashMap<u32, Skill>;
-    let damage_stats: DamageStats;
-
-    if compressed {
-        let raw_bytes: Vec<u8> = row.get(7).unwrap_or_default();
-        let mut decompress = GzDecoder::new(raw_bytes.as_slice());
-        let mut skill_string = String::new();
-        decompress
-            .read_to_string(&mut skill_string)
-            .expect("could not decompress skills");
-        skills = serde_json::from_str::<HashMap<u32, Skill>>(skill_string.as_str())
-            .unwrap_or_default();
-
-        let raw_bytes: Vec<u8> = row.get(8).unwrap_or_default();
-        let mut decompress = GzDecoder::new(raw_bytes.as_slice());
-        let mut damage_stats_string = String::new();
-        decompress
-            .read_to_string(&mut damage_stats_string)
-            .expect("could not decompress damage stats");
-        damage_stats = serde_json::from_str::<DamageStats>(damage_stats_string.as_str())
-            .unwrap_or_default();
-    } else {
-        let skill_str: String = row.get(7).unwrap_or_default();
-        skills = serde_json::from_str::<HashMap<u32, Skill>>(skill_str.as_str())
-            .unwrap_or_default();
-
-        let damage_stats_str: String = row.get(8).unwrap_or_default();
-        damage_stats = serde_json::from_str::<DamageStats>(damage_stats_str.as_str())
-            .unwrap_or_default();
-    }
+pub fn parse_entity(row: &Row, codec: &BlobCodec) -> rusqlite::Result<EncounterEntity> {
+    let skills: HashMap<u32, Skill> = read_json(row, 7, codec)?;
+    let damage_stats: DamageStats = read_json(row, 8, codec)?;
 
     let skill_stats_str: String = row.get(9).unwrap_or_default();
     let skill_stats =
@@ -230,7 +172,81 @@ pub fn parse_encounter_preview(row: &Row) -> rusqlite::Result<EncounterPreview>
         cleared: row.get(6)?,
         local_player: row.get(7)?,
         my_dps: row.get(8).unwrap_or(0),
+        personal_best: row.get(10).unwrap_or_default(),
+        personal_percentile: row.get(11).unwrap_or_default(),
+        local_percentile: row.get(12).unwrap_or_default(),
     };
 
     rusqlite::Result::Ok(result)
-}
\ No newline at end of file
+}
+
+pub fn row_to_map(row: &Row, json_columns: &[&str], codec: &BlobCodec) -> rusqlite::Result<Map<String, serde_json::Value>> {
+    let mut map = Map::new();
+    let names: Vec<String> = row.as_ref().column_names().into_iter().map(String::from).collect();
+
+    for (index, name) in names.into_iter().enumerate() {
+        let value = match row.get_ref(index)? {
+            ValueRef::Null => serde_json::Value::Null,
+            ValueRef::Integer(value) => json!(value),
+            ValueRef::Real(value) => json!(value),
+            ValueRef::Text(value) if json_columns.contains(&name.as_str()) => {
+                serde_json::from_slice(value)
+                    .unwrap_or_else(|_| json!(String::from_utf8_lossy(value)))
+            },
+            ValueRef::Text(value) => json!(String::from_utf8_lossy(value)),
+            ValueRef::Blob(value) if value.is_empty() => serde_json::Value::Null,
+            ValueRef::Blob(value) => codec.decode(value).map_err(|err| blob_error(index, err))?,
+        };
+
+        map.insert(name, value);
+    }
+
+    rusqlite::Result::Ok(map)
+}
+
+pub fn json_to_sql(value: &serde_json::Value, is_blob: bool, codec: &BlobCodec) -> Result<Value> {
+    let value = match value {
+        serde_json::Value::Null => Value::Null,
+        serde_json::Value::Bool(value) => Value::Integer(*value as i64),
+        serde_json::Value::Number(value) => match value.as_i64() {
+            Some(value) => Value::Integer(value),
+            None => Value::Real(value.as_f64().unwrap_or_default()),
+        },
+        // in blob columns this is json text from versions before 1.13.5, left for the recompression job
+        serde_json::Value::String(value) => Value::Text(value.clone()),
+        value if is_blob => Value::Blob(codec.encode(value)?),
+        value => Value::Text(serde_json::to_string(value)?),
+    };
+
+    Ok(value)
+}
+
+/// migrations are prefixed with their sequence number, e.g. `2_stats.sql`
+pub fn migration_order(path: &Path) -> (u32, String) {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+    let order = file_name
+        .split('_')
+        .next()
+        .and_then(|prefix| prefix.parse().ok())
+        .unwrap_or(u32::MAX);
+
+    (order, file_name)
+}
+
+pub fn read_migration(path: &Path) -> Result<String> {
+    let bytes = fs::read(path).with_context(|| format!("could not read migration {:?}", path))?;
+
+    let sql = match bytes.as_slice() {
+        [0xFF, 0xFE, rest @ ..] => {
+            let utf16: Vec<u16> = rest
+                .chunks_exact(2)
+                .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                .collect();
+            String::from_utf16(&utf16)?
+        },
+        [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8(rest.to_vec())?,
+        bytes => String::from_utf8(bytes.to_vec())?,
+    };
+
+    Ok(sql)
+}
@@ -2,16 +2,18 @@ use anyhow::*;
 use hashbrown::HashMap;
 use r2d2_sqlite::SqliteConnectionManager;
 use serde_json::json;
-use std::{cmp::Reverse, collections::BTreeMap, fs::{self, File}, hash::Hash, io::Read, path::PathBuf};
+use std::{cmp::{max, Reverse}, collections::BTreeMap, fs::{self, File}, hash::Hash, io::Read, path::{Path, PathBuf}, time::Duration};
 use log::*;
-use rusqlite::{params, params_from_iter, Connection, Transaction};
+use rusqlite::{backup::Backup, params, params_from_iter, Connection, OpenFlags, OptionalExtension, Transaction};
 use strfmt::strfmt;
 
-use crate::{constants::*, database::{models::*, queries::*, utils::*}, core::{stats_api::PlayerStats, utils::*}, models::*, misc::utils::compress_json};
+use crate::{constants::*, database::{codec::{self, BlobCodec, BlobTable}, models::*, queries::*, utils::*, SearchQuery}, core::{backup::{self, BackupReason}, retention::{self, RetentionReport, RetentionRow}, statistics::{self, EncounterRanking}, stats_api::{InspectRequest, PlayerStats}, utils::*}, models::*, misc::settings::RetentionSettings};
 
 pub struct Database {
     path: PathBuf,
+    backup_path: PathBuf,
     pool: r2d2::Pool<SqliteConnectionManager>,
+    codec: BlobCodec,
     is_new: bool
 }
 
@@ -19,45 +21,280 @@ impl Database {
     pub fn new(path: PathBuf) -> Self {
 
         let is_new = !path.exists();
+        let backup_path = path.with_file_name(BACKUP_DIR_NAME);
 
         let manager = SqliteConnectionManager::file(&path);
         let pool: r2d2::Pool<SqliteConnectionManager> = r2d2::Pool::new(manager).unwrap();
 
         Self {
             path,
+            backup_path,
             pool,
+            codec: BlobCodec::new(BLOB_COMPRESSION_LEVEL),
             is_new
         }
     }
 
     pub fn setup(&self, migration_path: PathBuf) -> Result<()> {
-        
-        if self.is_new {
-            let mut connection = self.pool.get()?;
-            
-            let mut sql_files: Vec<_> = fs::read_dir(&migration_path)?
-                .filter_map(|entry| entry.ok())
-                .filter(|entry| {
-                    entry.path().is_file() && entry.path().extension().map(|ext| ext == "sql").unwrap_or(false)
-                })
-                .collect();
+        let mut connection = self.pool.get()?;
+
+        connection.execute_batch(CREATE_MIGRATION_TABLE)?;
+
+        let mut statement = connection.prepare("SELECT 1 FROM sqlite_master WHERE type=? AND name=?")?;
+        let table_exists = statement.exists(["table", "encounter"])?;
+        drop(statement);
+
+        let mut applied = Self::get_applied_migrations(&connection)?;
+
+        // databases created before migrations were tracked already have the base schema
+        if !self.is_new && table_exists && !applied.iter().any(|name| name == INITIAL_MIGRATION) {
+            info!("marking {} as applied for existing database", INITIAL_MIGRATION);
+            Self::insert_migration(&connection, INITIAL_MIGRATION)?;
+            applied.push(INITIAL_MIGRATION.to_string());
+        }
+
+        let mut sql_files: Vec<_> = fs::read_dir(&migration_path)
+            .with_context(|| format!("could not read migration directory {:?}", migration_path))?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.is_file() && path.extension().map(|ext| ext == "sql").unwrap_or(false)
+            })
+            .collect();
+
+        sql_files.sort_by_key(|path| migration_order(path));
+
+        let has_pending = sql_files.iter().any(|path| {
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+            !applied.iter().any(|name| *name == file_name)
+        });
+
+        if !self.is_new && table_exists && has_pending {
+            let info = backup::create(self, &self.backup_path, BackupReason::Migration)
+                .context("could not back up database before running migrations")?;
+            info!("backed up database to {} before migrations", info.name);
+        }
+
+        for path in sql_files {
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+
+            if applied.contains(&file_name) {
+                continue;
+            }
+
+            info!("Running migration: {:?}", path);
+
+            let sql = read_migration(&path)?;
+            let tx = connection.transaction()?;
+
+            tx.execute_batch(&sql)
+                .with_context(|| format!("migration {} failed and was rolled back", file_name))?;
+            Self::insert_migration(&tx, &file_name)?;
+
+            tx.commit()?;
+        }
+
+        self.load_blob_dictionaries(&connection)?;
+
+        Ok(())
+    }
+
+    /// the newest dictionary is used for new blobs, older ones are still needed to read existing rows
+    fn load_blob_dictionaries(&self, connection: &Connection) -> Result<()> {
+        let mut statement = connection.prepare(SELECT_BLOB_DICTIONARIES)?;
+        let dictionaries = statement
+            .query_map([], |row| rusqlite::Result::Ok((row.get::<_, u32>(0)?, row.get::<_, Vec<u8>>(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        for (id, dictionary) in &dictionaries {
+            self.codec.add_dictionary(*id, dictionary);
+        }
+
+        if let Some((id, _)) = dictionaries.last() {
+            self.codec.set_active(*id);
+        }
+
+        Ok(())
+    }
+
+    /// reads again after reloading the dictionaries if a blob names one this instance doesn't know
+    fn read_blobs<T>(&self, connection: &Connection, read: impl Fn() -> rusqlite::Result<T>) -> Result<T> {
+        match read() {
+            Err(err) if is_missing_dictionary(&err) => {
+                self.load_blob_dictionaries(connection)?;
+                Ok(read()?)
+            }
+            result => Ok(result?),
+        }
+    }
+
+    pub fn has_blob_dictionary(&self) -> bool {
+        self.codec.current_header().dictionary != 0
+    }
+
+    /// samples recent blobs, returns `None` if there are too few encounters to train on
+    pub fn train_blob_dictionary(&self) -> Result<Option<u32>> {
+        let connection = self.pool.get()?;
+        let mut samples = vec![];
+
+        for query in [SELECT_ENTITY_BLOB_SAMPLES, SELECT_ENCOUNTER_BLOB_SAMPLES] {
+            let mut statement = connection.prepare(query)?;
+            let mut rows = statement.query(params![DICTIONARY_MAX_SAMPLES])?;
+
+            while let Some(row) = rows.next()? {
+                for index in 0..row.as_ref().column_count() {
+                    let bytes = match row.get_ref(index)? {
+                        rusqlite::types::ValueRef::Text(value) => value.to_vec(),
+                        rusqlite::types::ValueRef::Blob(value) => match self.codec.decode_bytes(value) {
+                            Ok(bytes) => bytes,
+                            Err(_) => continue,
+                        },
+                        _ => continue,
+                    };
+
+                    if !bytes.is_empty() {
+                        samples.push(bytes);
+                    }
+                }
+            }
+        }
+
+        if samples.len() < DICTIONARY_MIN_SAMPLES {
+            return Ok(None);
+        }
+
+        let dictionary = codec::train_dictionary(&samples, DICTIONARY_SIZE)?;
+        let id = self.add_blob_dictionary(&dictionary, samples.len())?;
+        info!("trained blob dictionary {} from {} samples", id, samples.len());
+
+        Ok(Some(id))
+    }
+
+    /// stores the dictionary and uses it for new blobs from now on
+    pub fn add_blob_dictionary(&self, dictionary: &[u8], samples: usize) -> Result<u32> {
+        let connection = self.pool.get()?;
+        let created_on = chrono::Utc::now().timestamp_millis();
+        connection.execute(INSERT_BLOB_DICTIONARY, params![dictionary, samples, created_on])?;
+        let id = connection.last_insert_rowid() as u32;
+
+        self.codec.add_dictionary(id, dictionary);
+        self.codec.set_active(id);
+
+        Ok(id)
+    }
 
-            sql_files.sort_by_key(|entry| entry.path());
+    /// rewrites up to `limit` rows after `after` whose blobs aren't in the current format,
+    /// returns the last row visited or `None` once the table is done
+    pub fn recompress_blobs(&self, table: BlobTable, after: i64, limit: usize) -> Result<Option<(i64, usize)>> {
+        let (select, update) = match table {
+            BlobTable::Entity => (SELECT_ENTITY_BLOBS_TO_RECOMPRESS, UPDATE_ENTITY_BLOBS),
+            BlobTable::Encounter => (SELECT_ENCOUNTER_BLOBS_TO_RECOMPRESS, UPDATE_ENCOUNTER_BLOBS),
+        };
+        let header = self.codec.current_header().to_bytes().to_vec();
 
-            for file in sql_files {
-                let path = file.path();
-                info!("Running migration: {:?}", path);
+        let mut connection = self.pool.get()?;
+        let tx = connection.transaction()?;
 
-                let sql = fs::read_to_string(&path)?;
-                connection.execute_batch(&sql)?;
+        let rows = tx
+            .prepare_cached(select)?
+            .query_map(params![after, header, limit], |row| {
+                let values = (1..row.as_ref().column_count())
+                    .map(|index| row.get::<_, rusqlite::types::Value>(index))
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                rusqlite::Result::Ok((row.get::<_, i64>(0)?, values))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let Some(last) = rows.last().map(|(rowid, _)| *rowid) else {
+            return Ok(None);
+        };
+
+        let mut rewritten = 0;
+
+        for (rowid, values) in rows {
+            let mut encoded = vec![];
+
+            for value in values {
+                let json = match value {
+                    rusqlite::types::Value::Text(value) => value.into_bytes(),
+                    rusqlite::types::Value::Blob(value) => match self.codec.decode_bytes(&value) {
+                        Ok(json) => json,
+                        Err(err) => {
+                            warn!("leaving undecodable blob in {:?} {} as is: {:?}", table, rowid, err);
+                            encoded.push(rusqlite::types::Value::Blob(value));
+                            continue;
+                        }
+                    },
+                    value => {
+                        encoded.push(value);
+                        continue;
+                    }
+                };
+
+                encoded.push(if json.is_empty() {
+                    rusqlite::types::Value::Null
+                } else {
+                    rusqlite::types::Value::Blob(self.codec.encode_bytes(&json)?)
+                });
             }
+
+            encoded.push(rowid.into());
+            tx.prepare_cached(update)?.execute(params_from_iter(encoded))?;
+            rewritten += 1;
         }
-        else {
+
+        tx.commit()?;
+
+        Ok(Some((last, rewritten)))
+    }
+
+    pub fn backup_path(&self) -> &Path {
+        &self.backup_path
+    }
+
+    /// online copy of the live database, writes from the meter are picked up while it runs
+    pub fn backup_to(&self, path: &Path) -> Result<()> {
+        let connection = self.pool.get()?;
+        let temp_path = path.with_extension("tmp");
+        let mut target = Connection::open(&temp_path)?;
+
+        Backup::new(&connection, &mut target)?
+            .run_to_completion(BACKUP_PAGES_PER_STEP, Duration::from_millis(BACKUP_STEP_PAUSE_MS), None)?;
+        drop(target);
+
+        fs::rename(&temp_path, path)?;
+
+        Ok(())
+    }
+
+    /// replaces the content of the live database, the pool stays usable afterwards,
+    /// backups taken before a migration are brought up to the current schema
+    pub fn restore_from(&self, path: &Path, migration_path: PathBuf) -> Result<()> {
+        let source = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+
+        {
             let mut connection = self.pool.get()?;
-            let mut statement = connection.prepare("SELECT 1 FROM sqlite_master WHERE type=? AND name=?")?;
-            let table_exists = statement.exists(["table", "encounter"])?;
+            Backup::new(&source, &mut connection)?
+                .run_to_completion(BACKUP_PAGES_PER_STEP, Duration::from_millis(BACKUP_STEP_PAUSE_MS), None)?;
         }
 
+        self.setup(migration_path)
+            .context("could not migrate the restored database")
+    }
+
+    fn get_applied_migrations(connection: &Connection) -> Result<Vec<String>> {
+        let mut statement = connection.prepare(SELECT_MIGRATIONS)?;
+        let names = statement
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+
+        Ok(names)
+    }
+
+    fn insert_migration(connection: &Connection, file_name: &str) -> Result<()> {
+        let recorded_on = chrono::Utc::now().timestamp_millis();
+        connection.execute(INSERT_MIGRATION, params![file_name, recorded_on])?;
+
         Ok(())
     }
 
@@ -83,7 +320,7 @@ impl Database {
         let connection = self.pool.get()?;
 
         if keep_favorites {
-            connection.execute(DELETE_FROM_ENCOUNTERS_IDS, [])?;
+            connection.execute(DELETE_FROM_ENCOUNTERS_INCLUDE_PREVIEW_IDS, [])?;
         } else {
             connection.execute(DELETE_FROM_ENCOUNTERS, [])?;
         }
@@ -97,7 +334,7 @@ impl Database {
 
         let query = if force_resync { "= '0'" } else { "IS NULL" };
         let mut args = std::collections::HashMap::new();
-        args.insert("{}".to_string(), query.to_string());
+        args.insert("upstream".to_string(), query.to_string());
         let query = strfmt(SELECT_ENCOUNTER_PREVIEW_JOIN_SYNC_FILTERED, &args)?;
 
         let mut statement = connection.prepare_cached(&query)?;
@@ -122,20 +359,6 @@ impl Database {
         Ok(())
     }
 
-    pub async fn insert_migration(&self, id: String) -> Result<()> {
-        let connection = self.pool.get()?;
-
-        connection.execute("PRAGMA foreign_keys = ON;", params![])?;
-        
-        let mut statement = connection.prepare_cached(DELETE_FROM_ENCOUNTERS_ID)?;
-
-        info!("deleting encounter: {}", id);
-
-        statement.execute(params![id])?;
-
-        Ok(())
-    }
-
     pub async fn delete_encounter(&self, id: String) -> Result<()> {
         let connection = self.pool.get()?;
 
@@ -153,18 +376,24 @@ impl Database {
     pub async fn load_encounter(&self, id: String) -> Result<Encounter> {
         let connection = self.pool.get()?;
 
-        let mut statement = connection.prepare_cached(SELECT_ENCOUNTER_JOIN_PREVIEW_BY_ID)?;
-
-        let (mut encounter, compressed) = statement
-            .query_row(params![id], |row| parse_encounter(row))
-            .unwrap_or_else(|_| (Encounter::default(), false));
-
-        let mut statement = connection.prepare_cached(SELECT_ENTITY_BY_ENCOUNTER_ID)?;
+        let mut encounter = self
+            .read_blobs(&connection, || {
+                connection
+                    .prepare_cached(SELECT_ENCOUNTER_JOIN_PREVIEW_BY_ID)?
+                    .query_row(params![id], |row| parse_encounter(row, &self.codec))
+                    .optional()
+            })?
+            .unwrap_or_default();
 
-        let entity_iter = statement.query_map(params![id], |row| parse_entity(row, compressed))?;
+        let entity_list = self.read_blobs(&connection, || {
+            connection
+                .prepare_cached(SELECT_ENTITY_BY_ENCOUNTER_ID)?
+                .query_map(params![id], |row| parse_entity(row, &self.codec))?
+                .collect::<rusqlite::Result<Vec<_>>>()
+        })?;
 
         let mut entities: HashMap<String, EncounterEntity> = HashMap::new();
-        for entity in entity_iter.flatten() {
+        for entity in entity_list {
             entities.insert(entity.name.to_string(), entity);
         }
 
@@ -184,7 +413,7 @@ impl Database {
         let mut statement = connection
             .prepare_cached(SELECT_LATEST_ENCOUNTER_ID)?;
 
-        let id = statement.query_row(params![], |row| row.get(0))?;
+        let id = statement.query_row(params![], |row| row.get(0)).optional()?;
 
         Ok(id)
     }
@@ -195,97 +424,22 @@ impl Database {
         search: String,
         filter: SearchFilter) -> Result<(Vec<EncounterPreview>, i32)> {
         let connection = self.pool.get()?;
-        let mut sql_params = vec![];
-
-        let join_clause = if search.len() > 2 {
-            let escaped_search = search
-                .split_whitespace()
-                .map(|word| format!("\"{}\"", word.replace("\"", "")))
-                .collect::<Vec<_>>()
-                .join(" ");
-            
-            sql_params.push(escaped_search);
-            "JOIN encounter_search(?) ON encounter_search.rowid = e.id"
-        } else {
-            ""
-        };
+        let query = SearchQuery::new(&search, &filter)?;
 
-        sql_params.push((filter.min_duration * 1000).to_string());
-
-        let boss_filter = if !filter.bosses.is_empty() {
-            let mut placeholders = "?,".repeat(filter.bosses.len());
-            placeholders.pop(); // remove trailing comma
-            sql_params.extend(filter.bosses);
-            format!("AND e.current_boss IN ({})", placeholders)
-        } else {
-            "".to_string()
-        };
-
-        let raid_clear_filter = if filter.cleared {
-            "AND cleared = 1"
-        } else {
-            ""
-        };
-
-        let favorite_filter = if filter.favorite {
-            "AND favorite = 1"
-        } else {
-            ""
-        };
-
-        let boss_only_damage_filter = if filter.boss_only_damage {
-            "AND boss_only_damage = 1"
-        } else {
-            ""
-        };
-
-        let difficulty_filter = if !filter.difficulty.is_empty() {
-            sql_params.push(filter.difficulty);
-            "AND difficulty = ?"
-        } else {
-            ""
-        };
-
-        let sort = format!("e.{}", filter.sort);
-
-        let count_params = sql_params.clone();
-
-        let mut args = std::collections::HashMap::new();
-        args.insert("{join}".to_string(), join_clause.to_string());
-        args.insert("{boss}".to_string(), boss_filter.to_string());
-        args.insert("{clear}".to_string(), raid_clear_filter.to_string());
-        args.insert("{favorite}".to_string(), favorite_filter.to_string());
-        args.insert("{difficulty}".to_string(), difficulty_filter.to_string());
-        args.insert("{boss_only_damage}".to_string(), boss_only_damage_filter.to_string());
-        args.insert("{sort}".to_string(), sort);
-        args.insert("{order}".to_string(), filter.order);
-
-        let query = strfmt(SELECT_ENCOUNTER_PREVIEW, &args)?;
-
-        let mut statement = connection.prepare_cached(&query)?;
+        let mut statement = connection.prepare_cached(&query.select(SELECT_ENCOUNTER_PREVIEW_COLUMNS))?;
 
         let offset = (page - 1) * page_size;
 
-        sql_params.push(page_size.to_string());
-        sql_params.push(offset.to_string());
-
-        let sql_params = params_from_iter(sql_params);
+        let mut sql_params = query.params();
+        sql_params.push(page_size.into());
+        sql_params.push(offset.into());
 
-        let encounter_iter = statement.query_map(sql_params, |row| parse_encounter_preview(row))?;
+        let encounter_iter = statement.query_map(params_from_iter(sql_params), |row| parse_encounter_preview(row))?;
 
         let encounters: Vec<EncounterPreview> = encounter_iter.collect::<Result<_, _>>()?;
 
-        let mut args = std::collections::HashMap::new();
-        args.insert("{join}".to_string(), join_clause.to_string());
-        args.insert("{boss}".to_string(), boss_filter.to_string());
-        args.insert("{clear}".to_string(), raid_clear_filter.to_string());
-        args.insert("{favorite}".to_string(), favorite_filter.to_string());
-        args.insert("{boss_only_damage}".to_string(), boss_only_damage_filter.to_string());
-
-        let query = strfmt(SELECT_ENCOUNTER_PREVIEW_FILTERED_COUNT, &args)?;
-
         let count: i32 = connection
-            .query_row_and_then(&query, params_from_iter(count_params), |row| row.get(0))?;
+            .query_row_and_then(&query.count(), params_from_iter(query.params()), |row| row.get(0))?;
 
         Ok((encounters, count))
     }
@@ -299,11 +453,62 @@ impl Database {
         Ok(())
     }
 
+    /// deletes what the policy matches unless `dry_run`, the report lists the matches either way
+    pub async fn apply_retention(&self, policy: &RetentionSettings, dry_run: bool) -> Result<RetentionReport> {
+        let db_size = fs::metadata(&self.path).map(|metadata| metadata.len()).unwrap_or_default();
+        let now = chrono::Utc::now().timestamp_millis();
+
+        let rows = {
+            let connection = self.pool.get()?;
+            let mut statement = connection.prepare_cached(SELECT_RETENTION_ROWS)?;
+
+            statement
+                .query_map([], |row| {
+                    rusqlite::Result::Ok(RetentionRow {
+                        id: row.get(0)?,
+                        fight_start: row.get::<_, Option<i64>>(1)?.unwrap_or_default(),
+                        boss: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                        favorite: row.get(3)?,
+                        cleared: row.get::<_, Option<bool>>(4)?.unwrap_or_default(),
+                        personal_best: row.get::<_, Option<bool>>(5)?.unwrap_or_default(),
+                    })
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?
+        };
+
+        let total = rows.len();
+        let entries = retention::plan(rows, policy, now, db_size);
+
+        if !dry_run && !entries.is_empty() {
+            let mut connection = self.pool.get()?;
+            connection.execute("PRAGMA foreign_keys = ON;", [])?;
+
+            let tx = connection.transaction()?;
+
+            {
+                let mut statement = tx.prepare_cached(DELETE_FROM_ENCOUNTERS_ID)?;
+
+                for entry in entries.iter() {
+                    statement.execute(params![entry.id])?;
+                }
+            }
+
+            tx.commit()?;
+            info!("retention deleted {} of {} encounters", entries.len(), total);
+        }
+
+        Ok(RetentionReport {
+            dry_run,
+            total,
+            entries,
+        })
+    }
+
     pub async fn get_db_stats(&self, min_duration: i64) -> Result<(i32, i32)> {
         let connection = self.pool.get()?;
 
         let result: (i32, i32) = connection
-            .query_row(SELECT_STATS, [], |row| {
+            .query_row(SELECT_STATS, params![min_duration * 1000], |row| {
                 let result = (row.get::<_, i32>(0).unwrap(), row.get::<_, i32>(1).unwrap());
                 rusqlite::Result::Ok(result)
             })?;
@@ -382,8 +587,348 @@ impl Database {
         Ok(())
     }
 
+    pub async fn export_encounters(&self, ids: Vec<i32>) -> Result<Vec<EncounterExportEntry>> {
+        let connection = self.pool.get()?;
+        let mut entries = vec![];
+
+        for id in ids {
+            let encounter = self
+                .read_blobs(&connection, || {
+                    connection.query_row(SELECT_ENCOUNTER_EXPORT_BY_ID, params![id], |row| row_to_map(row, &ENCOUNTER_JSON_COLUMNS, &self.codec))
+                })
+                .with_context(|| format!("could not export encounter {}", id))?;
+
+            let preview = connection
+                .query_row(SELECT_ENCOUNTER_PREVIEW_EXPORT_BY_ID, params![id], |row| row_to_map(row, &[], &self.codec))?;
+
+            let entities = self.read_blobs(&connection, || {
+                connection
+                    .prepare_cached(SELECT_ENTITY_EXPORT_BY_ENCOUNTER_ID)?
+                    .query_map(params![id], |row| row_to_map(row, &ENTITY_JSON_COLUMNS, &self.codec))?
+                    .collect::<rusqlite::Result<Vec<_>>>()
+            })?;
+
+            let sync = connection
+                .query_row(SELECT_SYNC_LOG_EXPORT_BY_ENCOUNTER_ID, params![id], |row| row_to_map(row, &[], &self.codec))
+                .optional()?;
+
+            entries.push(EncounterExportEntry {
+                encounter,
+                preview,
+                entities,
+                sync
+            });
+        }
+
+        info!("exported {} encounters", entries.len());
+
+        Ok(entries)
+    }
+
+    pub async fn import_encounters(&self, export: EncounterExport) -> Result<ImportSummary> {
+        if export.version > EXPORT_VERSION {
+            bail!("unsupported export version {}", export.version);
+        }
+
+        let mut connection = self.pool.get()?;
+        connection.execute("PRAGMA foreign_keys = ON;", params![])?;
+
+        let tx = connection.transaction()?;
+        let mut summary = ImportSummary::default();
+
+        for entry in export.encounters {
+            let EncounterExportEntry {
+                mut encounter,
+                mut preview,
+                entities,
+                sync
+            } = entry;
+
+            let key = ["fight_start", "current_boss", "local_player"]
+                .iter()
+                .map(|column| json_to_sql(preview.get(*column).unwrap_or(&serde_json::Value::Null), false, false))
+                .collect::<Result<Vec<_>>>()?;
+
+            let duplicate: Option<i64> = tx
+                .query_row(SELECT_DUPLICATE_ENCOUNTER, params_from_iter(key), |row| row.get(0))
+                .optional()?;
+
+            if duplicate.is_some() {
+                summary.duplicates += 1;
+                continue;
+            }
+
+            encounter.remove("id");
+            let encounter_id = self.insert_row(&tx, "encounter", &encounter, &ENCOUNTER_BLOB_COLUMNS)?;
+
+            preview.insert("id".into(), json!(encounter_id));
+            self.insert_row(&tx, "encounter_preview", &preview, &[])?;
+
+            for mut entity in entities {
+                entity.insert("encounter_id".into(), json!(encounter_id));
+                self.insert_row(&tx, "entity", &entity, &ENTITY_BLOB_COLUMNS)?;
+            }
+
+            if let Some(mut sync) = sync {
+                sync.insert("encounter_id".into(), json!(encounter_id));
+                self.insert_row(&tx, "sync_logs", &sync, &[])?;
+            }
+
+            summary.imported.push(encounter_id);
+        }
+
+        tx.commit()?;
+
+        info!("imported {} encounters, skipped {} duplicates", summary.imported.len(), summary.duplicates);
+
+        Ok(summary)
+    }
+
+    fn insert_row(
+        &self,
+        tx: &Transaction,
+        table: &str,
+        row: &serde_json::Map<String, serde_json::Value>,
+        blob_columns: &[&str]) -> Result<i64> {
+        let mut statement = tx.prepare_cached(SELECT_TABLE_COLUMNS)?;
+        let known_columns = statement
+            .query_map([table], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut columns = vec![];
+        let mut values = vec![];
+
+        for (column, value) in row {
+            if !known_columns.contains(column) {
+                warn!("skipping unknown column {}.{}", table, column);
+                continue;
+            }
+
+            columns.push(column.as_str());
+            values.push(json_to_sql(value, blob_columns.contains(&column.as_str()), &self.codec)?);
+        }
+
+        let placeholders = vec!["?"; columns.len()].join(",");
+        let sql = format!("INSERT INTO {} ({}) VALUES ({})", table, columns.join(","), placeholders);
+        tx.execute(&sql, params_from_iter(values))?;
+
+        Ok(tx.last_insert_rowid())
+    }
+
+    pub fn get_cached_player_stats(
+        &self,
+        region: &str,
+        names: &[String],
+        min_fetched_on: i64) -> Result<HashMap<String, PlayerStats>> {
+        let connection = self.pool.get()?;
+        let mut statement = connection.prepare_cached(SELECT_PLAYER_STATS_CACHE)?;
+        let mut cached = HashMap::new();
+
+        for name in names {
+            let value: Option<String> = statement
+                .query_row(params![name, region, min_fetched_on], |row| row.get(0))
+                .optional()?;
+
+            if let Some(stats) = value.and_then(|value| serde_json::from_str(&value).ok()) {
+                cached.insert(name.clone(), stats);
+            }
+        }
+
+        Ok(cached)
+    }
+
+    pub fn cache_player_stats(&self, region: &str, stats: &HashMap<String, PlayerStats>, fetched_on: i64) -> Result<()> {
+        let mut connection = self.pool.get()?;
+        let tx = connection.transaction()?;
+
+        {
+            let mut statement = tx.prepare_cached(UPSERT_PLAYER_STATS_CACHE)?;
+
+            for (name, player_stats) in stats {
+                statement.execute(params![name, region, fetched_on, serde_json::to_string(player_stats)?])?;
+            }
+        }
+
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    pub fn get_encounter_ranking(&self, encounter_id: i64) -> Result<EncounterRanking> {
+        let connection = self.pool.get()?;
+
+        let ranking = connection
+            .prepare_cached(SELECT_ENCOUNTER_PREVIEW_RANKING)?
+            .query_row(params![encounter_id], |row| {
+                rusqlite::Result::Ok(EncounterRanking {
+                    personal_best: row.get::<_, Option<bool>>(0)?.unwrap_or_default(),
+                    personal_percentile: row.get(1)?,
+                    local_percentile: row.get(2)?,
+                })
+            })?;
+
+        Ok(ranking)
+    }
+
+    pub fn enqueue_player_info(&self, encounter_id: i64, request: &InspectRequest, next_attempt_on: i64) -> Result<()> {
+        let connection = self.pool.get()?;
+        let now = chrono::Utc::now().timestamp_millis();
+
+        connection
+            .prepare_cached(UPSERT_PLAYER_INFO_QUEUE)?
+            .execute(params![encounter_id, serde_json::to_string(request)?, next_attempt_on, now])?;
+
+        Ok(())
+    }
+
+    pub fn get_due_player_info(&self, now: i64) -> Result<Vec<(i64, InspectRequest, u32)>> {
+        let connection = self.pool.get()?;
+        let mut statement = connection.prepare_cached(SELECT_DUE_PLAYER_INFO_QUEUE)?;
+
+        let rows = statement
+            .query_map(params![now], |row| {
+                rusqlite::Result::Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, u32>(2)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut due = vec![];
+
+        for (encounter_id, request, attempts) in rows {
+            match serde_json::from_str(&request) {
+                std::result::Result::Ok(request) => due.push((encounter_id, request, attempts)),
+                Err(err) => {
+                    warn!("dropping invalid player info request for {}: {:?}", encounter_id, err);
+                    self.remove_player_info(encounter_id)?;
+                }
+            }
+        }
+
+        Ok(due)
+    }
+
+    pub fn reschedule_player_info(&self, encounter_id: i64, attempts: u32, next_attempt_on: i64, error: &str) -> Result<()> {
+        let connection = self.pool.get()?;
+
+        connection
+            .prepare_cached(UPDATE_PLAYER_INFO_QUEUE)?
+            .execute(params![attempts, next_attempt_on, error, encounter_id])?;
+
+        Ok(())
+    }
+
+    pub fn remove_player_info(&self, encounter_id: i64) -> Result<()> {
+        let connection = self.pool.get()?;
+
+        connection
+            .prepare_cached(DELETE_PLAYER_INFO_QUEUE)?
+            .execute(params![encounter_id])?;
+
+        Ok(())
+    }
+
+    /// writes late inspect data into an already saved encounter and recalculates the stats of its entities,
+    /// returns the number of updated players
+    pub fn backfill_player_stats(&self, encounter_id: i64, player_info: &HashMap<String, PlayerStats>) -> Result<usize> {
+        let mut connection = self.pool.get()?;
+        let tx = connection.transaction()?;
+
+        let encounter = self.read_blobs(&tx, || {
+            tx.prepare_cached(SELECT_ENCOUNTER_JOIN_PREVIEW_BY_ID)?
+                .query_row(params![encounter_id], |row| parse_encounter(row, &self.codec))
+                .optional()
+        })?;
+
+        let Some(encounter) = encounter else {
+            // deleted in the meantime
+            return Ok(0);
+        };
+
+        let mut entities = self.read_blobs(&tx, || {
+            tx.prepare_cached(SELECT_ENTITY_BY_ENCOUNTER_ID)?
+                .query_map(params![encounter_id], |row| parse_entity(row, &self.codec))?
+                .collect::<rusqlite::Result<Vec<_>>>()
+        })?;
+
+        // the logs are folded into the saved skills, only the player info is new
+        let duration_seconds = max((encounter.last_combat_packet - encounter.fight_start) / 1000, 1);
+        calculate_stats(
+            &mut entities,
+            encounter.fight_start,
+            encounter.last_combat_packet,
+            duration_seconds,
+            &HashMap::new(),
+            &HashMap::new(),
+            Some(player_info.clone()),
+            &encounter.encounter_damage_stats,
+            &HashMap::new())?;
+
+        let mut updated = 0;
+        let mut local_character_id = None;
+
+        for entity in entities.iter().filter(|entity| player_info.contains_key(&entity.name)) {
+            let skills = self.codec.encode(&entity.skills)?;
+            let damage_stats = self.codec.encode(&entity.damage_stats)?;
+
+            tx.prepare_cached(UPDATE_ENTITY_PLAYER_INFO)?.execute(params![
+                skills,
+                damage_stats,
+                entity.damage_stats.dps,
+                entity.spec,
+                json!(entity.engraving_data),
+                entity.ark_passive_active,
+                json!(entity.ark_passive_data),
+                encounter_id,
+                entity.name
+            ])?;
+
+            tx.prepare_cached(UPDATE_PLAYER_STATS_SPEC)?
+                .execute(params![entity.spec, encounter_id, entity.character_id as i64])?;
+
+            if entity.name == encounter.local_player {
+                local_character_id = Some(entity.character_id as i64);
+            }
+
+            updated += 1;
+        }
+
+        // the spec decides which history the encounter is ranked against
+        if let Some(character_id) = local_character_id {
+            let ranking = Self::rank_encounter(&tx, encounter_id, character_id)?.unwrap_or_default();
+
+            tx.prepare_cached(UPDATE_ENCOUNTER_PREVIEW_RANKING)?.execute(params![
+                ranking.personal_best,
+                ranking.personal_percentile,
+                ranking.local_percentile,
+                encounter_id
+            ])?;
+        }
+
+        tx.commit()?;
+
+        Ok(updated)
+    }
+
     pub fn insert_data(&self, model: SaveToDb) -> Result<i64> {
+        let mut connection = self.pool.get()?;
+        let tx = connection.transaction()?;
+        let encounter_id = self.insert_model(&tx, model)?;
+        tx.commit()?;
+
+        Ok(encounter_id)
+    }
+
+    /// keeps only `model`, a reader sees either the old or the new encounter and no vacuum runs
+    pub fn replace_encounters(&self, model: SaveToDb) -> Result<i64> {
+        let mut connection = self.pool.get()?;
+        let tx = connection.transaction()?;
+        tx.execute(DELETE_FROM_ENCOUNTERS, [])?;
+        let encounter_id = self.insert_model(&tx, model)?;
+        tx.commit()?;
+
+        Ok(encounter_id)
+    }
 
+    fn insert_model(&self, tx: &Transaction, model: SaveToDb) -> Result<i64> {
         let SaveToDb {
             misc,
             duration,
@@ -401,19 +946,18 @@ impl Database {
             skill_cast_log,
             boss_hp_log,
             current_boss_name,
+            boss_max_hp,
+            ntp_fight_start,
             ..
         } = model;
 
-        let mut connection = self.pool.get()?;
-        let tx = connection.transaction()?;
-        
         let duration_seconds = (updated_on - started_on).num_seconds();
         let fight_start = started_on.timestamp_millis();
         let fight_end = updated_on.timestamp_millis();
-        let compressed_boss_hp = compress_json(&boss_hp_log)?;
-        let compressed_buffs = compress_json(&encounter_damage_stats.buffs)?;
-        let compressed_debuffs = compress_json(&encounter_damage_stats.debuffs)?;
-        let compressed_shields = compress_json(&encounter_damage_stats.applied_shield_buffs)?;
+        let compressed_boss_hp = self.codec.encode(&boss_hp_log)?;
+        let compressed_buffs = self.codec.encode(&encounter_damage_stats.buffs)?;
+        let compressed_debuffs = self.codec.encode(&encounter_damage_stats.debuffs)?;
+        let compressed_shields = self.codec.encode(&encounter_damage_stats.applied_shield_buffs)?;
 
         let encounter_db = EncounterDb {
             last_combat_packet: updated_on.timestamp_millis(),
@@ -432,9 +976,27 @@ impl Database {
             compressed_boss_hp,
         };
 
-        let encounter_id = self.insert_encounter(&tx, encounter_db)?;
-        let db_entities = Self::to_entities_db(&entities, encounter_id)?;
-        self.insert_entities(&tx, encounter_id, db_entities)?;
+        let encounter_id = self.insert_encounter(tx, encounter_db)?;
+        let db_entities = self.to_entities_db(&entities, encounter_id)?;
+        self.insert_entities(tx, encounter_id, db_entities)?;
+
+        if let Some(raid_name) = boss_to_raid_map(&current_boss_name, boss_max_hp) {
+            self.insert_player_stats(
+                tx,
+                encounter_id,
+                &raid_name,
+                raid_difficulty.as_ref(),
+                raid_clear,
+                fight_start,
+                duration_seconds,
+                &entities,
+                &damage_log)?;
+        }
+
+        let ranking = match entities.iter().find(|e| e.name == local_player && is_valid_player(e)) {
+            Some(entity) => Self::rank_encounter(tx, encounter_id, entity.character_id as i64)?,
+            None => None,
+        }.unwrap_or_default();
 
         let mut players = entities
             .iter()
@@ -466,14 +1028,244 @@ impl Database {
             local_player: local_player,
             local_player_dps,
             raid_clear,
-            boss_only_damage: boss_only_damage
+            boss_only_damage: boss_only_damage,
+            personal_best: ranking.personal_best,
+            personal_percentile: ranking.personal_percentile,
+            local_percentile: ranking.local_percentile,
+            ntp_fight_start,
         };
 
-        self.insert_encounter_preview(&tx, preview)?;
+        self.insert_encounter_preview(tx, preview)?;
+
+        Ok(encounter_id)
+    }
+
+    pub fn insert_player_stats(
+        &self,
+        tx: &Transaction,
+        encounter_id: i64,
+        raid_name: &str,
+        raid_difficulty: &str,
+        raid_clear: bool,
+        fight_start: i64,
+        duration_seconds: i64,
+        entities: &[EncounterEntity],
+        damage_log: &HashMap<u64, Vec<(i64, i64)>>) -> Result<()> {
+
+        let gate = raid_name
+            .rsplit_once(" G")
+            .and_then(|(_, gate)| gate.parse::<u8>().ok());
+        let npc_ids: Vec<u32> = entities
+            .iter()
+            .filter(|e| e.entity_type == EntityType::Boss)
+            .map(|e| e.npc_id)
+            .collect();
+        let npc_ids = if npc_ids.is_empty() { None } else { Some(serde_json::to_vec(&npc_ids)?) };
+
+        let raid_id: i64 = tx
+            .prepare_cached(UPSERT_RAID)?
+            .query_row(params![fight_start, raid_name, None::<String>, gate, npc_ids], |row| row.get(0))?;
+
+        let players = entities
+            .iter()
+            .filter(|e| is_valid_player(e) && e.damage_stats.damage_dealt > 0);
+
+        for player in players {
+            let character_id = player.character_id as i64;
+            let damage_dealt = player.damage_stats.damage_dealt as f64;
+
+            tx.prepare_cached(UPSERT_PLAYER)?.execute(params![
+                character_id,
+                player.name,
+                player.class_id,
+                player.gear_score,
+                fight_start
+            ])?;
+
+            tx.prepare_cached(INSERT_PLAYER_HISTORY)?.execute(params![
+                character_id,
+                encounter_id,
+                player.name,
+                player.class_id,
+                player.gear_score,
+                fight_start
+            ])?;
+
+            tx.prepare_cached(INSERT_PLAYER_STATS)?.execute(params![
+                character_id,
+                encounter_id,
+                raid_id,
+                raid_difficulty,
+                player.class_id,
+                player.spec,
+                player.gear_score,
+                raid_clear,
+                fight_start,
+                duration_seconds,
+                player.damage_stats.damage_dealt,
+                player.damage_stats.dps,
+                player.damage_stats.debuffed_by_support as f64 / damage_dealt,
+                player.damage_stats.buffed_by_support as f64 / damage_dealt,
+                player.damage_stats.buffed_by_identity as f64 / damage_dealt
+            ])?;
+
+            if let Some(log) = damage_log.get(&player.id) {
+                let compressed_log = self.codec.encode(log)?;
+                tx.prepare_cached(INSERT_DAMAGE_LOG)?
+                    .execute(params![encounter_id, character_id, compressed_log])?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// adds the stats rows of up to `limit` raid encounters after `after` that were saved without them,
+    /// returns the last encounter visited or `None` once all are done
+    pub fn backfill_encounter_stats(&self, after: i64, limit: usize) -> Result<Option<(i64, usize)>> {
+        let mut connection = self.pool.get()?;
+        let tx = connection.transaction()?;
+
+        let rows = tx
+            .prepare_cached(SELECT_ENCOUNTERS_WITHOUT_PLAYER_STATS)?
+            .query_map(params![after, limit], |row| {
+                rusqlite::Result::Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, Option<i64>>(2)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let Some(last) = rows.last().map(|(id, _, _)| *id) else {
+            return Ok(None);
+        };
+
+        let mut backfilled = 0;
+
+        for (encounter_id, boss, boss_max_hp) in rows {
+            let Some(raid_name) = boss_to_raid_map(&boss, boss_max_hp.unwrap_or_default()) else {
+                continue;
+            };
+
+            let read = self.read_blobs(&tx, || {
+                let encounter = tx.prepare_cached(SELECT_ENCOUNTER_JOIN_PREVIEW_BY_ID)?
+                    .query_row(params![encounter_id], |row| parse_encounter(row, &self.codec))?;
+                let entities = tx.prepare_cached(SELECT_ENTITY_BY_ENCOUNTER_ID)?
+                    .query_map(params![encounter_id], |row| parse_entity(row, &self.codec))?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+
+                rusqlite::Result::Ok((encounter, entities))
+            });
+
+            let (encounter, entities) = match read {
+                Ok(read) => read,
+                Err(err) => {
+                    warn!("skipping stats of encounter {}: {:?}", encounter_id, err);
+                    continue;
+                }
+            };
+
+            self.insert_player_stats(
+                &tx,
+                encounter_id,
+                &raid_name,
+                encounter.difficulty.as_deref().unwrap_or_default(),
+                encounter.cleared,
+                encounter.fight_start,
+                max((encounter.last_combat_packet - encounter.fight_start) / 1000, 1),
+                &entities,
+                &HashMap::new())?;
+
+            backfilled += 1;
+        }
 
         tx.commit()?;
 
-        Ok(encounter_id)
+        Ok(Some((last, backfilled)))
+    }
+
+    /// recalculates the ranking of up to `limit` encounters after `after`,
+    /// returns the last encounter visited or `None` once all are done
+    pub fn rank_encounters(&self, after: i64, limit: usize) -> Result<Option<(i64, usize)>> {
+        let mut connection = self.pool.get()?;
+        let tx = connection.transaction()?;
+
+        let rows = tx
+            .prepare_cached(SELECT_ENCOUNTERS_TO_RANK)?
+            .query_map(params![after, limit], |row| {
+                rusqlite::Result::Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let Some(last) = rows.last().map(|(id, _)| *id) else {
+            return Ok(None);
+        };
+
+        let mut ranked = 0;
+
+        for (encounter_id, character_id) in rows {
+            let ranking = Self::rank_encounter(&tx, encounter_id, character_id)?.unwrap_or_default();
+
+            tx.prepare_cached(UPDATE_ENCOUNTER_PREVIEW_RANKING)?.execute(params![
+                ranking.personal_best,
+                ranking.personal_percentile,
+                ranking.local_percentile,
+                encounter_id
+            ])?;
+
+            ranked += 1;
+        }
+
+        tx.commit()?;
+
+        Ok(Some((last, ranked)))
+    }
+
+    fn rank_encounter(tx: &Transaction, encounter_id: i64, character_id: i64) -> Result<Option<EncounterRanking>> {
+        let mut statement = tx.prepare_cached(SELECT_PLAYER_DPS_HISTORY)?;
+        let rows = statement
+            .query_map(params![encounter_id, character_id], |row| {
+                rusqlite::Result::Ok((row.get::<_, i64>(0)?, row.get::<_, Option<i64>>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let Some(dps) = rows.first().map(|(dps, _)| *dps) else {
+            return Ok(None);
+        };
+        let personal_history: Vec<i64> = rows.iter().filter_map(|(_, dps)| *dps).collect();
+
+        let mut statement = tx.prepare_cached(SELECT_RAID_DPS_HISTORY)?;
+        let local_history = statement
+            .query_map(params![encounter_id, character_id], |row| row.get::<_, i64>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(Some(statistics::rank(dps, &personal_history, &local_history)))
+    }
+
+    pub async fn get_player_progression(
+        &self,
+        name: String,
+        raid_name: String,
+        difficulty: Option<String>) -> Result<Vec<PlayerProgression>> {
+        let connection = self.pool.get()?;
+
+        let mut statement = connection.prepare_cached(SELECT_PLAYER_PROGRESSION)?;
+        let rows = statement.query_map(params![name, raid_name, difficulty], |row| {
+            rusqlite::Result::Ok(PlayerProgression {
+                encounter_id: row.get(0)?,
+                fight_start: row.get(1)?,
+                difficulty: row.get(2)?,
+                spec: row.get(3)?,
+                gear_score: row.get(4)?,
+                cleared: row.get(5)?,
+                duration: row.get(6)?,
+                total_damage: row.get(7)?,
+                dps: row.get(8)?,
+                brand_uptime: row.get(9)?,
+                attack_power_uptime: row.get(10)?,
+                identity_uptime: row.get(11)?,
+            })
+        })?;
+
+        let progression = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(progression)
     }
 
     pub fn insert_entities(&self, tx: &Transaction, encounter_id: i64, entities: Vec<EntityDb>) -> Result<()> {
@@ -550,7 +1342,11 @@ impl Database {
             entity.local_player,
             entity.local_player_dps,
             entity.raid_clear,
-            entity.boss_only_damage
+            entity.boss_only_damage,
+            entity.personal_best,
+            entity.personal_percentile,
+            entity.local_percentile,
+            entity.ntp_fight_start
         ];
 
         statement.execute(sql_params)?;
@@ -558,13 +1354,13 @@ impl Database {
         Ok(())
     }
 
-    pub fn to_entities_db(entities: &Vec<EncounterEntity>, encounter_id: i64) -> Result<Vec<EntityDb>> {
+    pub fn to_entities_db(&self, entities: &Vec<EncounterEntity>, encounter_id: i64) -> Result<Vec<EntityDb>> {
         let mut entities_db = vec![];
 
          for entity in entities {
 
-            let compressed_skills = compress_json(&entity.skills)?;
-            let compressed_damage_stats = compress_json(&entity.damage_stats)?;
+            let compressed_skills = self.codec.encode(&entity.skills)?;
+            let compressed_damage_stats = self.codec.encode(&entity.damage_stats)?;
 
             let entity_db = EntityDb {
                 name: entity.name.clone(),
@@ -598,4 +1394,52 @@ impl Database {
     pub fn save_to_db(model: SaveToDb) {
         
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::core::stats_api::GemData;
+    use crate::database::create_test_database;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn should_recalculate_backfilled_players() {
+        let database = create_test_database();
+        let started_on = Utc::now();
+        let alice = EncounterEntity {
+            name: "Alice".to_string(),
+            entity_type: EntityType::Player,
+            class_id: 102,
+            max_hp: 1_000,
+            skills: HashMap::from([(16_140, Skill { id: 16_140, total_damage: 900_000, ..Default::default() })]),
+            ..Default::default()
+        };
+        let mut model = SaveToDb {
+            local_player: "Alice".to_string(),
+            started_on,
+            updated_on: started_on + chrono::Duration::seconds(90),
+            entities: vec![alice],
+            ..Default::default()
+        };
+        model.entities[0].damage_stats.damage_dealt = 900_000;
+        let encounter_id = database.insert_data(model).unwrap();
+
+        let gem = GemData { tier: 4, skill_id: 16_140, gem_type: 5, value: 4400 };
+        let info = HashMap::from([(
+            "Alice".to_string(),
+            PlayerStats { gems: Some(vec![gem]), ..Default::default() },
+        )]);
+        assert_eq!(database.backfill_player_stats(encounter_id, &info).unwrap(), 1);
+
+        let encounter = database.load_encounter(encounter_id.to_string()).await.unwrap();
+        let alice = &encounter.entities["Alice"];
+        assert_eq!(alice.damage_stats.dps, 10_000);
+        assert_eq!(alice.skills[&16_140].dps, 10_000);
+        assert_eq!(alice.skills[&16_140].gem_damage, Some(10));
+
+        assert_eq!(database.backfill_player_stats(encounter_id + 1, &info).unwrap(), 0);
+    }
+}
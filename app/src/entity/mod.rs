@@ -9,6 +9,8 @@ use std::{fmt, ops::DerefMut};
 use crate::{misc::data::{ESTHER_BY_NPC_ID, NPC_DATA}, entity::{npc::{Boss, Esther, Npc}, player::Player}};
 use std::ops::Deref;
 
+use crate::core::diagnostics::{self, UnknownId};
+
 #[derive(Debug)]
 pub struct Entity(BaseEntity, EntityVariant);
 
@@ -91,6 +93,8 @@ impl Entity {
             return Self(base, entity)
         }
 
+        diagnostics::record_unknown(UnknownId::Npc(npc_id));
+
         let entity = Npc {
             name: format!("{:x}", id),
             npc_id,
@@ -1,24 +1,30 @@
+use crate::core::comparison::{self, CompareOptions, EncounterComparison};
+use crate::core::statistics::{self, DpsSummary};
 use crate::handlers::error::AppError;
 use crate::misc::app_context::AppContext;
-use crate::constants::{LOGS_WINDOW_LABEL, METER_MINI_WINDOW_LABEL, METER_WINDOW_LABEL};
-use crate::database::Database;
+use crate::constants::{EXPORT_VERSION, DB_VERSION, LOGS_WINDOW_LABEL, METER_MINI_WINDOW_LABEL, METER_WINDOW_LABEL};
+use crate::database::{DatabaseManager, EncounterExport, ImportSummary};
 use crate::models::*;
 use crate::misc::settings::{Settings, SettingsManager};
 use crate::misc::utils::CommandsManager;
+use crate::sync::{SyncManager, UploadResult};
 use log::{error, info, warn};
 use window_vibrancy::{apply_blur, clear_blur};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
 use std::sync::{Arc, Mutex};
 use tauri::{command, ipc, AppHandle, State};
 use tauri::{Emitter, Manager};
 
 #[command]
 pub async fn load_encounters_preview(
-    database: State<'_, Database>,
+    database: State<'_, Arc<DatabaseManager>>,
     page: i32,
     page_size: i32,
     search: String,
     filter: SearchFilter,
 ) -> Result<EncountersOverview, AppError> {
+    let database = database.get();
 
     let (encounters, count) = database.load_encounters_preview(
         page,
@@ -36,7 +42,8 @@ pub async fn load_encounters_preview(
 }
 
 #[command(async)]
-pub async fn load_encounter(database: State<'_, Database>, id: String) -> Result<Encounter, AppError> {
+pub async fn load_encounter(database: State<'_, Arc<DatabaseManager>>, id: String) -> Result<Encounter, AppError> {
+    let database = database.get();
   
     let encounter = database.load_encounter(id)
         .await.map_err(|_| AppError::Database)?;
@@ -45,7 +52,8 @@ pub async fn load_encounter(database: State<'_, Database>, id: String) -> Result
 }
 
 #[command]
-pub async fn get_sync_candidates(database: State<'_, Database>, force_resync: bool) -> Result<Vec<i32>, AppError> {
+pub async fn get_sync_candidates(database: State<'_, Arc<DatabaseManager>>, force_resync: bool) -> Result<Vec<i32>, AppError> {
+    let database = database.get();
 
     let result = database
         .get_sync_candidates(force_resync)
@@ -55,7 +63,8 @@ pub async fn get_sync_candidates(database: State<'_, Database>, force_resync: bo
 }
 
 #[command]
-pub async fn get_encounter_count(database: State<'_, Database>) -> Result<i32, AppError> {
+pub async fn get_encounter_count(database: State<'_, Arc<DatabaseManager>>) -> Result<i32, AppError> {
+    let database = database.get();
     
     let count = database
         .get_encounter_count()
@@ -65,7 +74,8 @@ pub async fn get_encounter_count(database: State<'_, Database>) -> Result<i32, A
 }
 
 #[command]
-pub async fn open_most_recent_encounter(app_handle: AppHandle, database: State<'_, Database>) -> Result<(), AppError> {
+pub async fn open_most_recent_encounter(app_handle: AppHandle, database: State<'_, Arc<DatabaseManager>>) -> Result<(), AppError> {
+    let database = database.get();
    
     let id = database.get_last_encounter().await.map_err(|_| AppError::Database)?;
 
@@ -85,14 +95,16 @@ pub async fn open_most_recent_encounter(app_handle: AppHandle, database: State<'
 }
 
 #[command]
-pub async fn toggle_encounter_favorite(database: State<'_, Database>, id: i32) -> Result<(), AppError> {
+pub async fn toggle_encounter_favorite(database: State<'_, Arc<DatabaseManager>>, id: i32) -> Result<(), AppError> {
+    let database = database.get();
     database.toggle_encounter_favorite(id).await.map_err(|_| AppError::Database)?;
 
     Ok(())
 }
 
 #[command]
-pub async fn delete_encounter(database: State<'_, Database>, id: String) -> Result<(), AppError> {
+pub async fn delete_encounter(database: State<'_, Arc<DatabaseManager>>, id: String) -> Result<(), AppError> {
+    let database = database.get();
    
     database.delete_encounter(id).await.map_err(|_| AppError::Database)?;
 
@@ -100,7 +112,8 @@ pub async fn delete_encounter(database: State<'_, Database>, id: String) -> Resu
 }
 
 #[command]
-pub async fn delete_encounters(database: State<'_, Database>, ids: Vec<i32>) -> Result<(), AppError> {
+pub async fn delete_encounters(database: State<'_, Arc<DatabaseManager>>, ids: Vec<i32>) -> Result<(), AppError> {
+    let database = database.get();
 
     database.delete_encounters(ids).await.map_err(|_| AppError::Database)?;
 
@@ -109,10 +122,11 @@ pub async fn delete_encounters(database: State<'_, Database>, ids: Vec<i32>) ->
 
 #[command]
 pub async fn delete_encounters_below_min_duration(
-    database: State<'_, Database>,
+    database: State<'_, Arc<DatabaseManager>>,
     min_duration: i64,
     keep_favorites: bool,
 ) -> Result<(), AppError> {
+    let database = database.get();
     
     database.delete_encounters_below_min_duration(min_duration, keep_favorites)
         .await.map_err(|_| AppError::Database)?;
@@ -121,7 +135,8 @@ pub async fn delete_encounters_below_min_duration(
 }
 
 #[command]
-pub async fn sync(database: State<'_, Database>, encounter: i32, upstream: String, failed: bool) -> Result<(), AppError> {
+pub async fn sync(database: State<'_, Arc<DatabaseManager>>, encounter: i32, upstream: String, failed: bool) -> Result<(), AppError> {
+    let database = database.get();
 
     database.insert_sync_log(encounter, upstream, failed)
         .await.map_err(|_| AppError::Database)?;
@@ -130,7 +145,49 @@ pub async fn sync(database: State<'_, Database>, encounter: i32, upstream: Strin
 }
 
 #[command]
-pub async fn delete_all_uncleared_encounters(database: State<'_, Database>, keep_favorites: bool) -> Result<(), AppError> {
+pub async fn start_sync(
+    app_handle: AppHandle,
+    manager: State<'_, Mutex<SettingsManager>>,
+    sync_manager: State<'_, Arc<SyncManager>>,
+    access_token: String,
+    visibility: String,
+    force_resync: bool,
+) -> Result<(), AppError> {
+    let settings = manager.lock().unwrap().get().map_err(|_| AppError::FileSystem)?.sync.clone();
+
+    sync_manager
+        .start(app_handle, &settings, access_token, visibility, force_resync)
+        .map_err(|err| AppError::Sync(err.to_string()))?;
+
+    Ok(())
+}
+
+#[command]
+pub async fn cancel_sync(sync_manager: State<'_, Arc<SyncManager>>) -> Result<bool, AppError> {
+    Ok(sync_manager.cancel())
+}
+
+#[command]
+pub async fn upload_encounter(
+    manager: State<'_, Mutex<SettingsManager>>,
+    sync_manager: State<'_, Arc<SyncManager>>,
+    id: i32,
+    access_token: String,
+    visibility: String,
+) -> Result<UploadResult, AppError> {
+    let settings = manager.lock().unwrap().get().map_err(|_| AppError::FileSystem)?.sync.clone();
+
+    let result = sync_manager
+        .upload(&settings, access_token, visibility, id)
+        .await
+        .map_err(|err| AppError::Sync(err.to_string()))?;
+
+    Ok(result)
+}
+
+#[command]
+pub async fn delete_all_uncleared_encounters(database: State<'_, Arc<DatabaseManager>>, keep_favorites: bool) -> Result<(), AppError> {
+    let database = database.get();
 
     database.delete_all_uncleared_encounters(keep_favorites)
         .await.map_err(|_| AppError::Database)?;
@@ -139,10 +196,116 @@ pub async fn delete_all_uncleared_encounters(database: State<'_, Database>, keep
 }
 
 #[command]
-pub async fn delete_all_encounters(database: State<'_, Database>, keep_favorites: bool) -> Result<(), AppError> {
+pub async fn delete_all_encounters(database: State<'_, Arc<DatabaseManager>>, keep_favorites: bool) -> Result<(), AppError> {
+    let database = database.get();
     
     database.delete_all_encounters(keep_favorites)
         .await.map_err(|_| AppError::Database)?;
 
     Ok(())
 }
+
+#[command]
+pub async fn export_encounters(
+    context: State<'_, Arc<AppContext>>,
+    database: State<'_, Arc<DatabaseManager>>,
+    ids: Vec<i32>,
+    path: String,
+) -> Result<usize, AppError> {
+    let database = database.get();
+
+    let encounters = database.export_encounters(ids)
+        .await.map_err(|err| {
+            error!("could not export encounters: {}", err);
+            AppError::Database
+        })?;
+    let count = encounters.len();
+
+    let export = EncounterExport {
+        version: EXPORT_VERSION,
+        app_version: context.version.clone(),
+        db_version: DB_VERSION,
+        exported_on: chrono::Utc::now().timestamp_millis(),
+        encounters,
+    };
+
+    let writer = BufWriter::new(File::create(&path)?);
+    serde_json::to_writer(writer, &export).map_err(|_| AppError::FileSystem)?;
+
+    info!("exported {} encounters to {}", count, path);
+
+    Ok(count)
+}
+
+#[command]
+pub async fn import_encounters(database: State<'_, Arc<DatabaseManager>>, path: String) -> Result<ImportSummary, AppError> {
+    let database = database.get();
+
+    let reader = BufReader::new(File::open(&path)?);
+    let export: EncounterExport = serde_json::from_reader(reader).map_err(|_| AppError::FileSystem)?;
+
+    let summary = database.import_encounters(export)
+        .await.map_err(|err| {
+            error!("could not import encounters: {}", err);
+            AppError::Database
+        })?;
+
+    Ok(summary)
+}
+
+#[command]
+pub async fn get_player_progression(
+    database: State<'_, Arc<DatabaseManager>>,
+    name: String,
+    raid_name: String,
+    difficulty: Option<String>,
+) -> Result<Vec<PlayerProgression>, AppError> {
+    let database = database.get();
+
+    let progression = database.get_player_progression(name, raid_name, difficulty)
+        .await.map_err(|_| AppError::Database)?;
+
+    Ok(progression)
+}
+
+#[command]
+pub async fn get_player_summary(
+    database: State<'_, Arc<DatabaseManager>>,
+    name: String,
+    raid_name: String,
+    difficulty: Option<String>,
+) -> Result<Vec<DpsSummary>, AppError> {
+    let database = database.get();
+
+    let progression = database.get_player_progression(name, raid_name, difficulty)
+        .await.map_err(|_| AppError::Database)?;
+
+    Ok(statistics::summarize(&progression))
+}
+
+#[command]
+pub async fn compare_encounters(
+    database: State<'_, Arc<DatabaseManager>>,
+    ids: Vec<String>,
+    options: Option<CompareOptions>,
+) -> Result<EncounterComparison, AppError> {
+    let database = database.get();
+
+    let mut encounters = vec![];
+
+    for id in ids {
+        let encounter = database.load_encounter(id.clone())
+            .await.map_err(|_| AppError::Database)?;
+
+        if encounter.fight_start == 0 {
+            return Err(AppError::Comparison(format!("encounter {} not found", id)));
+        }
+
+        encounters.push(encounter);
+    }
+
+    let comparison = comparison::compare_encounters(&encounters, &options.unwrap_or_default())
+        .map_err(|err| AppError::Comparison(err.to_string()))?;
+
+    Ok(comparison)
+}
@@ -17,7 +17,21 @@ pub enum AppError {
     #[error("Could not set start on boot")]
     SetStartOnBoot,
     #[error("Could not send event")]
-    Emit
+    Emit,
+    #[error("Could not compare encounters: {0}")]
+    Comparison(String),
+    #[error("Could not sync encounters: {0}")]
+    Sync(String),
+    #[error("Could not send notification: {0}")]
+    Notification(String),
+    #[error("Could not restore backup: {0}")]
+    Backup(String),
+    #[error("Could not switch profile: {0}")]
+    Profile(String),
+    #[error("Invalid settings: {0}")]
+    Settings(String),
+    #[error("Background worker error: {0}")]
+    Worker(String)
 }
 
 impl serde::Serialize for AppError {
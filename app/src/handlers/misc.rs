@@ -1,14 +1,23 @@
 use crate::handlers::error::AppError;
 use crate::misc::app_context::AppContext;
 use crate::constants::{LOGS_WINDOW_LABEL, METER_MINI_WINDOW_LABEL, METER_WINDOW_LABEL};
-use crate::database::Database;
+use crate::database::DatabaseManager;
 use crate::models::*;
-use crate::misc::settings::{Settings, SettingsManager};
+use crate::misc::crash::{self, CrashReport, CrashReportInfo};
+use crate::misc::profile::{ProfileList, ProfileManager};
+use crate::misc::settings::{RetentionSettings, Settings, SettingsManager};
+use crate::sync::SyncManager;
 use crate::misc::utils::CommandsManager;
+use crate::core::backup::{self, BackupInfo, BackupReason};
+use crate::core::control::{WorkerCommand, WorkerHandle, WorkerStatus};
+use crate::core::diagnostics::{self, DiagnosticsReport};
+use crate::core::{recompression, statistics};
+use crate::core::retention::{self, RetentionReport};
+use crate::core::notifications::{validate_webhook, NotificationContext, Notifier, WebhookSettings};
 use log::{error, info, warn};
 use std::sync::{Arc, Mutex};
 use tauri::{command, ipc, AppHandle, State};
-use tauri::{Emitter, Manager};
+use tauri::{Emitter, EventTarget, Manager};
 
 #[command]
 pub async fn open_db_path(manager: State<'_, CommandsManager>, app_context: State<'_, Arc<AppContext>>) -> Result<(), AppError> {
@@ -21,7 +30,8 @@ pub async fn open_db_path(manager: State<'_, CommandsManager>, app_context: Stat
 }
 
 #[command]
-pub async fn get_db_info(database: State<'_, Database>, min_duration: i64) -> Result<EncounterDbInfo, AppError> {
+pub async fn get_db_info(database: State<'_, Arc<DatabaseManager>>, min_duration: i64) -> Result<EncounterDbInfo, AppError> {
+    let database = database.get();
     
     let (count, filtered_count) = database.get_db_stats(min_duration).await.map_err(|_| AppError::Database)?;
 
@@ -37,13 +47,80 @@ pub async fn get_db_info(database: State<'_, Database>, min_duration: i64) -> Re
 }
 
 #[command]
-pub async fn optimize_database(database: State<'_, Database>) -> Result<(), AppError> {
+pub async fn optimize_database(database: State<'_, Arc<DatabaseManager>>) -> Result<(), AppError> {
+    let database = database.get();
     
     database.optimize().await.map_err(|_| AppError::Database)?;
 
     Ok(())
 }
 
+/// dry run with the given policy or the saved one
+#[command]
+pub async fn preview_retention(
+    database: State<'_, Arc<DatabaseManager>>,
+    manager: State<'_, Mutex<SettingsManager>>,
+    policy: Option<RetentionSettings>,
+) -> Result<RetentionReport, AppError> {
+    let database = database.get();
+    let policy = match policy {
+        Some(policy) => policy,
+        None => manager.lock().unwrap().get().map_err(|_| AppError::FileSystem)?.retention.clone(),
+    };
+
+    let report = database.apply_retention(&policy, true)
+        .await.map_err(|_| AppError::Database)?;
+
+    Ok(report)
+}
+
+#[command]
+pub async fn apply_retention(
+    database: State<'_, Arc<DatabaseManager>>,
+    manager: State<'_, Mutex<SettingsManager>>,
+) -> Result<RetentionReport, AppError> {
+    let database = database.get();
+    let policy = manager.lock().unwrap().get().map_err(|_| AppError::FileSystem)?.retention.clone();
+
+    let report = retention::apply(&database, &policy)
+        .await.map_err(|_| AppError::Database)?;
+
+    Ok(report)
+}
+
+#[command]
+pub async fn list_backups(database: State<'_, Arc<DatabaseManager>>) -> Result<Vec<BackupInfo>, AppError> {
+    let database = database.get();
+    let backups = backup::list(database.backup_path()).map_err(|_| AppError::FileSystem)?;
+
+    Ok(backups)
+}
+
+#[command]
+pub async fn create_backup(
+    database: State<'_, Arc<DatabaseManager>>,
+) -> Result<BackupInfo, AppError> {
+    let database = database.get();
+    let info = backup::create(&database, database.backup_path(), BackupReason::Manual)
+        .map_err(|_| AppError::Database)?;
+
+    Ok(info)
+}
+
+/// returns the backup of the state before the restore
+#[command]
+pub async fn restore_backup(
+    database: State<'_, Arc<DatabaseManager>>,
+    app_context: State<'_, Arc<AppContext>>,
+    name: String,
+) -> Result<BackupInfo, AppError> {
+    let database = database.get();
+    let undo = backup::restore(&database, database.backup_path(), &name, app_context.migration_path.clone())
+        .map_err(|err| AppError::Backup(format!("{:#}", err)))?;
+
+    Ok(undo)
+}
+
 #[command]
 pub async fn remove_driver(manager: State<'_, CommandsManager>) -> Result<(), AppError> {
     manager.remove_driver().await.map_err(|_| AppError::WindiverUnload)?;
@@ -63,6 +140,132 @@ pub async fn unload_driver(manager: State<'_, CommandsManager>) -> Result<(), Ap
     Ok(())
 }
 
+#[command]
+pub async fn get_profiles(profiles: State<'_, Mutex<ProfileManager>>) -> Result<ProfileList, AppError> {
+    Ok(profiles.lock().unwrap().get().clone())
+}
+
+#[command]
+pub async fn create_profile(
+    profiles: State<'_, Mutex<ProfileManager>>,
+    app_context: State<'_, Arc<AppContext>>,
+    name: String,
+) -> Result<ProfileList, AppError> {
+    let mut profiles = profiles.lock().unwrap();
+    profiles.create(&name, &app_context.resource_path)
+        .map_err(|err| AppError::Profile(err.to_string()))?;
+
+    Ok(profiles.get().clone())
+}
+
+/// the meter keeps running, an ongoing fight is saved to the old profile and the next one to the new profile
+#[command]
+pub async fn switch_profile(
+    app_handle: AppHandle,
+    profiles: State<'_, Mutex<ProfileManager>>,
+    database: State<'_, Arc<DatabaseManager>>,
+    settings: State<'_, Mutex<SettingsManager>>,
+    sync_manager: State<'_, Arc<SyncManager>>,
+    app_context: State<'_, Arc<AppContext>>,
+    name: String,
+) -> Result<(), AppError> {
+    let mut profiles = profiles.lock().unwrap();
+
+    if !profiles.exists(&name) {
+        return Err(AppError::Profile(format!("unknown profile {}", name)));
+    }
+
+    if profiles.active() == name {
+        return Ok(());
+    }
+
+    let paths = app_context.profile_paths(&name);
+
+    // a running sync would keep uploading from the old database
+    sync_manager.cancel();
+
+    // read first, settings this version can't use must not leave the database on the new profile
+    let new_settings = SettingsManager::read(&paths.settings_path)
+        .map_err(|err| AppError::Profile(format!("{:#}", err)))?;
+
+    let switched = database.switch(name.clone(), paths.database_path)
+        .map_err(|err| AppError::Profile(format!("{:#}", err)))?;
+
+    // the active profile is what opens on the next launch, keep the database on it
+    if let Err(err) = profiles.set_active(&name) {
+        let previous = profiles.active().to_string();
+        let previous_paths = app_context.profile_paths(&previous);
+
+        if let Err(err) = database.switch(previous, previous_paths.database_path) {
+            error!("could not switch the database back: {:?}", err);
+        }
+
+        return Err(AppError::Profile(err.to_string()));
+    }
+
+    settings.lock().unwrap().load(paths.settings_path, new_settings);
+    info!("switched to profile {}", name);
+
+    if let Some(switched) = switched {
+        tauri::async_runtime::spawn(recompression::run_recompression(switched.clone()));
+        tauri::async_runtime::spawn(statistics::run_ranking_backfill(switched));
+    }
+    app_handle.emit_to(EventTarget::Any, "profile-switched", &name).map_err(|_| AppError::Emit)?;
+
+    Ok(())
+}
+
+#[command]
+pub async fn send_worker_command(worker: State<'_, WorkerHandle>, command: WorkerCommand) -> Result<(), AppError> {
+    worker.send(command).await.map_err(|err| AppError::Worker(err.to_string()))?;
+
+    Ok(())
+}
+
+#[command]
+pub async fn get_worker_status(worker: State<'_, WorkerHandle>) -> Result<WorkerStatus, AppError> {
+    Ok(worker.status())
+}
+
+#[command]
+pub async fn get_diagnostics() -> Result<DiagnosticsReport, AppError> {
+    Ok(diagnostics::report())
+}
+
+#[command]
+pub async fn reset_diagnostics() -> Result<(), AppError> {
+    diagnostics::reset();
+
+    Ok(())
+}
+
+#[command]
+pub async fn list_crash_reports(app_context: State<'_, Arc<AppContext>>) -> Result<Vec<CrashReportInfo>, AppError> {
+    let reports = crash::list(&app_context.crashes_path).map_err(|_| AppError::FileSystem)?;
+
+    Ok(reports)
+}
+
+#[command]
+pub async fn get_crash_report(app_context: State<'_, Arc<AppContext>>, name: String) -> Result<CrashReport, AppError> {
+    let report = crash::load(&app_context.crashes_path, &name).map_err(|_| AppError::FileSystem)?;
+
+    Ok(report)
+}
+
+/// opens the directory holding the report and the packets to replay
+#[command]
+pub async fn open_crash_report(
+    manager: State<'_, Arc<CommandsManager>>,
+    app_context: State<'_, Arc<AppContext>>,
+    name: String,
+) -> Result<(), AppError> {
+    let path = crash::report_path(&app_context.crashes_path, &name).map_err(|_| AppError::FileSystem)?;
+    manager.open_folder(&path.to_string_lossy()).await.map_err(|_| AppError::FileSystem)?;
+
+    Ok(())
+}
+
 #[command]
 pub async fn check_start_on_boot(manager: State<'_, CommandsManager>) -> Result<bool, AppError> {
     let is_set = manager.check_start_on_boot().await.map_err(|_| AppError::SetStartOnBoot)?;
@@ -111,8 +314,36 @@ pub fn open_url(app_handle: AppHandle, url: String) -> Result<(), AppError> {
 }
 
 #[command]
-pub async fn save_settings(manager: State<'_, Mutex<SettingsManager>>, settings: Settings) -> Result<(), AppError> {
-    manager.lock().unwrap().save(settings).map_err(|_| AppError::FileSystem)?;
+pub async fn save_settings(manager: State<'_, Mutex<SettingsManager>>, settings: serde_json::Map<String, serde_json::Value>) -> Result<(), AppError> {
+    manager.lock().unwrap().merge(settings).map_err(|err| AppError::Settings(format!("{:#}", err)))?;
+
+    Ok(())
+}
+
+#[command]
+pub async fn test_webhook(notifier: State<'_, Arc<Notifier>>, webhook: WebhookSettings) -> Result<(), AppError> {
+    validate_webhook(&webhook).map_err(|err| AppError::Notification(err.to_string()))?;
+
+    let context = NotificationContext {
+        boss: "Test Boss".to_string(),
+        raid: "Test Raid".to_string(),
+        difficulty: "Normal".to_string(),
+        duration_seconds: 300,
+        cleared: true,
+        top_player: "Player".to_string(),
+        local_player: "Player".to_string(),
+        rank: 1,
+        ..Default::default()
+    };
+    let test = WebhookSettings {
+        raids: vec![],
+        cleared_only: false,
+        personal_best_only: false,
+        enabled: true,
+        ..webhook
+    };
+
+    notifier.notify(&[test], &context);
 
     Ok(())
 }
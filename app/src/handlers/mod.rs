@@ -21,6 +21,14 @@ pub fn generate_handlers() -> Box<dyn Fn(ipc::Invoke) -> bool + Send + Sync> {
         encounter::delete_all_uncleared_encounters,
         encounter::get_sync_candidates,
         encounter::sync,
+        encounter::start_sync,
+        encounter::cancel_sync,
+        encounter::upload_encounter,
+        encounter::export_encounters,
+        encounter::import_encounters,
+        encounter::get_player_progression,
+        encounter::get_player_summary,
+        encounter::compare_encounters,
         ui::toggle_meter_window,
         ui::toggle_logs_window,
         ui::disable_blur,
@@ -31,10 +39,26 @@ pub fn generate_handlers() -> Box<dyn Fn(ipc::Invoke) -> bool + Send + Sync> {
         misc::open_url,
         misc::save_settings,
         misc::get_settings,
+        misc::test_webhook,
         misc::open_folder,
         misc::open_db_path,
         misc::get_db_info,
         misc::optimize_database,
+        misc::preview_retention,
+        misc::apply_retention,
+        misc::list_backups,
+        misc::create_backup,
+        misc::restore_backup,
+        misc::get_profiles,
+        misc::create_profile,
+        misc::switch_profile,
+        misc::send_worker_command,
+        misc::get_worker_status,
+        misc::get_diagnostics,
+        misc::reset_diagnostics,
+        misc::list_crash_reports,
+        misc::get_crash_report,
+        misc::open_crash_report,
         misc::check_start_on_boot,
         misc::set_start_on_boot,
         misc::check_loa_running,
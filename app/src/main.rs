@@ -15,6 +15,8 @@ mod models;
 mod misc;
 mod database;
 mod constants;
+mod server;
+mod sync;
 use anyhow::Result;
 use log::LevelFilter;
 
@@ -49,6 +51,7 @@ async fn main() -> Result<()> {
         .plugin(tauri_plugin_single_instance::init(|app, args, cwd| {}))
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_process::init())
+        .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_updater::Builder::new().build())
         .plugin(tauri_plugin_window_state::Builder::new().build())
         .plugin(tauri_plugin_global_shortcut::Builder::new().build())
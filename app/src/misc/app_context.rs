@@ -1,15 +1,16 @@
 use std::path::PathBuf;
 
 use crate::constants::*;
+use crate::misc::profile::ProfilePaths;
 
 pub struct AppContext {
     pub version: String,
     pub app_name: String,
     pub resource_path: PathBuf,
     pub region_path: PathBuf,
-    pub settings_path: PathBuf,
-    pub database_path: PathBuf,
-    pub local_players_path: PathBuf,
+    pub profiles_path: PathBuf,
+    pub migration_path: PathBuf,
+    pub crashes_path: PathBuf,
     pub current_exe: String
 }
 
@@ -19,11 +20,15 @@ impl AppContext {
             app_name: app_name,
             resource_path: resource_path.clone(),
             region_path: resource_path.clone().join(REGION_NAME),
-            settings_path: resource_path.clone().join(SETTINGS_NAME),
-            database_path: resource_path.clone().join(DB_NAME),
-            local_players_path: resource_path.clone().join(LOCAL_PLAYERS_NAME),
+            profiles_path: resource_path.clone().join(PROFILES_NAME),
+            migration_path: resource_path.clone().join("assets/migration"),
+            crashes_path: resource_path.clone().join(CRASHES_DIR_NAME),
             current_exe: std::env::current_exe().unwrap().to_string_lossy().to_string(),
             version,
         }
     }
+
+    pub fn profile_paths(&self, profile: &str) -> ProfilePaths {
+        ProfilePaths::new(&self.resource_path, profile)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,262 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::Result;
+use chrono::Utc;
+use meter_core::packets::opcodes::Pkt;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::constants::{CRASH_PACKET_CAPACITY, CRASH_PACKETS_NAME, CRASH_REPORT_NAME};
+use crate::misc::recorder::Recorder;
+use crate::misc::settings::Settings;
+
+/// what the panic hook knows about the app, kept up to date by the background worker
+#[derive(Default)]
+struct CrashContext {
+    dir: Option<PathBuf>,
+    version: String,
+    settings_digest: String,
+    encounter: Option<EncounterSummary>,
+    packets: VecDeque<(Pkt, Vec<u8>)>,
+}
+
+static CONTEXT: Lazy<Mutex<CrashContext>> = Lazy::new(|| Mutex::new(CrashContext::default()));
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncounterSummary {
+    pub current_boss: String,
+    pub local_player: String,
+    pub raid_difficulty: String,
+    pub region: Option<String>,
+    pub started_on: i64,
+    pub entities: usize,
+    pub total_damage_dealt: i64,
+    pub saved: bool,
+    pub paused: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrashReport {
+    pub created_on: i64,
+    pub version: String,
+    pub message: String,
+    pub location: String,
+    pub thread: String,
+    pub backtrace: String,
+    pub settings_digest: String,
+    pub encounter: Option<EncounterSummary>,
+    /// oldest first, the same packets are in the recording next to the report
+    pub packets: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrashReportInfo {
+    pub name: String,
+    pub created_on: i64,
+    pub version: String,
+    pub message: String,
+    pub location: String,
+    pub has_packets: bool,
+}
+
+pub fn init(dir: PathBuf, version: String) {
+    let mut context = CONTEXT.lock().unwrap_or_else(|err| err.into_inner());
+    context.dir = Some(dir);
+    context.version = version;
+}
+
+pub fn set_settings(settings: &Settings) {
+    let digest = settings_digest(settings);
+
+    if let Ok(mut context) = CONTEXT.lock() {
+        context.settings_digest = digest;
+    }
+}
+
+pub fn set_encounter(summary: Option<EncounterSummary>) {
+    if let Ok(mut context) = CONTEXT.lock() {
+        context.encounter = summary;
+    }
+}
+
+pub fn record_packet(op: Pkt, data: &[u8]) {
+    if let Ok(mut context) = CONTEXT.lock() {
+        if context.packets.len() == CRASH_PACKET_CAPACITY {
+            context.packets.pop_front();
+        }
+
+        context.packets.push_back((op, data.to_vec()));
+    }
+}
+
+/// a hash of the settings file, enough to tell whether two reports ran with the same settings
+/// without putting tokens and webhook urls into the report
+pub fn settings_digest(settings: &Settings) -> String {
+    let value = serde_json::to_value(settings).map(canonicalize).unwrap_or_default();
+    let json = serde_json::to_string(&value).unwrap_or_default();
+
+    format!("v{}-{:016x}", settings.version, fnv1a(json.as_bytes()))
+}
+
+/// sorts object keys, maps in the settings don't keep an order between runs
+fn canonicalize(value: Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(String, Value)> = map.into_iter().collect();
+            entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+
+            Value::Object(entries.into_iter().map(|(key, value)| (key, canonicalize(value))).collect::<Map<_, _>>())
+        }
+        Value::Array(values) => Value::Array(values.into_iter().map(canonicalize).collect()),
+        value => value,
+    }
+}
+
+/// unlike `DefaultHasher` the same on every build
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// called from the panic hook, must not panic itself
+pub fn write_report(message: &str, location: &str) -> Option<PathBuf> {
+    // the panicking thread may hold the lock, better no report than a deadlock
+    let context = CONTEXT.try_lock().ok()?;
+    let dir = context.dir.as_ref()?;
+
+    let created_on = Utc::now();
+    let name = created_on.format("%Y%m%d-%H%M%S%.3f").to_string();
+    let report_dir = dir.join(&name);
+    fs::create_dir_all(&report_dir).ok()?;
+
+    let report = CrashReport {
+        created_on: created_on.timestamp_millis(),
+        version: context.version.clone(),
+        message: message.to_string(),
+        location: location.to_string(),
+        thread: std::thread::current().name().unwrap_or("unnamed").to_string(),
+        backtrace: std::backtrace::Backtrace::force_capture().to_string(),
+        settings_digest: context.settings_digest.clone(),
+        encounter: context.encounter.clone(),
+        packets: context.packets.iter().map(|(op, data)| format!("{:?} ({} bytes)", op, data.len())).collect(),
+    };
+
+    let json = serde_json::to_vec_pretty(&report).ok()?;
+    fs::write(report_dir.join(CRASH_REPORT_NAME), json).ok()?;
+
+    if !context.packets.is_empty() {
+        let mut recorder = Recorder::new(report_dir.join(CRASH_PACKETS_NAME)).ok()?;
+
+        for (op, data) in context.packets.iter() {
+            recorder.write(*op, data).ok()?;
+        }
+    }
+
+    Some(report_dir)
+}
+
+pub fn list(dir: &Path) -> Result<Vec<CrashReportInfo>> {
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut reports: Vec<CrashReportInfo> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let path = entry.path();
+            let report: CrashReport = serde_json::from_slice(&fs::read(path.join(CRASH_REPORT_NAME)).ok()?).ok()?;
+
+            Some(CrashReportInfo {
+                name: entry.file_name().to_string_lossy().to_string(),
+                created_on: report.created_on,
+                version: report.version,
+                message: report.message,
+                location: report.location,
+                has_packets: path.join(CRASH_PACKETS_NAME).exists(),
+            })
+        })
+        .collect();
+
+    reports.sort_unstable_by_key(|report| std::cmp::Reverse(report.created_on));
+
+    Ok(reports)
+}
+
+pub fn load(dir: &Path, name: &str) -> Result<CrashReport> {
+    let path = report_path(dir, name)?.join(CRASH_REPORT_NAME);
+    let report = serde_json::from_slice(&fs::read(path)?)?;
+
+    Ok(report)
+}
+
+/// rejects anything that isn't a report directory, the name comes from the ui
+pub fn report_path(dir: &Path, name: &str) -> Result<PathBuf> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.contains("..") {
+        anyhow::bail!("invalid crash report name {}", name);
+    }
+
+    let path = dir.join(name);
+
+    if !path.join(CRASH_REPORT_NAME).exists() {
+        anyhow::bail!("unknown crash report {}", name);
+    }
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_write_report_with_recent_packets() {
+        let dir = std::env::temp_dir().join(format!("drama-meter-crashes-{}", uuid::Uuid::new_v4()));
+        init(dir.clone(), "1.0.0".to_string());
+
+        for index in 0..CRASH_PACKET_CAPACITY + 10 {
+            record_packet(Pkt::NewPC, &(index as u32).to_le_bytes());
+        }
+
+        let path = write_report("test panic", "src/main.rs:1").unwrap();
+        let reports = list(&dir).unwrap();
+        assert_eq!(reports.len(), 1);
+        assert!(reports[0].has_packets);
+
+        let report = load(&dir, &reports[0].name).unwrap();
+        assert_eq!(report.message, "test panic");
+        assert_eq!(report.packets.len(), CRASH_PACKET_CAPACITY);
+
+        // the oldest packets were dropped
+        let mut recorder = Recorder::new(path.join(CRASH_PACKETS_NAME)).unwrap();
+        let (op, data) = recorder.read().unwrap().unwrap();
+        assert_eq!(op, Pkt::NewPC);
+        assert_eq!(data, 10u32.to_le_bytes());
+
+        assert!(load(&dir, "../settings").is_err());
+    }
+
+    #[test]
+    fn should_digest_equal_settings_equally() {
+        let mut settings = Settings::default();
+        let mut other = Settings::default();
+
+        for (region, endpoint) in [("NAE", "https://nae"), ("EUC", "https://euc"), ("SA", "https://sa")] {
+            settings.stats_api.region_endpoints.insert(region.to_string(), endpoint.to_string());
+        }
+
+        for (region, endpoint) in [("SA", "https://sa"), ("EUC", "https://euc"), ("NAE", "https://nae")] {
+            other.stats_api.region_endpoints.insert(region.to_string(), endpoint.to_string());
+        }
+
+        assert_eq!(settings_digest(&settings), settings_digest(&other));
+
+        other.general.port += 1;
+        assert_ne!(settings_digest(&settings), settings_digest(&other));
+    }
+}
@@ -1,11 +1,11 @@
 use std::sync::Arc;
 
 use anyhow::*;
-use log::debug;
-use tauri::{Manager, Window, WindowEvent};
+use log::{debug, info, warn};
+use tauri::{AppHandle, Manager, Runtime, Window, WindowEvent};
 use tauri_plugin_window_state::AppHandleExt;
 
-use crate::{constants::{LOGS_WINDOW_LABEL, METER_MINI_WINDOW_LABEL, METER_WINDOW_LABEL, WINDOW_STATE_FLAGS}, misc::utils::CommandsManager};
+use crate::{constants::{LOGS_WINDOW_LABEL, METER_MINI_WINDOW_LABEL, METER_WINDOW_LABEL, WINDOW_STATE_FLAGS}, core::control::{WorkerCommand, WorkerHandle}, misc::utils::CommandsManager};
 
 pub fn on_window_event(window: &Window, event: &WindowEvent) {
     let label = window.label();
@@ -34,7 +34,6 @@ pub fn on_window_event(window: &Window, event: &WindowEvent) {
 
 fn on_window_close(window: &Window) -> Result<()> {
     let app_handle = window.app_handle();
-    let commands_manager = app_handle.state::<Arc<CommandsManager>>();
     let meter_window = app_handle.get_webview_window(METER_WINDOW_LABEL).ok_or_else(|| anyhow!("Could not find window"))?;
     let logs_window = app_handle.get_webview_window(LOGS_WINDOW_LABEL).ok_or_else(|| anyhow!("Could not find window"))?;
 
@@ -46,13 +45,32 @@ fn on_window_close(window: &Window) -> Result<()> {
         meter_window.unminimize()?;
     }
 
-    app_handle
-        .save_window_state(WINDOW_STATE_FLAGS)
-        .expect("failed to save window state");
-    
-    commands_manager.unload_driver();
-
-    app_handle.exit(0);
+    shutdown(app_handle);
 
     Ok(())
+}
+
+/// lets the background worker save the ongoing encounter before the process exits
+pub fn shutdown<R: Runtime>(app_handle: &AppHandle<R>) {
+    if let Err(err) = app_handle.save_window_state(WINDOW_STATE_FLAGS) {
+        warn!("failed to save window state: {}", err);
+    }
+
+    let app_handle = app_handle.clone();
+
+    tauri::async_runtime::spawn(async move {
+        let worker = app_handle.state::<WorkerHandle>().inner().clone();
+
+        match worker.send(WorkerCommand::Shutdown).await {
+            Ok(_) => info!("background worker stopped"),
+            Err(err) => warn!("could not stop background worker: {}", err),
+        }
+
+        let commands_manager = app_handle.state::<Arc<CommandsManager>>().inner().clone();
+        if let Err(err) = commands_manager.unload_driver().await {
+            warn!("could not unload driver: {}", err);
+        }
+
+        app_handle.exit(0);
+    });
 }
\ No newline at end of file
@@ -2,6 +2,8 @@ use std::thread;
 
 use log::error;
 
+use crate::misc::crash;
+
 pub fn setup_hook() {
     std::panic::set_hook(Box::new(|info| {
         let payload = if let Some(s) = info.payload().downcast_ref::<&str>() {
@@ -17,6 +19,11 @@ pub fn setup_hook() {
             .unwrap_or_else(|| "Unknown location".to_string());
 
         error!("Panicked at '{}', {}", payload, location);
+
+        if let Some(path) = crash::write_report(payload, &location) {
+            error!("crash report written to {}", path.display());
+        }
+
         log::logger().flush();
     }));
 }
\ No newline at end of file
@@ -1,4 +1,5 @@
 pub mod hook;
+pub mod crash;
 pub mod system_tray;
 pub mod events;
 pub mod settings;
@@ -8,5 +9,5 @@ pub mod updater;
 pub mod local;
 pub mod region;
 pub mod app_context;
+pub mod profile;
 pub mod recorder;
-pub mod flags;
\ No newline at end of file
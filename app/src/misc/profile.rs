@@ -0,0 +1,158 @@
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use anyhow::*;
+use serde::{Deserialize, Serialize};
+
+use crate::constants::*;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+    pub name: String,
+    pub created_on: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ProfileList {
+    pub active: String,
+    pub profiles: Vec<Profile>,
+}
+
+impl Default for ProfileList {
+    fn default() -> Self {
+        Self {
+            active: DEFAULT_PROFILE.to_string(),
+            profiles: vec![Profile {
+                name: DEFAULT_PROFILE.to_string(),
+                created_on: 0,
+            }],
+        }
+    }
+}
+
+/// everything that belongs to one profile, the region file is shared since it follows the game client
+#[derive(Debug, Clone)]
+pub struct ProfilePaths {
+    pub database_path: PathBuf,
+    pub settings_path: PathBuf,
+    pub local_players_path: PathBuf,
+    pub checkpoint_path: PathBuf,
+}
+
+impl ProfilePaths {
+    /// the default profile keeps the files in the resource dir so existing installs don't move
+    pub fn new(resource_path: &Path, profile: &str) -> Self {
+        let dir = if profile == DEFAULT_PROFILE {
+            resource_path.to_path_buf()
+        } else {
+            resource_path.join(PROFILES_DIR_NAME).join(profile)
+        };
+
+        Self {
+            database_path: dir.join(DB_NAME),
+            settings_path: dir.join(SETTINGS_NAME),
+            local_players_path: dir.join(LOCAL_PLAYERS_NAME),
+            checkpoint_path: dir.join(CHECKPOINT_DIR_NAME).join(DB_NAME),
+        }
+    }
+}
+
+pub struct ProfileManager(PathBuf, ProfileList);
+
+impl ProfileManager {
+    pub fn new(path: PathBuf) -> Result<Self> {
+        let list = if path.exists() {
+            let reader = File::open(&path)?;
+            serde_json::from_reader(reader)?
+        } else {
+            ProfileList::default()
+        };
+
+        Ok(Self(path, list))
+    }
+
+    pub fn get(&self) -> &ProfileList {
+        &self.1
+    }
+
+    pub fn active(&self) -> &str {
+        &self.1.active
+    }
+
+    pub fn exists(&self, name: &str) -> bool {
+        self.1.profiles.iter().any(|profile| profile.name == name)
+    }
+
+    /// names end up in paths, so only a conservative set of characters is allowed
+    pub fn create(&mut self, name: &str, resource_path: &Path) -> Result<()> {
+        let valid = !name.is_empty()
+            && name.len() <= 32
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+        if !valid {
+            bail!("profile names may only contain letters, digits, - and _");
+        }
+
+        if self.exists(name) {
+            bail!("profile {} already exists", name);
+        }
+
+        fs::create_dir_all(resource_path.join(PROFILES_DIR_NAME).join(name))?;
+
+        self.1.profiles.push(Profile {
+            name: name.to_string(),
+            created_on: chrono::Utc::now().timestamp_millis(),
+        });
+
+        self.save()
+    }
+
+    pub fn set_active(&mut self, name: &str) -> Result<()> {
+        if !self.exists(name) {
+            bail!("unknown profile {}", name);
+        }
+
+        let previous = std::mem::replace(&mut self.1.active, name.to_string());
+
+        // the profile stays active in memory only if it's saved
+        self.save().inspect_err(|_| self.1.active = previous)
+    }
+
+    fn save(&self) -> Result<()> {
+        let writer = File::create(&self.0)?;
+        serde_json::to_writer_pretty(writer, &self.1)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_create_and_activate_profiles() {
+        let dir = std::env::temp_dir().join(format!("drama-meter-profiles-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(PROFILES_NAME);
+
+        let mut manager = ProfileManager::new(path.clone()).unwrap();
+        assert_eq!(manager.active(), DEFAULT_PROFILE);
+
+        assert!(manager.create("../evil", &dir).is_err());
+        manager.create("nae-alt", &dir).unwrap();
+        assert!(manager.create("nae-alt", &dir).is_err());
+        manager.set_active("nae-alt").unwrap();
+
+        let manager = ProfileManager::new(path).unwrap();
+        assert_eq!(manager.active(), "nae-alt");
+        assert_eq!(
+            ProfilePaths::new(&dir, "nae-alt").database_path,
+            dir.join(PROFILES_DIR_NAME).join("nae-alt").join(DB_NAME));
+        assert_eq!(ProfilePaths::new(&dir, DEFAULT_PROFILE).database_path, dir.join(DB_NAME));
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
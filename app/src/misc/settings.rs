@@ -1,75 +1,425 @@
 use anyhow::*;
-use std::fs::File;
-use std::path::PathBuf;
+use hashbrown::HashMap;
+use log::{info, warn};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
+use tokio::sync::watch;
 
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 
-pub struct SettingsManager(PathBuf, Settings);
+use crate::constants::{API_PORT, PORT, SETTINGS_VERSION};
+use crate::core::notifications::{validate_webhook, WebhookSettings};
+use crate::core::stats_api::API_URL;
+
+/// the receivers of `subscribe` see every saved or loaded settings
+pub struct SettingsManager(PathBuf, Settings, watch::Sender<Settings>);
 
 impl SettingsManager {
     pub fn new(path: PathBuf) -> Result<Mutex<Self>> {
+        let settings = Self::read(&path)?;
+        let (sender, _) = watch::channel(settings.clone());
+
+        Ok(Mutex::new(Self(path, settings, sender)))
+    }
 
-        let settings = if path.exists() {
-            let reader = File::open(&path)?;
-            serde_json::from_reader(reader)?
-        } else {
+    /// switches to the settings file of another profile, `settings` were read from it with `read`
+    pub fn load(&mut self, path: PathBuf, settings: Settings) {
+        self.1 = settings;
+        self.0 = path;
+        self.2.send_replace(self.1.clone());
+    }
+
+    /// migrates older files in place, a file from a newer version is rejected rather than overwritten
+    pub fn read(path: &PathBuf) -> Result<Settings> {
+        if !path.exists() {
             let settings = Settings::default();
-            let writer = File::create(&path)?;
-            serde_json::to_writer_pretty(writer, &settings)?;
-            settings
-        };
+            write_atomic(path, &settings)?;
+            return Ok(settings);
+        }
+
+        let reader = File::open(path)?;
+        let mut raw: Map<String, Value> = serde_json::from_reader(reader)?;
+        let migrated = migrate(&mut raw)?;
+        let settings: Settings = serde_json::from_value(Value::Object(raw))?;
 
-        Ok(Mutex::new(Self(path, settings)))
+        if let Err(err) = settings.validate() {
+            warn!("invalid settings in {}: {}", path.display(), err);
+        }
+
+        if migrated {
+            info!("migrated settings to version {}", SETTINGS_VERSION);
+            write_atomic(path, &settings)?;
+        }
+
+        Ok(settings)
     }
 
     pub fn get(&self) -> Result<&Settings> {
         Ok(&self.1)
     }
 
+    pub fn subscribe(&self) -> watch::Receiver<Settings> {
+        self.2.subscribe()
+    }
+
+    /// fields missing from `partial` keep their current value, the ui only knows about some of them
+    pub fn merge(&mut self, partial: Map<String, Value>) -> Result<()> {
+        let mut settings = match serde_json::to_value(&self.1)? {
+            Value::Object(map) => map,
+            _ => Map::new(),
+        };
+        merge_objects(&mut settings, partial);
+        settings.insert("version".to_string(), SETTINGS_VERSION.into());
+
+        self.save(serde_json::from_value(Value::Object(settings))?)
+    }
+
+    /// nothing is written if validation fails
     pub fn save(&mut self, settings: Settings) -> Result<()> {
-        let writer = File::create(&self.0)?;
-        serde_json::to_writer_pretty(writer, &settings)?;
+        settings.validate()?;
+        write_atomic(&self.0, &settings)?;
+        self.1 = settings;
+        self.2.send_replace(self.1.clone());
 
         Ok(())
     }
 }
 
+/// objects are merged key by key at every level, anything else is replaced
+fn merge_objects(target: &mut Map<String, Value>, partial: Map<String, Value>) {
+    for (key, value) in partial {
+        match (target.get_mut(&key), value) {
+            (Some(Value::Object(target)), Value::Object(partial)) => merge_objects(target, partial),
+            (_, value) => {
+                target.insert(key, value);
+            }
+        }
+    }
+}
+
+/// a crash while saving leaves either the old or the new file, never a truncated one
+fn write_atomic(path: &Path, settings: &Settings) -> Result<()> {
+    let temp_path = path.with_extension("json.tmp");
+
+    let mut writer = File::create(&temp_path)?;
+    serde_json::to_writer_pretty(&mut writer, settings)?;
+    writer.sync_all()?;
+    fs::rename(&temp_path, path)?;
+
+    Ok(())
+}
+
+/// `MIGRATIONS[n]` turns version n into n + 1, files without a version are version 0
+const MIGRATIONS: &[fn(&mut Map<String, Value>)] = &[
+    migrate_zero_ports,
+];
+
+/// 0 used to mean the default port, it's rejected by validation now
+fn migrate_zero_ports(raw: &mut Map<String, Value>) {
+    let Some(Value::Object(general)) = raw.get_mut("general") else {
+        return;
+    };
+
+    for (key, default) in [("port", PORT), ("apiPort", API_PORT)] {
+        if general.get(key).and_then(Value::as_u64) == Some(0) {
+            general.insert(key.to_string(), default.into());
+        }
+    }
+}
+
+/// returns whether anything changed
+fn migrate(raw: &mut Map<String, Value>) -> Result<bool> {
+    let version = raw.get("version").and_then(Value::as_u64).unwrap_or(0) as u32;
+
+    if version > SETTINGS_VERSION {
+        bail!("settings version {} is newer than the supported version {}", version, SETTINGS_VERSION);
+    }
+
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(raw);
+    }
+
+    raw.insert("version".to_string(), SETTINGS_VERSION.into());
+
+    Ok(version < SETTINGS_VERSION)
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("{}", .0.join("; "))]
+pub struct InvalidSettings(pub Vec<String>);
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase", default)]
 pub struct Settings {
+    pub version: u32,
     pub general: GeneralSettings,
     pub dev: DevSettings,
+    pub stats_api: StatsApiSettings,
+    pub sync: SyncSettings,
+    pub notifications: NotificationSettings,
+    pub retention: RetentionSettings,
+    pub backup: BackupSettings,
     #[serde(flatten)]
     pub extra: Map<String, Value>,
 }
 
+impl Settings {
+    /// collects every problem so the ui can show them at once
+    pub fn validate(&self) -> Result<()> {
+        let mut errors = vec![];
+
+        if self.general.port == 0 {
+            errors.push("general.port must not be 0".to_string());
+        }
+
+        if self.general.api_port == 0 {
+            errors.push("general.apiPort must not be 0".to_string());
+        }
+
+        if self.general.api_port == self.general.port {
+            errors.push("general.apiPort must differ from general.port".to_string());
+        }
+
+        if self.stats_api.endpoint.is_empty() {
+            errors.push("statsApi.endpoint must not be empty".to_string());
+        }
+
+        if self.sync.target == SyncTargetKind::Http && self.sync.endpoint.is_empty() {
+            errors.push("sync.endpoint must not be empty".to_string());
+        }
+
+        if self.retention.interval_hours == 0 {
+            errors.push("retention.intervalHours must be at least 1".to_string());
+        }
+
+        if self.backup.interval_hours == 0 {
+            errors.push("backup.intervalHours must be at least 1".to_string());
+        }
+
+        if self.backup.keep == 0 {
+            errors.push("backup.keep must be at least 1".to_string());
+        }
+
+        for (index, webhook) in self.notifications.webhooks.iter().enumerate() {
+            if let Err(err) = validate_webhook(webhook) {
+                errors.push(format!("notifications.webhooks[{}]: {}", index, err));
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(InvalidSettings(errors).into());
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase", default)]
 pub struct DevSettings {
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct StatsApiSettings {
+    /// only use previously cached player stats
+    pub offline: bool,
+    pub endpoint: String,
+    pub region_endpoints: HashMap<String, String>,
+    pub cache_ttl_hours: u32,
+}
+
+impl Default for StatsApiSettings {
+    fn default() -> Self {
+        Self {
+            offline: false,
+            endpoint: API_URL.to_string(),
+            region_endpoints: HashMap::new(),
+            cache_ttl_hours: 24 * 7,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RetentionSettings {
+    /// whether the background job applies the rules, previews work regardless
+    pub enabled: bool,
+    pub max_age_days: Option<u32>,
+    pub max_per_boss: Option<u32>,
+    pub max_size_mb: Option<u64>,
+    pub keep_favorites: bool,
+    pub keep_cleared: bool,
+    pub keep_personal_bests: bool,
+    pub interval_hours: u32,
+}
+
+impl Default for RetentionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_age_days: None,
+            max_per_boss: None,
+            max_size_mb: None,
+            keep_favorites: true,
+            keep_cleared: false,
+            keep_personal_bests: true,
+            interval_hours: 24,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BackupSettings {
+    pub enabled: bool,
+    pub interval_hours: u32,
+    /// number of backups kept, older ones are removed after each scheduled backup
+    pub keep: usize,
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_hours: 24,
+            keep: 5,
+        }
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase", default)]
+pub struct NotificationSettings {
+    pub webhooks: Vec<WebhookSettings>,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SyncTargetKind {
+    #[default]
+    Http,
+    Filesystem,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SyncSettings {
+    pub target: SyncTargetKind,
+    pub endpoint: String,
+    /// used by the filesystem target
+    pub directory: String,
+    /// minimum delay between two uploads
+    pub min_interval_ms: u64,
+    pub max_retries: u32,
+}
+
+impl Default for SyncSettings {
+    fn default() -> Self {
+        Self {
+            target: SyncTargetKind::Http,
+            endpoint: API_URL.to_string(),
+            directory: String::new(),
+            min_interval_ms: 1000,
+            max_retries: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
 pub struct GeneralSettings {
     pub start_loa_on_start: bool,
     pub low_performance_mode: bool,
-    #[serde(default = "default_true")]
     pub auto_iface: bool,
     pub port: u16,
-    #[serde(default = "default_true")]
     pub always_on_top: bool,
-    #[serde(default = "default_true")]
     pub boss_only_damage: bool,
-    #[serde(default = "default_true")]
     pub hide_meter_on_start: bool,
     pub hide_logs_on_start: bool,
     pub mini: bool,
+    pub live_api_enabled: bool,
+    pub rest_api_enabled: bool,
+    pub api_port: u16,
+    pub api_token: String,
     #[serde(flatten)]
     pub extra: Map<String, Value>,
 }
 
-fn default_true() -> bool {
-    true
-}
\ No newline at end of file
+impl Default for GeneralSettings {
+    fn default() -> Self {
+        Self {
+            start_loa_on_start: false,
+            low_performance_mode: false,
+            auto_iface: true,
+            port: PORT,
+            always_on_top: true,
+            boss_only_damage: true,
+            hide_meter_on_start: true,
+            hide_logs_on_start: false,
+            mini: false,
+            live_api_enabled: false,
+            rest_api_enabled: false,
+            api_port: API_PORT,
+            api_token: String::new(),
+            extra: Map::new(),
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn create_path() -> PathBuf {
+        std::env::temp_dir().join(format!("drama-meter-settings-{}.json", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn should_migrate_and_validate() {
+        let path = create_path();
+        let legacy = json!({ "general": { "port": 0, "lowPerformanceMode": true, "showNames": false } });
+        fs::write(&path, legacy.to_string()).unwrap();
+
+        let manager = SettingsManager::new(path.clone()).unwrap();
+        let mut manager = manager.lock().unwrap();
+        let settings = manager.get().unwrap().clone();
+        assert_eq!(settings.version, SETTINGS_VERSION);
+        assert_eq!(settings.general.port, PORT);
+        assert!(settings.general.low_performance_mode);
+        assert_eq!(settings.general.extra["showNames"], json!(false));
+
+        let mut receiver = manager.subscribe();
+        let partial = json!({ "backup": { "keep": 0 }, "general": { "port": 0 } });
+        let err = manager.merge(partial.as_object().cloned().unwrap()).unwrap_err();
+        assert_eq!(err.downcast_ref::<InvalidSettings>().unwrap().0.len(), 2);
+        assert!(!receiver.has_changed().unwrap());
+
+        // the rejected save must not reach the file either
+        let saved: Settings = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(saved, settings);
+
+        let partial = json!({ "general": { "port": 6050 } });
+        manager.merge(partial.as_object().cloned().unwrap()).unwrap();
+        let merged = receiver.borrow_and_update().clone();
+        assert_eq!(merged.general.port, 6050);
+        // the rest of the section is left alone
+        assert!(merged.general.low_performance_mode);
+        assert_eq!(merged.general.extra["showNames"], json!(false));
+
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn should_reject_newer_version() {
+        let path = create_path();
+        fs::write(&path, json!({ "version": SETTINGS_VERSION + 1 }).to_string()).unwrap();
+
+        assert!(SettingsManager::new(path.clone()).is_err());
+
+        fs::remove_file(&path).ok();
+    }
+}
@@ -1,11 +1,9 @@
-use std::sync::Arc;
-
 use anyhow::*;
 use log::error;
-use tauri::{async_runtime, menu::*, tray::{TrayIcon, TrayIconBuilder, TrayIconEvent}, App, LogicalPosition, LogicalSize, Manager, Position, Runtime, Size, Wry};
+use tauri::{menu::*, tray::{TrayIcon, TrayIconBuilder, TrayIconEvent}, App, LogicalPosition, LogicalSize, Manager, Position, Runtime, Size, Wry};
 use tauri_plugin_window_state::{AppHandleExt, WindowExt};
 
-use crate::{constants::*, misc::settings::SettingsManager, misc::utils::CommandsManager};
+use crate::{constants::*, misc::events};
 
 
 pub fn build(app: &App) -> Result<()> {
@@ -51,19 +49,9 @@ pub fn on_menu_event<R: Runtime>(app: &tauri::AppHandle<R>, event: MenuEvent) {
 
 pub fn on_menu_event_inner<R: Runtime>(app: &tauri::AppHandle<R>, event: MenuEvent) -> Result<()> {
     let menu_item_id = event.id().0.as_str();
-    let settings = app.state::<SettingsManager>();
-    let commands = app.state::<Arc<CommandsManager>>();
 
     match menu_item_id {
-        "quit" => {
-            app.save_window_state(WINDOW_STATE_FLAGS)?;
-            let commands = commands.clone();
-            async_runtime::block_on(async {
-                commands.unload_driver().await;
-            });
-            
-            app.exit(0);
-        }
+        "quit" => events::shutdown(app),
         "hide" => {
             let meter_window = app.get_webview_window(METER_WINDOW_LABEL).ok_or_else(|| anyhow!("Could not find window"))?;
             meter_window.hide()?;
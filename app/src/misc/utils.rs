@@ -136,9 +136,9 @@ where
     let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
     let bytes = serde_json::to_vec(value)?;
     encoder.write_all(&bytes)?;
-    encoder.finish()?;
+    let compressed = encoder.finish()?;
 
-    Ok(bytes)
+    Ok(compressed)
 }
 
 // #[command]
@@ -222,6 +222,9 @@ pub struct Encounter {
     pub boss_only_damage: bool,
     pub sync: Option<String>,
     pub region: Option<String>,
+    pub personal_best: bool,
+    pub personal_percentile: Option<f64>,
+    pub local_percentile: Option<f64>,
 }
 
 #[derive(Debug, Serialize, Clone, Default)]
@@ -590,6 +593,12 @@ pub struct EncounterMisc {
     pub ntp_fight_start: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub manual_save: Option<bool>,
+    /// restored from a checkpoint after the app stopped mid fight
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recovered: Option<bool>,
+    /// why some damage packets of the fight were dropped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub damage_invalid_reason: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
@@ -811,6 +820,26 @@ pub struct EncounterPreview {
     pub my_dps: i64,
     pub favorite: bool,
     pub cleared: bool,
+    pub personal_best: bool,
+    pub personal_percentile: Option<f64>,
+    pub local_percentile: Option<f64>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerProgression {
+    pub encounter_id: i64,
+    pub fight_start: i64,
+    pub difficulty: String,
+    pub spec: Option<String>,
+    pub gear_score: f32,
+    pub cleared: bool,
+    pub duration: i64,
+    pub total_damage: i64,
+    pub dps: i64,
+    pub brand_uptime: f64,
+    pub attack_power_uptime: f64,
+    pub identity_uptime: f64,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
@@ -832,6 +861,16 @@ pub struct SearchFilter {
     pub boss_only_damage: bool,
     pub sort: String,
     pub order: String,
+    /// matched against a single player of the encounter
+    pub classes: Vec<String>,
+    pub player_name: String,
+    pub spec: String,
+    pub min_gear_score: Option<f64>,
+    pub local_player: String,
+    pub region: String,
+    /// fight start range in unix ms, inclusive
+    pub from: Option<i64>,
+    pub to: Option<i64>,
 }
 
 
@@ -941,6 +980,24 @@ pub enum RaidDifficulty {
     Trial = 6
 }
 
+/// `raid_result` of the RaidResult packet
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RaidOutcome {
+    Clear,
+    Wipe,
+    Unknown(u8),
+}
+
+impl From<u8> for RaidOutcome {
+    fn from(value: u8) -> Self {
+        match value {
+            0 => RaidOutcome::Clear,
+            1 => RaidOutcome::Wipe,
+            value => RaidOutcome::Unknown(value),
+        }
+    }
+}
+
 #[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
 pub enum StatusEffectTargetType {
     #[default]
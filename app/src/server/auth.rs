@@ -0,0 +1,35 @@
+use std::sync::Arc;
+
+use axum::extract::{Request, State};
+use axum::http::{header::AUTHORIZATION, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+use crate::server::ServerState;
+
+/// accepts `Authorization: Bearer <token>` or `?token=<token>` as browser sources can't set headers
+pub async fn require_token(State(state): State<Arc<ServerState>>, request: Request, next: Next) -> Response {
+    let header = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    let query = request
+        .uri()
+        .query()
+        .and_then(|query| query.split('&').find_map(|pair| pair.strip_prefix("token=")));
+
+    let token = header.or(query).unwrap_or_default();
+
+    if state.token.is_empty() || !constant_time_eq(token.as_bytes(), state.token.as_bytes()) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    next.run(request).await
+}
+
+/// takes as long for a near miss as for a wrong first byte, only the length can leak
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
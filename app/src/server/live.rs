@@ -0,0 +1,98 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use log::*;
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, Listener};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::server::ServerState;
+
+pub const LIVE_EVENTS: [&str; 4] = ["encounter-update", "zone-change", "phase-transition", "raid-start"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LiveEvent {
+    pub event: &'static str,
+    pub payload: Value,
+}
+
+pub fn router() -> Router<Arc<ServerState>> {
+    Router::new()
+        .route("/live", get(subscribe))
+        .route("/live/snapshot", get(snapshot))
+}
+
+pub fn forward_events(app_handle: &AppHandle, state: Arc<ServerState>) {
+    for event in LIVE_EVENTS {
+        let state = state.clone();
+
+        app_handle.listen_any(event, move |message| {
+            let payload = serde_json::from_str(message.payload()).unwrap_or(Value::Null);
+
+            if event == "encounter-update" {
+                *state.snapshot.write().unwrap() = Some(payload.clone());
+            }
+
+            // fails only when nobody is connected
+            state.events.send(LiveEvent { event, payload }).ok();
+        });
+    }
+}
+
+async fn snapshot(State(state): State<Arc<ServerState>>) -> Response {
+    let snapshot = state.snapshot.read().unwrap().clone();
+
+    match snapshot {
+        Some(snapshot) => Json(snapshot).into_response(),
+        None => StatusCode::NO_CONTENT.into_response(),
+    }
+}
+
+async fn subscribe(ws: WebSocketUpgrade, State(state): State<Arc<ServerState>>) -> Response {
+    ws.on_upgrade(move |socket| stream(socket, state))
+}
+
+async fn stream(mut socket: WebSocket, state: Arc<ServerState>) {
+    let mut receiver = state.events.subscribe();
+    let snapshot = state.snapshot.read().unwrap().clone();
+
+    if let Some(payload) = snapshot {
+        let event = LiveEvent { event: "encounter-update", payload };
+
+        if send(&mut socket, &event).await.is_err() {
+            return;
+        }
+    }
+
+    loop {
+        tokio::select! {
+            event = receiver.recv() => match event {
+                Ok(event) => {
+                    if send(&mut socket, &event).await.is_err() {
+                        break;
+                    }
+                },
+                Err(RecvError::Lagged(skipped)) => debug!("live client skipped {} events", skipped),
+                Err(RecvError::Closed) => break,
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                _ => {},
+            },
+        }
+    }
+}
+
+async fn send(socket: &mut WebSocket, event: &LiveEvent) -> Result<()> {
+    let text = serde_json::to_string(event)?;
+    socket.send(Message::Text(text.into())).await?;
+
+    Ok(())
+}
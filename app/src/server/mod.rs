@@ -0,0 +1,83 @@
+mod auth;
+mod live;
+mod rest;
+
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::{Arc, RwLock};
+
+use anyhow::Result;
+use axum::{middleware, Router};
+use log::*;
+use tauri::AppHandle;
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+
+use crate::constants::{API_PORT, LIVE_EVENT_CAPACITY};
+use crate::database::DatabaseManager;
+use crate::misc::settings::GeneralSettings;
+
+pub use live::LiveEvent;
+
+pub struct ServerState {
+    pub token: String,
+    pub events: broadcast::Sender<LiveEvent>,
+    pub snapshot: RwLock<Option<serde_json::Value>>,
+    pub database: Arc<DatabaseManager>,
+}
+
+impl ServerState {
+    pub fn new(token: String, database: Arc<DatabaseManager>) -> Self {
+        let (events, _) = broadcast::channel(LIVE_EVENT_CAPACITY);
+
+        Self {
+            token,
+            events,
+            snapshot: RwLock::new(None),
+            database,
+        }
+    }
+}
+
+pub fn start(app_handle: AppHandle, settings: &GeneralSettings, database: Arc<DatabaseManager>) {
+    let port = if settings.api_port == 0 { API_PORT } else { settings.api_port };
+    let state = Arc::new(ServerState::new(settings.api_token.clone(), database));
+
+    if settings.live_api_enabled {
+        live::forward_events(&app_handle, state.clone());
+    }
+
+    let router = router(state, settings.live_api_enabled, settings.rest_api_enabled);
+
+    tauri::async_runtime::spawn(async move {
+        if let Err(err) = serve(router, port).await {
+            error!("api server stopped: {:?}", err);
+        }
+    });
+}
+
+pub fn router(state: Arc<ServerState>, live: bool, rest: bool) -> Router {
+    let mut router = Router::new();
+
+    if live {
+        router = router.merge(live::router());
+    }
+
+    if rest {
+        router = router.merge(rest::router());
+    }
+
+    router
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth::require_token))
+        .with_state(state)
+}
+
+async fn serve(router: Router, port: u16) -> Result<()> {
+    let address = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let listener = TcpListener::bind(address).await?;
+
+    info!("api server listening on {}", address);
+
+    axum::serve(listener, router).await?;
+
+    Ok(())
+}
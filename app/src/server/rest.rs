@@ -0,0 +1,254 @@
+use std::sync::Arc;
+
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use log::*;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::database::{SortColumn, SortOrder};
+use crate::models::*;
+use crate::server::ServerState;
+
+const MAX_PAGE_SIZE: i32 = 100;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PreviewQuery {
+    pub page: i32,
+    pub page_size: i32,
+    pub search: String,
+    pub filter: SearchFilter,
+}
+
+impl Default for PreviewQuery {
+    fn default() -> Self {
+        Self {
+            page: 1,
+            page_size: 10,
+            search: String::new(),
+            filter: SearchFilter::default(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PageQuery {
+    pub page: i32,
+    pub page_size: i32,
+    pub min_duration: i64,
+}
+
+impl Default for PageQuery {
+    fn default() -> Self {
+        Self {
+            page: 1,
+            page_size: 10,
+            min_duration: 0,
+        }
+    }
+}
+
+pub enum ApiError {
+    BadRequest(String),
+    NotFound,
+    Internal(anyhow::Error),
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(err: anyhow::Error) -> Self {
+        Self::Internal(err)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            ApiError::BadRequest(message) => (StatusCode::BAD_REQUEST, message),
+            ApiError::NotFound => (StatusCode::NOT_FOUND, "not found".to_string()),
+            ApiError::Internal(err) => {
+                error!("api error: {:?}", err);
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal error".to_string())
+            },
+        };
+
+        (status, Json(json!({ "error": message }))).into_response()
+    }
+}
+
+pub fn router() -> Router<Arc<ServerState>> {
+    Router::new()
+        .route("/encounters", get(list_encounters))
+        .route("/encounters/search", post(search_encounters))
+        .route("/encounters/favorites", get(list_favorites))
+        .route("/encounters/{id}", get(get_encounter))
+        .route("/stats", get(get_stats))
+}
+
+async fn list_encounters(
+    State(state): State<Arc<ServerState>>,
+    Query(query): Query<PageQuery>) -> Result<Json<EncountersOverview>, ApiError> {
+    let query = PreviewQuery {
+        page: query.page,
+        page_size: query.page_size,
+        ..Default::default()
+    };
+
+    search(&state, query).await
+}
+
+async fn list_favorites(
+    State(state): State<Arc<ServerState>>,
+    Query(query): Query<PageQuery>) -> Result<Json<EncountersOverview>, ApiError> {
+    let mut query = PreviewQuery {
+        page: query.page,
+        page_size: query.page_size,
+        ..Default::default()
+    };
+    query.filter.favorite = true;
+
+    search(&state, query).await
+}
+
+async fn search_encounters(
+    State(state): State<Arc<ServerState>>,
+    Json(query): Json<PreviewQuery>) -> Result<Json<EncountersOverview>, ApiError> {
+    search(&state, query).await
+}
+
+async fn search(state: &ServerState, mut query: PreviewQuery) -> Result<Json<EncountersOverview>, ApiError> {
+    if query.page < 1 || !(1..=MAX_PAGE_SIZE).contains(&query.page_size) {
+        return Err(ApiError::BadRequest(format!("page must be >= 1 and pageSize within 1..={}", MAX_PAGE_SIZE)));
+    }
+
+    let filter = &mut query.filter;
+
+    if filter.sort.is_empty() {
+        filter.sort = "fight_start".to_string();
+    }
+
+    // checked here as well so clients get a 400 instead of a failed query
+    SortColumn::parse(&filter.sort).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+    SortOrder::parse(&filter.order).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+
+    let (encounters, total_encounters) = state.database.get()
+        .load_encounters_preview(query.page, query.page_size, query.search, query.filter)
+        .await?;
+
+    Ok(Json(EncountersOverview {
+        encounters,
+        total_encounters,
+    }))
+}
+
+async fn get_encounter(
+    State(state): State<Arc<ServerState>>,
+    Path(id): Path<i64>) -> Result<Json<Encounter>, ApiError> {
+    let encounter = state.database.get().load_encounter(id.to_string()).await?;
+
+    if encounter.fight_start == 0 {
+        return Err(ApiError::NotFound);
+    }
+
+    Ok(Json(encounter))
+}
+
+async fn get_stats(
+    State(state): State<Arc<ServerState>>,
+    Query(query): Query<PageQuery>) -> Result<Json<EncounterDbInfo>, ApiError> {
+    let database = state.database.get();
+    let (total_encounters, total_encounters_filtered) = database.get_db_stats(query.min_duration).await?;
+    let size = database.get_metadata()?;
+
+    Ok(Json(EncounterDbInfo {
+        size,
+        total_encounters,
+        total_encounters_filtered,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::{to_bytes, Body};
+    use axum::http::{header::AUTHORIZATION, Request};
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    use crate::database::{create_test_database_manager, test_export, DatabaseManager, TempDatabase, TestEncounter};
+    use crate::server::{router, ServerState};
+
+    use super::*;
+
+    const TOKEN: &str = "test-token";
+
+    async fn create_router() -> (Router, Vec<i64>, TempDatabase<Arc<DatabaseManager>>) {
+        let database = create_test_database_manager();
+
+        let encounters = vec![
+            TestEncounter { favorite: true, ..Default::default() }.build(),
+            TestEncounter { fight_start: 1_700_001_000_000, ..Default::default() }.build(),
+        ];
+        let summary = database.get().import_encounters(test_export(encounters)).await.unwrap();
+
+        let state = Arc::new(ServerState::new(TOKEN.to_string(), database.clone()));
+
+        (router(state, false, true), summary.imported, database)
+    }
+
+    async fn get(router: &Router, uri: &str, token: Option<&str>) -> (StatusCode, Value) {
+        let mut request = Request::get(uri);
+
+        if let Some(token) = token {
+            request = request.header(AUTHORIZATION, format!("Bearer {}", token));
+        }
+
+        let response = router.clone().oneshot(request.body(Body::empty()).unwrap()).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+        (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn should_reject_missing_token() {
+        let (router, _, _database) = create_router().await;
+
+        let (status, _) = get(&router, "/encounters", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, _) = get(&router, "/encounters", Some("wrong")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, _) = get(&router, "/encounters", Some("test-tokem")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn should_query_encounters() {
+        let (router, ids, _database) = create_router().await;
+
+        let (status, body) = get(&router, "/encounters?page=1&pageSize=10", Some(TOKEN)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["totalEncounters"], 2);
+        assert_eq!(body["encounters"][0]["id"], ids[1]);
+
+        let (status, body) = get(&router, &format!("/encounters/favorites?token={}", TOKEN), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["totalEncounters"], 1);
+
+        let (status, body) = get(&router, &format!("/encounters/{}", ids[0]), Some(TOKEN)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["localPlayer"], "Alice");
+
+        let (status, _) = get(&router, "/encounters/999", Some(TOKEN)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, body) = get(&router, "/stats", Some(TOKEN)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["totalEncounters"], 2);
+    }
+}
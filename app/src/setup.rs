@@ -3,12 +3,13 @@ use tauri_plugin_opener::OpenerExt;
 use tokio::runtime::Runtime;
 use std::{sync::{
     atomic::{AtomicBool, Ordering},
-    Arc,
+    Arc, Mutex,
 }, thread::{self, JoinHandle}};
 use tauri::{App, AppHandle, Manager};
+use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
 use tauri_plugin_window_state::WindowExt;
 
-use crate::{constants::*, core::background_worker::{BackgroundWorker, BackgroundWorkerArgs}, database::Database, misc::{app_context::AppContext, settings::{Settings, SettingsManager}, system_tray, updater, utils::CommandsManager}, sniffer::PacketSniffer};
+use crate::{constants::*, core::notifications::Notifier, core::{backup, recompression, retention, statistics}, core::background_worker::{BackgroundWorker, BackgroundWorkerArgs}, core::control::{self, WorkerControl}, database::DatabaseManager, misc::{app_context::AppContext, crash, profile::ProfileManager, settings::{Settings, SettingsManager}, system_tray, updater, utils::CommandsManager}, server, sniffer::SnifferKind, sync::SyncManager};
 
 pub fn setup_app(app: &mut App) -> std::result::Result<(), Box<dyn std::error::Error>> {
     system_tray::build(app)?;
@@ -30,29 +31,61 @@ pub fn setup_app(app: &mut App) -> std::result::Result<(), Box<dyn std::error::E
     let app_context = AppContext::new(app_name, resource_path.clone(), version.clone());
     let app_context = Arc::new(app_context);
     app.manage(app_context.clone());
+    crash::init(app_context.crashes_path.clone(), version.clone());
 
     let commands_manager = Arc::new(CommandsManager::new(app_handle.clone(), app_context.clone()));
     app.manage(commands_manager.clone());
 
     let commands_manager_test = app.state::<Arc<CommandsManager>>();
 
-    let settings_manager = SettingsManager::new(app_context.settings_path.clone())?;
-    let settings = settings_manager.lock().unwrap().get()?.clone();
+    let profile_manager = ProfileManager::new(app_context.profiles_path.clone())?;
+    let profile = profile_manager.active().to_string();
+    let profile_paths = app_context.profile_paths(&profile);
+    app.manage(Mutex::new(profile_manager));
+    info!("using profile {}", profile);
+
+    let settings_manager = SettingsManager::new(profile_paths.settings_path.clone())?;
+    let mut settings = settings_manager.lock().unwrap().get()?.clone();
     info!("settings loaded");
+
+    let api_enabled = settings.general.live_api_enabled || settings.general.rest_api_enabled;
+
+    if api_enabled && settings.general.api_token.is_empty() {
+        settings.general.api_token = uuid::Uuid::new_v4().simple().to_string();
+        settings_manager.lock().unwrap().save(settings.clone())?;
+    }
+
+    crash::set_settings(&settings);
     app.manage(settings_manager);
 
-    let database = Database::new(app_context.database_path.clone());
+    let database = DatabaseManager::open(app_context.migration_path.clone(), profile, profile_paths.database_path);
     let database = Arc::new(database);
 
-    let migration_path = resource_path.join("assets/migration");
-    match database.setup(migration_path) {
-        Ok(_) => (),
-        Err(e) => {
-            warn!("error setting up database: {}", e);
-        }
+    if let Some(err) = database.setup_error() {
+        app.dialog()
+            .message(format!("The database could not be updated, logs may fail to load or save.\n\n{err}"))
+            .title("Database error")
+            .kind(MessageDialogKind::Error)
+            .show(|_| {});
     }
 
     app.manage(database.clone());
+    app.manage(Arc::new(SyncManager::new(database.clone())));
+    app.manage(Arc::new(Notifier::new()));
+
+    let (worker_handle, worker_control) = control::channel();
+    worker_handle.setup_listeners(&app_handle);
+    worker_handle.forward_status(app_handle.clone());
+    app.manage(worker_handle);
+
+    tauri::async_runtime::spawn(retention::run_retention(app_handle.clone(), database.clone()));
+    tauri::async_runtime::spawn(recompression::run_recompression(database.get()));
+    tauri::async_runtime::spawn(statistics::run_ranking_backfill(database.get()));
+    tauri::async_runtime::spawn(backup::run_backups(app_handle.clone(), database.clone()));
+
+    if api_enabled {
+        server::start(app_handle.clone(), &settings.general, database.clone());
+    }
 
     info!("starting app v{}", app_context.version);
 
@@ -78,7 +111,8 @@ pub fn setup_app(app: &mut App) -> std::result::Result<(), Box<dyn std::error::E
             app_context,
             app_handle,
             commands_manager,
-            database).await;
+            database,
+            worker_control).await;
 
         match result {
             Ok(_) => {
@@ -130,7 +164,8 @@ pub async fn on_launch(
     app_context: Arc<AppContext>,
     app_handle: AppHandle,
     commands_manager: Arc<CommandsManager>,
-    database: Arc<Database>
+    database: Arc<DatabaseManager>,
+    control: WorkerControl
 ) -> anyhow::Result<()> {
     if settings.general.start_loa_on_start {
         info!("auto launch game enabled");
@@ -146,26 +181,12 @@ pub async fn on_launch(
         std::thread::sleep(std::time::Duration::from_millis(100));
     }
 
-    info!("listening on port: {}", PORT);
-    let mut packet_sniffer: Box<dyn PacketSniffer>;
-
-    #[cfg(feature = "meter-core")]
-    {
-        use crate::sniffer::WindivertSniffer;
-        packet_sniffer = Box::new(WindivertSniffer::new());
-    }
-
-    #[cfg(feature = "fake")]
-    {
-        use crate::sniffer::FakeSniffer;
-        packet_sniffer = Box::new(FakeSniffer::new());
-    }
-
     let args = BackgroundWorkerArgs {
         app: app_handle,
         context: app_context,
         database,
-        packet_sniffer,
+        control,
+        sniffer: SnifferKind::default(),
         port,
         settings,
         version
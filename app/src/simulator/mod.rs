@@ -1,6 +1,6 @@
 mod models;
 mod utils;
-mod packet;
+pub mod packet;
 mod id_generator;
 
 use std::{fs::{self, File}, path::{Path, PathBuf}, str::FromStr, sync::mpsc, thread::sleep, time::Duration, vec};
@@ -18,6 +18,9 @@ static CONFIG: config::Configuration = config::standard();
 
 pub type Packet = (Pkt, Vec<u8>);
 
+/// every player hits the boss once per tick, the boss dies after about this many ticks
+const FIGHT_TICKS: i64 = 40;
+
 pub struct Simulator {
     id_generator: IdGenerator,
     rng: ThreadRng,
@@ -25,6 +28,17 @@ pub struct Simulator {
     ids: HashSet<u64>,
     instance_ids: HashSet<u32>,
     character_ids: HashSet<u64>,
+    raid_instance_id: u32,
+    players: Vec<u64>,
+    boss: Option<SimulatedBoss>,
+    started_on: Option<DateTime<Utc>>,
+}
+
+struct SimulatedBoss {
+    object_id: u64,
+    npc_id: u32,
+    hp: i64,
+    max_hp: i64,
 }
 
 
@@ -42,18 +56,89 @@ impl Simulator {
             ids: HashSet::new(),
             instance_ids: HashSet::new(),
             character_ids: HashSet::new(),
+            raid_instance_id: 0,
+            players: vec![],
+            boss: None,
+            started_on: None,
         })
     }
 
-    pub fn setup(&mut self) -> Result<()> {
-        Ok(())
-    }
+    /// the packets of entering the zone, damage is encrypted with the key of `ZONE_INSTANCE_ID`
+    pub fn setup(&mut self) -> Result<Vec<Packet>> {
+        let mut packets = vec![encode_new_transit(ZONE_INSTANCE_ID)];
+        self.raid_instance_id = self.id_generator.resolve_u32(&self.data.raid.id);
+
+        let members: Vec<EncounterTemplatePartyMember> = self.data.raid.parties
+            .iter()
+            .flat_map(|party| party.members.clone())
+            .collect();
+
+        for member in members {
+            let player_id = self.id_generator.new_u64();
+            let character_id = self.id_generator.new_u64();
+            let class_id = self.id_generator.resolve_class(&member.class_id) as u32;
+            let gear_level = match parse_range(&member.gear_score) {
+                Some((min, max)) => self.rng.random_range(min..=max),
+                None => member.gear_score.parse()?,
+            };
+
+            let packet = if member.name == self.data.local_player {
+                encode_init_pc(player_id, member.name, class_id, gear_level, character_id, member.hp as i64)
+            } else {
+                let name = self.id_generator.resolve_nickname(&member.name);
+                encode_new_pc(player_id, name, class_id, gear_level, character_id, member.hp as i64)
+            };
+
+            packets.push(packet);
+            self.players.push(player_id);
+        }
 
-    pub fn tick(&mut self, now: DateTime<Utc>) -> Option<Vec<Packet>> {
-        None
+        let boss = SimulatedBoss {
+            object_id: self.id_generator.new_u64(),
+            npc_id: self.data.boss.id,
+            hp: self.data.boss.hp as i64,
+            max_hp: self.data.boss.hp as i64,
+        };
+        packets.push(encode_new_npc(boss.object_id, boss.npc_id, self.data.boss.level, None, boss.max_hp));
+        packets.push(encode_trigger_boss_battle_status(self.id_generator.new_u32(), 1, false));
+        self.boss = Some(boss);
+
+        Ok(packets)
     }
 
+    /// the damage of one round, the round that kills the boss ends with the kill and the result,
+    /// `None` once the fight is over
+    pub fn tick(&mut self, now: DateTime<Utc>) -> Option<Vec<Packet>> {
+        let boss = self.boss.as_mut()?;
+        let started_on = *self.started_on.get_or_insert(now);
+        let hit = (boss.max_hp / FIGHT_TICKS / self.players.len().max(1) as i64).max(1);
+        let mut packets = vec![];
+
+        for &player_id in &self.players {
+            let hit_flag = if self.rng.random_bool(0.5) { HitFlag::Critical } else { HitFlag::Normal };
+            let damage = ((hit as f64 * self.rng.random_range(0.5..1.5)) as i64).min(boss.hp);
+            boss.hp -= damage;
+
+            packets.push(encode_skill_damage_packet(
+                player_id,
+                0,
+                boss.object_id,
+                hit_flag,
+                HitOption::None,
+                boss.hp,
+                boss.max_hp,
+                damage));
+
+            if boss.hp == 0 {
+                packets.push(encode_raid_boss_kill_notify(boss.npc_id, now.timestamp_millis()));
+                packets.push(encode_raid_result(self.raid_instance_id, 0, (now - started_on).num_milliseconds()));
+                self.boss = None;
+                break;
+            }
+        }
 
+        Some(packets)
+    }
 }
 
 #[cfg(test)]
@@ -61,19 +146,24 @@ mod tests {
     use super::*;
 
     #[test]
-    fn should_load_encounter_template() {
-        let path = PathBuf::from(r#"C:\repos\drama-meter\app\assets\templates\mordum_g3.json"#);
+    fn should_play_template_until_the_kill() {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("assets/templates/mordum_g3.json");
         let mut simulator = Simulator::new(&path).unwrap();
 
-        simulator.setup().unwrap();
+        let setup = simulator.setup().unwrap();
+        assert_eq!(setup[0].0, Pkt::NewTransit);
+        assert!(setup.iter().any(|(op, _)| *op == Pkt::InitPC));
 
-        loop {
-            let now = Utc::now();
-            let packets = simulator.tick(now);
+        let mut now = Utc::now();
+        let mut packets = vec![];
 
-            for packet in packets {
-                
-            }
+        while let Some(tick) = simulator.tick(now) {
+            packets.extend(tick);
+            now += chrono::Duration::seconds(1);
+            assert!(packets.len() < 10_000);
         }
+
+        let ops: Vec<Pkt> = packets.iter().rev().take(2).map(|(op, _)| *op).collect();
+        assert_eq!(ops, vec![Pkt::RaidResult, Pkt::RaidBossKillNotify]);
     }
-}
\ No newline at end of file
+}
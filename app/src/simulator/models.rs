@@ -20,7 +20,7 @@ pub struct EncounterTemplateBoss {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct EncounterTemplateSidereal {
     pub id: u32,
-    pub damage: i64,
+    pub damage: f64,
     #[serde(deserialize_with = "parse_duration_hms")]
     pub appears_after: Duration,
     #[serde(deserialize_with = "parse_duration_hms")]
@@ -1,7 +1,16 @@
 use bincode::encode_to_vec;
 use meter_core::packets::{definitions::*, opcodes::Pkt, structures::{NpcStruct, NpcStructBalance, SkillDamageEvent, StatPair, StatusEffectData}};
 
-use crate::{models::{HitFlag, HitOption}, simulator::{utils::encode_modifier, Packet, CONFIG}};
+use crate::{core::decryption::XorDecryptor, models::{HitFlag, HitOption}, simulator::{utils::encode_modifier, Packet, CONFIG}};
+
+/// the fake sniffer decrypts damage with the key of this zone, sent with `encode_new_transit`
+pub const ZONE_INSTANCE_ID: u32 = 0x5EED;
+
+pub fn encode_new_transit(channel_id: u32) -> Packet {
+    let pkt = PKTNewTransit { channel_id };
+    let bytes = encode_to_vec(pkt, CONFIG).unwrap();
+    (Pkt::NewTransit, bytes)
+}
 
 pub fn encode_skill_damage_packet(
     source_id: u64,
@@ -13,17 +22,20 @@ pub fn encode_skill_damage_packet(
     max_boss_hp: i64,
     damage: i64,
 ) -> Packet {
-     let packet = PKTSkillDamageNotify {
+    let mut event = SkillDamageEvent {
+        target_id,
+        modifier: encode_modifier(hit_flag, hit_option),
+        cur_hp: current_boss_hp,
+        max_hp: max_boss_hp,
+        damage: damage,
+        ..Default::default()
+    };
+    XorDecryptor::encrypt(&mut event, ZONE_INSTANCE_ID);
+
+    let packet = PKTSkillDamageNotify {
         source_id,
         skill_id,
-        skill_damage_events: vec![SkillDamageEvent {
-            target_id,
-            modifier: encode_modifier(hit_flag, hit_option),
-            cur_hp: current_boss_hp,
-            max_hp: max_boss_hp,
-            damage: damage,
-            ..Default::default()
-        }],
+        skill_damage_events: vec![event],
         skill_effect_id: None,
     };
 
@@ -266,4 +278,22 @@ pub fn encode_status_effect_remove_notify(object_id: u64, reason: u8, id: u32) -
 
     let bytes = encode_to_vec(packet, CONFIG).unwrap();
     (Pkt::StatusEffectRemoveNotify, bytes)
-}
\ No newline at end of file
+}
+
+pub fn encode_raid_boss_kill_notify(npc_id: u32, kill_time: i64) -> Packet {
+    let pkt = PKTRaidBossKillNotify { npc_id, kill_time };
+    let bytes = encode_to_vec(pkt, CONFIG).unwrap();
+    (Pkt::RaidBossKillNotify, bytes)
+}
+
+pub fn encode_raid_result(raid_instance_id: u32, raid_result: u8, total_time: i64) -> Packet {
+    let pkt = PKTRaidResult { raid_instance_id, raid_result, total_time };
+    let bytes = encode_to_vec(pkt, CONFIG).unwrap();
+    (Pkt::RaidResult, bytes)
+}
+
+pub fn encode_trigger_boss_battle_status(trigger_id: u32, step: u32, skip_battle_phase: bool) -> Packet {
+    let pkt = PKTTriggerBossBattleStatus { trigger_id, step, skip_battle_phase };
+    let bytes = encode_to_vec(pkt, CONFIG).unwrap();
+    (Pkt::TriggerBossBattleStatus, bytes)
+}
@@ -54,14 +54,21 @@ pub fn process(tx: Sender<Packet>, template_path: PathBuf, destination_dir: Path
 
         let mut simulator = Simulator::new(&path).unwrap();
 
-        simulator.setup();
+        // the zone comes first, damage can't be decrypted without its key
+        for packet in simulator.setup().unwrap() {
+            if tx.send(packet).is_err() {
+                return;
+            }
+        }
 
         let mut now = Utc::now();
 
         while let Some(packets) = simulator.tick(now) {
             for packet in packets {
-                
-                tx.send(packet).unwrap();
+                // the worker dropped the receiver, it switched to another sniffer
+                if tx.send(packet).is_err() {
+                    return;
+                }
                 sleep(Duration::from_millis(500));
             }
             sleep(Duration::from_millis(500));
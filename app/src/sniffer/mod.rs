@@ -3,6 +3,7 @@ mod windivert;
 
 use anyhow::*;
 use meter_core::packets::opcodes::Pkt;
+use serde::{Deserialize, Serialize};
 use std::{error::Error, sync::mpsc::{self, Receiver, Sender}};
 
 pub use fake::FakeSniffer;
@@ -10,4 +11,33 @@ pub use windivert::WindivertSniffer;
 
 pub trait PacketSniffer : Send + Sync {
     fn start(&mut self, port: u16, region_file_path: String) -> Result<Receiver<(Pkt, Vec<u8>)>>;
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SnifferKind {
+    Windivert,
+    Fake,
+}
+
+impl Default for SnifferKind {
+    /// the fake sniffer wins if both are compiled in, like it always has
+    fn default() -> Self {
+        if cfg!(feature = "fake") {
+            SnifferKind::Fake
+        } else {
+            SnifferKind::Windivert
+        }
+    }
+}
+
+pub fn create(kind: SnifferKind) -> Result<Box<dyn PacketSniffer>> {
+    match kind {
+        #[cfg(feature = "meter-core")]
+        SnifferKind::Windivert => Ok(Box::new(WindivertSniffer::new())),
+        #[cfg(feature = "fake")]
+        SnifferKind::Fake => Ok(Box::new(FakeSniffer::new())),
+        #[allow(unreachable_patterns)]
+        kind => bail!("the {:?} sniffer is not part of this build", kind),
+    }
+}
@@ -0,0 +1,364 @@
+mod target;
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use log::*;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, EventTarget};
+use tokio::sync::watch;
+
+use crate::database::{Database, DatabaseManager};
+use crate::misc::settings::{SyncSettings, SyncTargetKind};
+
+pub use target::*;
+
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncProgress {
+    pub total: usize,
+    pub processed: usize,
+    pub uploaded: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub current: Option<i32>,
+    pub finished: bool,
+    pub cancelled: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadResult {
+    pub upstream: Option<String>,
+    pub duplicate: bool,
+    pub skipped: bool,
+    pub unauthorized: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SyncOptions {
+    pub force_resync: bool,
+    pub min_interval: Duration,
+    pub max_retries: u32,
+}
+
+impl SyncOptions {
+    pub fn new(settings: &SyncSettings, force_resync: bool) -> Self {
+        Self {
+            force_resync,
+            min_interval: Duration::from_millis(settings.min_interval_ms),
+            max_retries: settings.max_retries,
+        }
+    }
+}
+
+/// at most one sync runs at a time, the sender cancels it
+pub struct SyncManager {
+    database: Arc<DatabaseManager>,
+    running: Mutex<Option<watch::Sender<bool>>>,
+}
+
+impl SyncManager {
+    pub fn new(database: Arc<DatabaseManager>) -> Self {
+        Self {
+            database,
+            running: Mutex::new(None),
+        }
+    }
+
+    pub fn start(
+        self: &Arc<Self>,
+        app_handle: AppHandle,
+        settings: &SyncSettings,
+        access_token: String,
+        visibility: String,
+        force_resync: bool) -> Result<()> {
+        let options = SyncOptions::new(settings, force_resync);
+
+        match settings.target {
+            SyncTargetKind::Http => {
+                let target = HttpTarget::new(settings.endpoint.clone(), access_token, visibility);
+                self.spawn(app_handle, target, options)
+            }
+            SyncTargetKind::Filesystem => {
+                if settings.directory.is_empty() {
+                    bail!("sync directory is not set");
+                }
+
+                let target = FileTarget::new(PathBuf::from(&settings.directory));
+                self.spawn(app_handle, target, options)
+            }
+        }
+    }
+
+    /// uploads a single encounter right away, regardless of a running sync
+    pub async fn upload(
+        &self,
+        settings: &SyncSettings,
+        access_token: String,
+        visibility: String,
+        id: i32) -> Result<UploadResult> {
+        let options = SyncOptions::new(settings, true);
+        let database = self.database.get();
+
+        match settings.target {
+            SyncTargetKind::Http => {
+                let target = HttpTarget::new(settings.endpoint.clone(), access_token, visibility);
+                upload_encounter(&database, &target, id, &options).await
+            }
+            SyncTargetKind::Filesystem => {
+                let target = FileTarget::new(PathBuf::from(&settings.directory));
+                upload_encounter(&database, &target, id, &options).await
+            }
+        }
+    }
+
+    fn spawn<T: SyncTarget + 'static>(self: &Arc<Self>, app_handle: AppHandle, target: T, options: SyncOptions) -> Result<()> {
+        let mut running = self.running.lock().unwrap();
+
+        if running.is_some() {
+            bail!("a sync is already running");
+        }
+
+        let (cancel, cancelled) = watch::channel(false);
+        *running = Some(cancel);
+
+        let manager = self.clone();
+        // a sync finishes on the database it started with, even if the profile changes meanwhile
+        let database = self.database.get();
+
+        tauri::async_runtime::spawn(async move {
+            let on_progress = |progress: &SyncProgress| {
+                app_handle.emit_to(EventTarget::Any, "sync-progress", progress).ok();
+            };
+
+            if let Err(err) = run(&database, &target, &options, cancelled, on_progress).await {
+                error!("sync failed: {:?}", err);
+            }
+
+            manager.running.lock().unwrap().take();
+        });
+
+        Ok(())
+    }
+
+    /// returns false if nothing was running
+    pub fn cancel(&self) -> bool {
+        match self.running.lock().unwrap().as_ref() {
+            Some(cancel) => cancel.send(true).is_ok(),
+            None => false,
+        }
+    }
+}
+
+pub async fn run<T: SyncTarget>(
+    database: &Database,
+    target: &T,
+    options: &SyncOptions,
+    mut cancelled: watch::Receiver<bool>,
+    mut on_progress: impl FnMut(&SyncProgress)) -> Result<SyncProgress> {
+    let ids = database.get_sync_candidates(options.force_resync).await?;
+
+    let mut progress = SyncProgress {
+        total: ids.len(),
+        ..Default::default()
+    };
+    on_progress(&progress);
+
+    for (index, id) in ids.into_iter().enumerate() {
+        if *cancelled.borrow() {
+            progress.cancelled = true;
+            break;
+        }
+
+        if index > 0 && !sleep(options.min_interval, &mut cancelled).await {
+            progress.cancelled = true;
+            break;
+        }
+
+        progress.current = Some(id);
+        let encounter = database.load_encounter(id.to_string()).await?;
+
+        if !target.accepts(&encounter) {
+            progress.skipped += 1;
+        } else {
+            match upload(target, id, &encounter, options, &mut cancelled).await {
+                Ok(Some(UploadOutcome::Uploaded(upstream))) | Ok(Some(UploadOutcome::Duplicate(upstream))) => {
+                    info!("synced encounter {} upstream: {}", id, upstream);
+                    database.insert_sync_log(id, upstream, false).await?;
+                    progress.uploaded += 1;
+                }
+                Ok(Some(UploadOutcome::Rejected(reason))) => {
+                    warn!("encounter {} was rejected: {}", id, reason);
+                    database.insert_sync_log(id, "0".to_string(), true).await?;
+                    progress.failed += 1;
+                }
+                Ok(None) => {
+                    progress.cancelled = true;
+                    break;
+                }
+                Err(UploadError::Unauthorized) => {
+                    progress.error = Some(UploadError::Unauthorized.to_string());
+                    break;
+                }
+                Err(err) => {
+                    // no sync log, the next sync picks it up again
+                    warn!("could not sync encounter {}: {:?}", id, err);
+                    progress.failed += 1;
+                }
+            }
+        }
+
+        progress.processed += 1;
+        on_progress(&progress);
+    }
+
+    progress.current = None;
+    progress.finished = true;
+    on_progress(&progress);
+
+    Ok(progress)
+}
+
+pub async fn upload_encounter<T: SyncTarget>(
+    database: &Database,
+    target: &T,
+    id: i32,
+    options: &SyncOptions) -> Result<UploadResult> {
+    let encounter = database.load_encounter(id.to_string()).await?;
+    let (_cancel, mut cancelled) = watch::channel(false);
+    let mut result = UploadResult::default();
+
+    if !target.accepts(&encounter) {
+        result.skipped = true;
+        return Ok(result);
+    }
+
+    match upload(target, id, &encounter, options, &mut cancelled).await {
+        Ok(Some(UploadOutcome::Uploaded(upstream))) => {
+            database.insert_sync_log(id, upstream.clone(), false).await?;
+            result.upstream = Some(upstream);
+        }
+        Ok(Some(UploadOutcome::Duplicate(upstream))) => {
+            database.insert_sync_log(id, upstream.clone(), false).await?;
+            result.upstream = Some(upstream);
+            result.duplicate = true;
+        }
+        Ok(Some(UploadOutcome::Rejected(reason))) => {
+            database.insert_sync_log(id, "0".to_string(), true).await?;
+            result.error = Some(reason);
+        }
+        Ok(None) => {}
+        Err(err) => {
+            result.unauthorized = matches!(err, UploadError::Unauthorized);
+            result.error = Some(err.to_string());
+        }
+    }
+
+    Ok(result)
+}
+
+/// retries transient errors with exponential backoff, `None` if cancelled while waiting
+async fn upload<T: SyncTarget>(
+    target: &T,
+    id: i32,
+    encounter: &crate::models::Encounter,
+    options: &SyncOptions,
+    cancelled: &mut watch::Receiver<bool>) -> Result<Option<UploadOutcome>, UploadError> {
+    let mut attempt = 0;
+
+    loop {
+        let delay = match target.upload(id, encounter).await {
+            Ok(outcome) => return Ok(Some(outcome)),
+            Err(UploadError::Unauthorized) => return Err(UploadError::Unauthorized),
+            Err(err) if attempt >= options.max_retries => return Err(err),
+            Err(UploadError::RateLimited(Some(retry_after))) => retry_after,
+            Err(err) => {
+                debug!("retrying encounter {} after {:?}", id, err);
+                Duration::from_secs(1 << attempt.min(6))
+            }
+        };
+
+        attempt += 1;
+
+        if !sleep(delay, cancelled).await {
+            return Ok(None);
+        }
+    }
+}
+
+/// false if the sync was cancelled in the meantime
+async fn sleep(duration: Duration, cancelled: &mut watch::Receiver<bool>) -> bool {
+    if duration.is_zero() {
+        return !*cancelled.borrow();
+    }
+
+    tokio::select! {
+        _ = tokio::time::sleep(duration) => {},
+        _ = cancelled.changed() => {},
+    }
+
+    !*cancelled.borrow()
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+
+    use crate::database::{create_test_database, test_export, TempDatabase, TestEncounter};
+
+    use super::*;
+
+    async fn create_database(count: i64) -> TempDatabase<Arc<Database>> {
+        let database = create_test_database();
+
+        let encounters = (0..count)
+            .map(|index| TestEncounter { fight_start: 1_700_000_000_000 + index * 1_000_000, ..Default::default() }.build())
+            .collect();
+        database.import_encounters(test_export(encounters)).await.unwrap();
+
+        database
+    }
+
+    #[tokio::test]
+    async fn should_retry_and_write_sync_logs() {
+        let database = create_database(2).await;
+        let target = MockTarget::new(vec![
+            UploadError::Other(anyhow!("connection reset")),
+            UploadError::RateLimited(Some(Duration::ZERO)),
+        ]);
+        let options = SyncOptions {
+            force_resync: false,
+            min_interval: Duration::ZERO,
+            max_retries: 3,
+        };
+        let (_cancel, cancelled) = watch::channel(false);
+
+        let progress = run(&database, &target, &options, cancelled, |_| {}).await.unwrap();
+        assert!(progress.finished);
+        assert_eq!(progress.total, 2);
+        assert_eq!(progress.uploaded, 2);
+        assert!(database.get_sync_candidates(false).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn should_stop_when_cancelled() {
+        let database = create_database(1).await;
+        let target = MockTarget::new(vec![]);
+        let options = SyncOptions {
+            force_resync: false,
+            min_interval: Duration::ZERO,
+            max_retries: 0,
+        };
+        let (cancel, cancelled) = watch::channel(false);
+        cancel.send(true).unwrap();
+
+        let progress = run(&database, &target, &options, cancelled, |_| {}).await.unwrap();
+        assert!(target.uploaded.lock().unwrap().is_empty());
+        assert_eq!(progress.processed, 0);
+    }
+}
@@ -0,0 +1,187 @@
+use std::fs;
+use std::future::Future;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+
+use crate::misc::data::RAID_MAP;
+use crate::misc::utils::compress_json;
+use crate::models::Encounter;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UploadOutcome {
+    Uploaded(String),
+    /// the target already has this encounter under the given upstream id
+    Duplicate(String),
+    /// the target refused the encounter, retrying will not help
+    Rejected(String),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum UploadError {
+    #[error("invalid access token")]
+    Unauthorized,
+    #[error("rate limited")]
+    RateLimited(Option<Duration>),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+pub trait SyncTarget: Send + Sync {
+    /// encounters the target is known to refuse are skipped without a sync log
+    fn accepts(&self, _encounter: &Encounter) -> bool {
+        true
+    }
+
+    fn upload(&self, id: i32, encounter: &Encounter) -> impl Future<Output = Result<UploadOutcome, UploadError>> + Send;
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct UploadResponse {
+    id: Option<serde_json::Value>,
+    error: Option<String>,
+    duplicate: Option<serde_json::Value>,
+}
+
+pub struct HttpTarget {
+    client: Client,
+    endpoint: String,
+    access_token: String,
+    visibility: String,
+}
+
+impl HttpTarget {
+    pub fn new(endpoint: String, access_token: String, visibility: String) -> Self {
+        Self {
+            client: Client::new(),
+            endpoint,
+            access_token,
+            visibility,
+        }
+    }
+}
+
+impl SyncTarget for HttpTarget {
+    fn accepts(&self, encounter: &Encounter) -> bool {
+        encounter.cleared
+            && encounter.boss_only_damage
+            && encounter.difficulty.as_ref().is_some_and(|difficulty| !difficulty.is_empty())
+            && RAID_MAP.contains_key(&encounter.current_boss_name)
+    }
+
+    async fn upload(&self, _id: i32, encounter: &Encounter) -> Result<UploadOutcome, UploadError> {
+        let body = compress_json(encounter)?;
+
+        let response = self
+            .client
+            .post(format!("{}/logs/upload", self.endpoint))
+            .header("access_token", &self.access_token)
+            .header("Content-Encoding", "gzip")
+            .header("Content-Type", "application/json")
+            .header("visibility", &self.visibility)
+            .body(body)
+            .send()
+            .await
+            .map_err(anyhow::Error::from)?;
+
+        match response.status() {
+            StatusCode::UNAUTHORIZED => return Err(UploadError::Unauthorized),
+            StatusCode::TOO_MANY_REQUESTS => {
+                let retry_after = response
+                    .headers()
+                    .get("Retry-After")
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.parse().ok())
+                    .map(Duration::from_secs);
+
+                return Err(UploadError::RateLimited(retry_after));
+            }
+            status if !status.is_success() && status != StatusCode::BAD_REQUEST => {
+                let text = response.text().await.unwrap_or_default();
+                return Err(anyhow!("server responded with {}: {}", status, text).into());
+            }
+            _ => {}
+        }
+
+        let body: UploadResponse = response.json().await.map_err(anyhow::Error::from)?;
+
+        if let Some(error) = body.error {
+            return Ok(match body.duplicate {
+                Some(duplicate) => UploadOutcome::Duplicate(to_upstream(&duplicate)),
+                None => UploadOutcome::Rejected(error),
+            });
+        }
+
+        match body.id {
+            Some(id) => Ok(UploadOutcome::Uploaded(to_upstream(&id))),
+            None => Err(anyhow!("upload response is missing the upstream id").into()),
+        }
+    }
+}
+
+fn to_upstream(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// writes gzipped encounters into a folder, e.g. a synced drive
+pub struct FileTarget {
+    directory: PathBuf,
+}
+
+impl FileTarget {
+    pub fn new(directory: PathBuf) -> Self {
+        Self { directory }
+    }
+}
+
+impl SyncTarget for FileTarget {
+    async fn upload(&self, id: i32, encounter: &Encounter) -> Result<UploadOutcome, UploadError> {
+        let file_name = format!("{}_{}.json.gz", encounter.fight_start, id);
+        let path = self.directory.join(&file_name);
+        let temp_path = self.directory.join(format!("{}.tmp", file_name));
+        let bytes = compress_json(encounter)?;
+
+        // rename keeps readers of the folder from seeing half written files
+        fs::create_dir_all(&self.directory).map_err(anyhow::Error::from)?;
+        fs::write(&temp_path, bytes).map_err(anyhow::Error::from)?;
+        fs::rename(&temp_path, &path).map_err(anyhow::Error::from)?;
+
+        Ok(UploadOutcome::Uploaded(file_name))
+    }
+}
+
+#[cfg(test)]
+pub struct MockTarget {
+    /// failures returned before each upload succeeds
+    pub failures: std::sync::Mutex<Vec<UploadError>>,
+    pub uploaded: std::sync::Mutex<Vec<i32>>,
+}
+
+#[cfg(test)]
+impl MockTarget {
+    pub fn new(failures: Vec<UploadError>) -> Self {
+        Self {
+            failures: std::sync::Mutex::new(failures),
+            uploaded: std::sync::Mutex::new(vec![]),
+        }
+    }
+}
+
+#[cfg(test)]
+impl SyncTarget for MockTarget {
+    async fn upload(&self, id: i32, _encounter: &Encounter) -> Result<UploadOutcome, UploadError> {
+        if let Some(error) = self.failures.lock().unwrap().pop() {
+            return Err(error);
+        }
+
+        self.uploaded.lock().unwrap().push(id);
+
+        Ok(UploadOutcome::Uploaded(format!("upstream-{}", id)))
+    }
+}
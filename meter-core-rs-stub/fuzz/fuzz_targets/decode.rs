@@ -0,0 +1,55 @@
+#![no_main]
+
+//! every `PKT*::new`, the first byte picks the decoder and the rest is the payload
+//! run with `cargo fuzz run decode` from `meter-core-rs-stub`
+
+use libfuzzer_sys::fuzz_target;
+use meter_core::packets::definitions::*;
+
+macro_rules! decoders {
+    ($($struct_name:ident),*) => {
+        const DECODERS: &[fn(&[u8]) -> bool] = &[$(|data| $struct_name::new(data).is_ok()),*];
+    };
+}
+
+decoders!(
+    PKTPartyStatusEffectAddNotify,
+    PKTPartyStatusEffectRemoveNotify,
+    PKTPartyStatusEffectResultNotify,
+    PKTStatusEffectAddNotify,
+    PKTStatusEffectRemoveNotify,
+    PKTTriggerStartNotify,
+    PKTZoneMemberLoadStatusNotify,
+    PKTZoneObjectUnpublishNotify,
+    PKTStatusEffectSyncDataNotify,
+    PKTTroopMemberUpdateMinNotify,
+    PKTNewTransit,
+    PKTNewPC,
+    PKTPartyLeaveResult,
+    PKTCounterAttackNotify,
+    PKTDeathNotify,
+    PKTIdentityGaugeChangeNotify,
+    PKTInitEnv,
+    PKTInitPC,
+    PKTNewNpc,
+    PKTNewNpcSummon,
+    PKTNewProjectile,
+    PKTSkillStartNotify,
+    PKTSkillCastNotify,
+    PKTRaidBegin,
+    PKTRaidBossKillNotify,
+    PKTRaidResult,
+    PKTTriggerBossBattleStatus,
+    PKTNewTrap,
+    PKTRemoveObject,
+    PKTPartyInfo,
+    PKTSkillDamageAbnormalMoveNotify,
+    PKTSkillDamageNotify
+);
+
+fuzz_target!(|data: &[u8]| {
+    if let Some((selector, payload)) = data.split_first() {
+        let decode = DECODERS[*selector as usize % DECODERS.len()];
+        decode(payload);
+    }
+});
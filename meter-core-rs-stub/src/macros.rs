@@ -2,7 +2,11 @@ use bincode::config;
 
 use crate::packets::definitions::*;
 
-static CONFIG: config::Configuration = config::standard();
+/// a truncated or corrupted length prefix must fail the decode instead of allocating gigabytes
+pub const MAX_PACKET_SIZE: usize = 1 << 20;
+
+static CONFIG: config::Configuration<config::LittleEndian, config::Varint, config::Limit<MAX_PACKET_SIZE>> =
+    config::standard().with_limit::<MAX_PACKET_SIZE>();
 
 macro_rules! impl_new_default {
     ($($struct_name:ident),*) => {
@@ -45,6 +49,9 @@ impl_new_default!(
     PKTSkillStartNotify,
     PKTSkillCastNotify,
     PKTRaidBegin,
+    PKTRaidBossKillNotify,
+    PKTRaidResult,
+    PKTTriggerBossBattleStatus,
     PKTNewTrap,
     PKTRemoveObject,
     PKTPartyInfo,
@@ -55,22 +62,57 @@ impl_new_default!(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use bincode::{encode_to_vec, decode_from_slice};
     use bincode::config::Configuration;
 
     static CONFIG: Configuration = bincode::config::standard();
     
     macro_rules! test_serialization {
         ($($struct_name:ident),*) => {
-            $(
-                #[test]
-                fn $struct_name() {
-                    let original = $struct_name::default();
-                    let encoded = encode_to_vec(&original, CONFIG).expect("Serialization failed");
-                    let (decoded, _): ($struct_name, _) = 
-                        decode_from_slice(&encoded, CONFIG).expect("Deserialization failed");
-                }
-            )*
+            // one module per packet so the test names say which decoder failed
+            mod packets {
+                $(
+                    mod $struct_name {
+                        use bincode::{encode_to_vec, decode_from_slice};
+                        use proptest::prelude::*;
+                        use crate::packets::definitions::$struct_name as Packet;
+                        use super::super::CONFIG;
+
+                        #[test]
+                        fn default() {
+                            let original = Packet::default();
+                            let encoded = encode_to_vec(&original, CONFIG).expect("Serialization failed");
+                            let (decoded, _): (Packet, _) =
+                                decode_from_slice(&encoded, CONFIG).expect("Deserialization failed");
+                        }
+
+                        proptest! {
+                            #![proptest_config(ProptestConfig::with_cases(64))]
+
+                            #[test]
+                            fn round_trip(original in any::<Packet>()) {
+                                let encoded = encode_to_vec(&original, CONFIG).unwrap();
+                                let decoded = Packet::new(&encoded).unwrap();
+
+                                // no PartialEq on the packets, the bytes have to match instead
+                                prop_assert_eq!(encode_to_vec(&decoded, CONFIG).unwrap(), encoded);
+                            }
+
+                            #[test]
+                            fn truncated(original in any::<Packet>(), cut in any::<prop::sample::Index>()) {
+                                let encoded = encode_to_vec(&original, CONFIG).unwrap();
+                                let len = cut.index(encoded.len());
+
+                                prop_assert!(Packet::new(&encoded[..len]).is_err());
+                            }
+
+                            #[test]
+                            fn random_bytes(data in proptest::collection::vec(any::<u8>(), 0..512)) {
+                                let _ = Packet::new(&data);
+                            }
+                        }
+                    }
+                )*
+            }
         };
     }
 
@@ -99,10 +141,22 @@ mod tests {
         PKTSkillStartNotify,
         PKTSkillCastNotify,
         PKTRaidBegin,
+        PKTRaidBossKillNotify,
+        PKTRaidResult,
+        PKTTriggerBossBattleStatus,
         PKTNewTrap,
         PKTRemoveObject,
         PKTPartyInfo,
         PKTSkillDamageAbnormalMoveNotify,
         PKTSkillDamageNotify
     );
-}
\ No newline at end of file
+
+    #[test]
+    fn should_reject_oversized_lengths() {
+        // a vec prefix claiming u64::MAX elements
+        let data = [253, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+
+        assert!(PKTRemoveObject::new(&data).is_err());
+        assert!(PKTPartyInfo::new(&[&[0, 0][..], &data].concat()).is_err());
+    }
+}
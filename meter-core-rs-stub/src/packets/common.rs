@@ -2,6 +2,7 @@ use serde::{Deserialize, Serialize};
 use bincode::{Decode, Encode};
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct SkillMoveOptionData {
     pub down_time: Option<f32>,
     pub stand_up_time: Option<f32>,
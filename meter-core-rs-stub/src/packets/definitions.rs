@@ -4,16 +4,19 @@ use crate::types::*;
 use super::{common::SkillMoveOptionData, structures::*};
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTCounterAttackNotify {
     pub source_id: u64
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTDeathNotify {
     pub target_id: u64
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTIdentityGaugeChangeNotify {
     pub player_id: EntityId,
     pub identity_gauge1: u32,
@@ -22,11 +25,13 @@ pub struct PKTIdentityGaugeChangeNotify {
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTInitEnv {
     pub player_id: EntityId
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTInitPC {
     pub player_id: EntityId,
     pub name: String,
@@ -38,11 +43,13 @@ pub struct PKTInitPC {
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTNewNpc {
     pub npc_struct: NpcStruct
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTNewNpcSummon {
     pub owner_id: EntityId,
     pub npc_struct: NpcStruct
@@ -50,6 +57,7 @@ pub struct PKTNewNpcSummon {
 
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTNewProjectileInner {
     pub projectile_id: EntityId,
     pub owner_id: EntityId,
@@ -58,11 +66,13 @@ pub struct PKTNewProjectileInner {
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTNewProjectile {
     pub projectile_info: PKTNewProjectileInner
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTNewTrapInner {
     pub object_id: EntityId,
     pub owner_id: EntityId,
@@ -71,32 +81,56 @@ pub struct PKTNewTrapInner {
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTNewTrap {
     pub trap_struct: PKTNewTrapInner
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTRaidBegin {
     pub raid_id: u32,
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
+pub struct PKTRaidBossKillNotify {
+    pub npc_id: NpcId,
+    /// server time in unix ms
+    pub kill_time: i64,
+}
+
+#[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
+pub struct PKTRaidResult {
+    pub raid_instance_id: RaidInstanceId,
+    /// 0 clear, 1 fail
+    pub raid_result: u8,
+    /// ms since the raid began
+    pub total_time: i64,
+}
+
+#[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTRemoveObjectInner {
     pub object_id: EntityId
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTRemoveObject {
     pub unpublished_objects: Vec<PKTRemoveObjectInner>
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTSkillCastNotify {
     pub source_id: EntityId,
     pub skill_id: SkillId,
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone, Copy)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct TripodIndex {
     pub first: u8,
     pub second: u8,
@@ -104,6 +138,7 @@ pub struct TripodIndex {
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone, Copy)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct TripodLevel {
     pub first: u16,
     pub second: u16,
@@ -111,12 +146,14 @@ pub struct TripodLevel {
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTSkillStartNotifyInner {
     pub tripod_index: Option<TripodIndex>,
     pub tripod_level: Option<TripodLevel>,
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTSkillStartNotify {
     pub source_id: EntityId,
     pub skill_id: SkillId,
@@ -124,12 +161,14 @@ pub struct PKTSkillStartNotify {
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTSkillDamageAbnormalMoveNotifyInner {
     pub skill_damage_event: SkillDamageEvent,
     pub skill_move_option_data: SkillMoveOptionData
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTSkillDamageAbnormalMoveNotify {
     pub source_id: EntityId,
     pub skill_damage_abnormal_move_events: Vec<PKTSkillDamageAbnormalMoveNotifyInner>,
@@ -138,6 +177,7 @@ pub struct PKTSkillDamageAbnormalMoveNotify {
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTSkillDamageNotify {
     pub source_id: EntityId,
     pub skill_damage_events: Vec<SkillDamageEvent>,
@@ -146,6 +186,7 @@ pub struct PKTSkillDamageNotify {
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTPartyInfoInner {
     pub name: String,
     pub class_id: ClassId,
@@ -154,6 +195,7 @@ pub struct PKTPartyInfoInner {
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTPartyInfo {
     pub party_instance_id: PartyInstanceId,
     pub raid_instance_id: RaidInstanceId,
@@ -161,18 +203,21 @@ pub struct PKTPartyInfo {
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTPartyLeaveResult {
     pub party_instance_id: PartyInstanceId,
     pub name: String
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTPartyStatusEffectAddNotify {
     pub character_id: u64,
     pub status_effect_datas: Vec<StatusEffectData>
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTPartyStatusEffectRemoveNotify {
     pub character_id: CharacterId,
     pub status_effect_instance_ids: Vec<StatusEffectInstanceId>,
@@ -180,6 +225,7 @@ pub struct PKTPartyStatusEffectRemoveNotify {
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTPartyStatusEffectResultNotify {
     pub raid_instance_id: RaidInstanceId,
     pub party_instance_id: PartyInstanceId,
@@ -187,12 +233,14 @@ pub struct PKTPartyStatusEffectResultNotify {
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTStatusEffectAddNotify {
     pub object_id: EntityId,
     pub status_effect_data: StatusEffectData
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTStatusEffectRemoveNotify {
     pub object_id: EntityId,
     pub status_effect_instance_ids: Vec<StatusEffectInstanceId>,
@@ -200,22 +248,34 @@ pub struct PKTStatusEffectRemoveNotify {
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
+pub struct PKTTriggerBossBattleStatus {
+    pub trigger_id: u32,
+    pub step: u32,
+    pub skip_battle_phase: bool,
+}
+
+#[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTTriggerStartNotify {
     pub signal: u32,
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTZoneMemberLoadStatusNotify {
     pub zone_id: u32,
     pub zone_level: u32
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTZoneObjectUnpublishNotify {
     pub object_id: u64
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTStatusEffectSyncDataNotify {
     pub object_id: EntityId,
     pub status_effect_instance_id: StatusEffectInstanceId,
@@ -224,6 +284,7 @@ pub struct PKTStatusEffectSyncDataNotify {
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTTroopMemberUpdateMinNotify {
     pub character_id: u64,
     pub cur_hp: i64,
@@ -232,11 +293,13 @@ pub struct PKTTroopMemberUpdateMinNotify {
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTNewTransit {
     pub channel_id: u32
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTNewPCInner {
     pub player_id: EntityId,
     pub name: String,
@@ -249,6 +312,7 @@ pub struct PKTNewPCInner {
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct PKTNewPC {
     pub pc_struct: PKTNewPCInner
 }
\ No newline at end of file
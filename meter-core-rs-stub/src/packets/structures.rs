@@ -3,11 +3,13 @@ use bincode::{Decode, Encode};
 use crate::types::*;
 
 #[derive(Debug, Default, Encode, Decode, Serialize, Deserialize, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct StatusEffectDataValue {
     pub bytearray_0: Option<Vec<u8>>,
 }
 
 #[derive(Debug, Default, Encode, Decode, Serialize, Deserialize, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct StatusEffectData {
     
     pub source_id: EntityId,
@@ -20,16 +22,19 @@ pub struct StatusEffectData {
 }
 
 #[derive(Debug, Default, Encode, Decode, Serialize, Deserialize, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct EquipItemData {
 
 }
 
 #[derive(Debug, Default, Encode, Decode, Serialize, Deserialize, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct NpcStructBalance {
     pub value: Option<u16>
 }
 
 #[derive(Debug, Default, Encode, Decode, Serialize, Deserialize, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct NpcStruct {
     pub object_id: EntityId,
     pub type_id: NpcId,
@@ -40,23 +45,27 @@ pub struct NpcStruct {
 }
 
 #[derive(Debug, Default, Encode, Decode, Serialize, Deserialize, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct StatPair {
     pub stat_type: u8,
     pub value: i64
 }
 
 #[derive(Debug, Default, Encode, Decode, Serialize, Deserialize, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct SkillDamageEventInner {
     pub p64_0: Option<i64>
 }
 
 #[derive(Debug, Encode, Decode, Serialize, Deserialize, Default, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct SkillDamageEventShield {
     pub p64_0: Option<i64>
 }
 
 
 #[derive(Debug, Default, Encode, Decode, Serialize, Deserialize, Clone)]
+#[cfg_attr(any(test, feature = "arbitrary"), derive(proptest_derive::Arbitrary))]
 pub struct SkillDamageEvent {
     pub target_id: u64,
     pub damage: i64,
@@ -18,6 +18,19 @@ export const mergeSettings = (defaultSettings: any, storageSettings: any) => {
   }
 };
 
+/**
+ * Save settings in the backend, settings it rejects are shown as a toast.
+ */
+const saveSettings = async (settings: LogSettings) => {
+  try {
+    await invoke("save_settings", { settings: $state.snapshot(settings) });
+  } catch (error) {
+    // the toaster reads the settings store, importing it up front would be circular
+    const { addToast } = await import("$lib/components/Toaster.svelte");
+    addToast({ data: { title: "Settings Error", description: String(error), color: "border-red-500/30" } });
+  }
+};
+
 class Settings {
   app = $state(defaultSettings);
   sync = $state(syncSettings);
@@ -36,7 +49,7 @@ class Settings {
             const settingsFromStorage = JSON.parse(settings) as LogSettings;
             mergeSettings(this.app, settingsFromStorage);
             if (!init) {
-              invoke("save_settings", { settings: this.app });
+              saveSettings(this.app);
             }
           } catch (e) {
             console.error(e);
@@ -130,6 +143,14 @@ export class EncounterFilter {
   favorite = $state(false);
   cleared = $state(false);
   difficulty = $state("");
+  className = $state("");
+  playerName = $state("");
+  spec = $state("");
+  localPlayer = $state("");
+  region = $state("");
+  minGearScore: number | null = $state(null);
+  from = $state("");
+  to = $state("");
   sort: sortColumns = $state("id");
   order: sortOrder = $state("desc");
   minDuration = $derived(settings.app.logs.minEncounterDuration);
@@ -142,6 +163,14 @@ export class EncounterFilter {
     this.favorite = false;
     this.cleared = false;
     this.difficulty = "";
+    this.className = "";
+    this.playerName = "";
+    this.spec = "";
+    this.localPlayer = "";
+    this.region = "";
+    this.minGearScore = null;
+    this.from = "";
+    this.to = "";
     this.sort = "id";
     this.order = "desc";
   }
@@ -189,6 +218,10 @@ export const defaultSettings = {
     miniEdit: true,
     autoShow: false,
     autoHideDelay: 5,
+    liveApiEnabled: false,
+    restApiEnabled: false,
+    apiPort: 6041,
+    apiToken: "",
   },
   shortcuts: {
     hideMeter: "Control+ArrowDown",
@@ -348,7 +381,6 @@ export class SyncProgress {
   uploaded = $state(0);
   total = $state(0);
   message = $state("");
-  stop = $state(false);
 }
 
 export class SkillCastInfo {
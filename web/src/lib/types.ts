@@ -90,6 +90,9 @@ export interface EncounterPreview {
   myDps: number;
   favorite: boolean;
   cleared: boolean;
+  personalBest: boolean;
+  personalPercentile?: number | null;
+  localPercentile?: number | null;
 }
 
 export interface EncounterDamageStats {
@@ -114,6 +117,8 @@ export interface EncounterMisc {
   bossHpLog?: { [key: string]: Array<BossHpLog> };
   partyInfo?: PartyInfo;
   rdpsValid?: boolean;
+  recovered?: boolean;
+  damageInvalidReason?: string;
   rdpsMessage?: string;
   region?: string;
 }
@@ -521,6 +526,109 @@ export interface EncounterDbInfo {
   totalEncountersFiltered: number;
 }
 
+export interface WebhookSettings {
+  name: string;
+  url: string;
+  enabled: boolean;
+  format: "discord" | "json";
+  template: string;
+  raids: string[];
+  clearedOnly: boolean;
+  personalBestOnly: boolean;
+}
+
+export interface RetentionSettings {
+  enabled: boolean;
+  maxAgeDays: number | null;
+  maxPerBoss: number | null;
+  maxSizeMb: number | null;
+  keepFavorites: boolean;
+  keepCleared: boolean;
+  keepPersonalBests: boolean;
+  intervalHours: number;
+}
+
+export interface RetentionReport {
+  dryRun: boolean;
+  total: number;
+  entries: { id: number; fightStart: number; boss: string; reason: "maxAge" | "maxPerBoss" | "maxSize" }[];
+}
+
+export interface BackupInfo {
+  name: string;
+  reason: "scheduled" | "migration" | "manual" | "restore";
+  createdOn: number;
+  size: number;
+}
+
+export type WorkerCommand =
+  | { type: "reset" | "togglePause" | "save" | "startRecording" | "stopRecording" | "shutdown" }
+  | { type: "setBossOnlyDamage"; value: boolean }
+  | { type: "setUpdateInterval" | "setPort"; value: number }
+  | { type: "switchSniffer"; value: "windivert" | "fake" };
+
+export interface WorkerStatus {
+  state: "starting" | "waitingForAssets" | "running" | "snifferError" | "stopped" | "failed";
+  message?: string;
+  sniffer: "windivert" | "fake";
+  port: number;
+  paused: boolean;
+  bossOnlyDamage: boolean;
+  updateIntervalMs: number;
+  recording: string | null;
+}
+
+export interface DiagnosticsReport {
+  since: number;
+  opcodes: Array<{ opcode: string; count: number; bytes: number; avgMicros: number; maxMicros: number }>;
+  decodeErrors: Array<{ opcode: string; count: number; message: string; sample: string; lastSeen: number }>;
+  unknownNpcs: number[];
+  unknownSkills: number[];
+  unknownBuffs: number[];
+  latency: { samples: number; p50: number; p90: number; p99: number; max: number };
+}
+
+export interface CrashReportInfo {
+  name: string;
+  createdOn: number;
+  version: string;
+  message: string;
+  location: string;
+  hasPackets: boolean;
+}
+
+export interface Profile {
+  name: string;
+  createdOn: number;
+}
+
+export interface ProfileList {
+  active: string;
+  profiles: Profile[];
+}
+
+export interface UploadResult {
+  upstream: string | null;
+  duplicate: boolean;
+  skipped: boolean;
+  unauthorized: boolean;
+  error: string | null;
+}
+
+export interface SyncProgressEvent {
+  payload: {
+    total: number;
+    processed: number;
+    uploaded: number;
+    skipped: number;
+    failed: number;
+    current: number | null;
+    finished: boolean;
+    cancelled: boolean;
+    error: string | null;
+  };
+}
+
 export class SearchFilter {
   bosses: Set<string>;
   encounters: Set<string>;
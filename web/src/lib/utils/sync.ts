@@ -1,11 +1,9 @@
 import { addToast } from "$lib/components/Toaster.svelte";
 import { settings } from "$lib/stores.svelte";
-import type { Encounter } from "$lib/types";
+import type { Encounter, UploadResult } from "$lib/types";
 import { invoke } from "@tauri-apps/api/core";
-import pako from "pako";
 import { uploadError, uploadTokenError } from "./toasts";
 import { info, warn } from "@tauri-apps/plugin-log";
-import { getAppContext } from "$lib/context";
 
 export const API_URL = "https://api.snow.xyz";
 // export const API_URL = "http://localhost:5180";
@@ -20,59 +18,43 @@ export async function uploadLog(raidGates: Record<string, string>, id: number |
     return;
   }
 
-  const jsonString = JSON.stringify(encounter);
-  const compressedData = pako.gzip(jsonString);
-  const blob = new Blob([compressedData as any], { type: "application/octet-stream" });
+  const result = (await invoke("upload_encounter", {
+    id: Number(id),
+    accessToken: settings.sync.accessToken,
+    visibility: settings.sync.visibility ?? ""
+  })) as UploadResult;
 
-  const resp = await fetch(API_URL + "/logs/upload", {
-    method: "POST",
-    headers: {
-      access_token: settings.sync.accessToken,
-      "Content-Encoding": "gzip",
-      "Content-Type": "application/json",
-      visibility: settings.sync.visibility ?? ""
-    },
-    body: blob
-  });
-
-  // basic errors
-  if (!resp.ok && resp.status !== 400) {
-    let error = "";
-    if (resp.status == 401) {
-      error = "invalid access token";
-      if (showToast) addToast(uploadTokenError);
-    } else {
-      error = await resp.text();
-      if (showToast) addToast(uploadError("server bwonk", id));
-    }
-
-    await info(`couldn't upload encounter ${id} (${encounter.currentBossName}) - error: ${error}`);
+  if (result.unauthorized) {
+    if (showToast) addToast(uploadTokenError);
+    await info(`couldn't upload encounter ${id} (${encounter.currentBossName}) - error: invalid access token`);
     return;
   }
 
-  // handle response
-  const body = await resp.json();
-  // failed server side encounter validation
-  if (body.error) {
-    if (body.duplicate) {
-      const duplicate = body.duplicate;
-      await info(`did not upload duplicate encounter ${id} (${encounter.currentBossName}) using existing upstream: ${duplicate}`);
-      await invoke("sync", { encounter: Number(id), upstream: duplicate.toString(), failed: false });
-      return duplicate;
-    }
-
-    await warn(`couldn't upload encounter ${id} (${encounter.currentBossName}) - error: ${body.error.toLowerCase()}`);
-    if (showToast && !body.error.includes("Boss not supported")) addToast(uploadError(body.error, id));
-    await invoke("sync", { encounter: Number(id), upstream: "0", failed: true });
+  if (result.error) {
+    await warn(`couldn't upload encounter ${id} (${encounter.currentBossName}) - error: ${result.error.toLowerCase()}`);
+    if (showToast && !result.error.includes("Boss not supported")) addToast(uploadError(result.error, id));
     return;
   }
 
-  // successful upload
-  const upstream = body.id;
+  if (result.duplicate) {
+    await info(`did not upload duplicate encounter ${id} (${encounter.currentBossName}) using existing upstream: ${result.upstream}`);
+  } else if (result.upstream) {
+    await info(`uploaded encounter ${id} (${encounter.currentBossName}) upstream: ${result.upstream}`);
+  }
+
+  return result.upstream ?? undefined;
+}
+
+export async function startSync(force: boolean) {
+  await invoke("start_sync", {
+    accessToken: settings.sync.accessToken,
+    visibility: settings.sync.visibility ?? "",
+    forceResync: force
+  });
+}
 
-  await info(`uploaded encounter ${id} (${encounter.currentBossName}) upstream: ${upstream}`);
-  await invoke("sync", { encounter: Number(id), upstream: upstream.toString(), failed: false });
-  return upstream;
+export async function cancelSync() {
+  return (await invoke("cancel_sync")) as boolean;
 }
 
 export async function checkAccessToken(accessToken: string) {
@@ -14,6 +14,15 @@ export const networkSettingsChanged: AddToastProps<ToastData> = {
   closeDelay: 20000 // 20 seconds
 };
 
+export const apiSettingsChanged: AddToastProps<ToastData> = {
+  data: {
+    title: "API Settings Changed",
+    description: "The local API will not start or stop until the app is restarted.",
+    color: error
+  },
+  closeDelay: 20000 // 20 seconds
+};
+
 export const screenshotError: AddToastProps<ToastData> = {
   data: {
     title: "Screenshot Error",